@@ -26,7 +26,7 @@ fn bench_complete_data_payload_fhdr(c: &mut Criterion) {
 
                 assert_eq!(fctrl.f_opts_len(), 0);
 
-                assert!(!fctrl.f_pending(), "no f_pending");
+                assert!(!fctrl.class_b(), "no class_b");
 
                 assert!(!fctrl.ack(), "no ack");
 
@@ -81,8 +81,8 @@ fn bench_complete_data_payload_decrypt(c: &mut Criterion) {
             let mut data = data_payload();
 
             if let Ok(mac_payload) = Uplink::try_mut_from_bytes(&mut data) {
-                mac_payload.encrypt(&mut crypto, 1, 5);
-                assert_eq!(&mac_payload.frm_payload().data[..5], b"hello");
+                mac_payload.encrypt(&mut crypto, 1, 5).unwrap();
+                assert_eq!(&mac_payload.frm_payload().unwrap().data[..5], b"hello");
             } else {
                 panic!("failed to parse DataPayload");
             }
@@ -0,0 +1,72 @@
+//! Decodes arbitrary bytes as every frame type and MAC command list, which must never panic
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use lorawan_encoding::{
+    crypto::soft::SoftCrypto,
+    mac_command::{
+        certification::downlink::CertificationDownlinkMacCommand,
+        downlink::DownlinkMacCommandDecoder,
+    },
+    multicast::downlink::MulticastDownlinkCommandDecoder,
+    phy_payload::{
+        join_accept::JoinAccept,
+        mac_payload::{downlink::Downlink, uplink::Uplink, MacPayload, Mhdr},
+    },
+    types::{AppKey, AppSKey, NwkSKey},
+};
+use zerocopy::{FromBytes, TryFromBytes};
+
+fn crypto() -> SoftCrypto {
+    SoftCrypto::new(
+        NwkSKey::read_from_bytes(&[1; 16]).unwrap(),
+        AppSKey::read_from_bytes(&[2; 16]).unwrap(),
+    )
+}
+
+fn mac_payload<MHDR: Mhdr>(payload: &MacPayload<MHDR>) {
+    let _ = payload.f_ctrl();
+    let _ = payload.f_cnt();
+    let _ = payload.dev_addr();
+    let _ = payload.f_port();
+    let _ = payload.frm_payload();
+    let _ = payload.mic();
+}
+
+fuzz_target!(|data: &[u8]| {
+    // without any checks, as a received buffer may be cast directly
+    if let Ok(uplink) = Uplink::try_ref_from_bytes(data) {
+        mac_payload(uplink);
+    }
+    if let Ok(downlink) = Downlink::try_ref_from_bytes(data) {
+        mac_payload(downlink);
+        downlink.mac_commands().map(|cmds| cmds.count());
+    }
+    if let Ok(uplink) = Uplink::from_bytes(data) {
+        mac_payload(uplink);
+    }
+    if let Ok(downlink) = Downlink::from_bytes(data) {
+        mac_payload(downlink);
+        downlink.mac_commands().map(|cmds| cmds.count());
+    }
+
+    let mut buf = data.to_vec();
+    if let Ok(uplink) = Uplink::new_from_encrypted(&mut buf, 0, &mut crypto()) {
+        mac_payload(uplink);
+    }
+    let mut buf = data.to_vec();
+    if let Ok(downlink) = Downlink::new_from_encrypted(&mut buf, 0, &mut crypto()) {
+        mac_payload(downlink);
+        downlink.mac_commands().map(|cmds| cmds.count());
+    }
+    let mut buf = data.to_vec();
+    let app_key = AppKey::read_from_bytes(&[3; 16]).unwrap();
+    if let Ok(join_accept) = JoinAccept::new_from_encrypted(&mut buf, &mut crypto(), &app_key) {
+        let _ = join_accept.rx_delay();
+        let _ = join_accept.cf_list();
+    }
+
+    DownlinkMacCommandDecoder::new(data).count();
+    MulticastDownlinkCommandDecoder::new(data).count();
+    let _ = CertificationDownlinkMacCommand::decode(data);
+});
@@ -0,0 +1,310 @@
+//! Network-side ADR following the Semtech reference algorithm: each 3 dB of link margin over the
+//! recent uplinks raises the data rate, then lowers the TX power, by one step, a negative margin
+//! raises the TX power, and NbTrans follows the packet loss rate.
+use crate::{
+    mac_command::downlink::{DataRateTXPower, LinkADRReq, Redundancy},
+    region::{Modulation, Region},
+};
+
+/// Installation margin in dB
+pub const DEFAULT_MARGIN: f32 = 10.0;
+/// Number of uplinks the decision is based on
+pub const DEFAULT_HISTORY_LEN: usize = 20;
+
+/// NbTrans by packet loss rate (< 5%, < 10%, < 30%, more) and current NbTrans
+const NB_TRANS: [[u8; 3]; 4] = [[1, 1, 2], [1, 2, 3], [2, 3, 3], [3, 3, 3]];
+
+/// Metadata of a received uplink, with the best SNR and RSSI among the receiving gateways
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct UplinkMetadata {
+    pub f_cnt: u32,
+    /// SNR in dB
+    pub snr: f32,
+    /// RSSI in dBm
+    pub rssi: i16,
+    pub data_rate: u8,
+    pub gateway_count: u8,
+}
+
+/// Transmission parameters requested by the network
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct AdrParams {
+    pub data_rate: u8,
+    pub tx_power: u8,
+    pub nb_trans: u8,
+}
+
+impl AdrParams {
+    pub fn link_adr_req(&self, ch_mask_cntl: u8, ch_mask: u16) -> LinkADRReq {
+        LinkADRReq {
+            data_rate_tx_power: DataRateTXPower::new()
+                .with_data_rate(self.data_rate)
+                .with_tx_power(self.tx_power),
+            ch_mask: ch_mask.to_le_bytes(),
+            redundancy: Redundancy::new()
+                .with_ch_mask_cntl(ch_mask_cntl)
+                .with_nb_trans(self.nb_trans),
+        }
+    }
+}
+
+/// ADR state of a device, keeping the metadata of its last `N` uplinks
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Adr<const N: usize = DEFAULT_HISTORY_LEN> {
+    /// Installation margin in dB
+    margin: f32,
+    history: [UplinkMetadata; N],
+    len: usize,
+    /// Index of the next entry to write, the oldest one once the history is full
+    next: usize,
+}
+
+impl<const N: usize> Default for Adr<N> {
+    fn default() -> Self {
+        Self::new(DEFAULT_MARGIN)
+    }
+}
+
+impl<const N: usize> Adr<N> {
+    pub fn new(margin: f32) -> Self {
+        Self {
+            margin,
+            history: [UplinkMetadata::default(); N],
+            len: 0,
+            next: 0,
+        }
+    }
+    pub fn margin(&self) -> f32 {
+        self.margin
+    }
+    /// Records a received uplink. A retransmission of the last one only improves its metadata.
+    pub fn uplink(&mut self, metadata: UplinkMetadata) {
+        if N == 0 {
+            return;
+        }
+        if let Some(last) = self.history().last().copied() {
+            if last.f_cnt == metadata.f_cnt {
+                let index = (self.next + N - 1) % N;
+                self.history[index] = UplinkMetadata {
+                    snr: last.snr.max(metadata.snr),
+                    rssi: last.rssi.max(metadata.rssi),
+                    gateway_count: last.gateway_count.max(metadata.gateway_count),
+                    ..metadata
+                };
+                return;
+            }
+        }
+        self.history[self.next] = metadata;
+        self.next = (self.next + 1) % N;
+        self.len = (self.len + 1).min(N);
+    }
+    /// Forgets the recorded uplinks, e.g. after the device rejoined
+    pub fn reset(&mut self) {
+        self.len = 0;
+        self.next = 0;
+    }
+    /// Recorded uplinks, oldest first
+    pub fn history(&self) -> impl Iterator<Item = &UplinkMetadata> + '_ {
+        let start = (self.next + N - self.len) % N.max(1);
+        (0..self.len).map(move |i| &self.history[(start + i) % N])
+    }
+    /// Percentage of the uplinks missing from the history, from its frame counter gaps
+    pub fn packet_loss(&self) -> u32 {
+        let mut history = self.history();
+        let (Some(first), Some(last)) = (history.next(), history.last()) else {
+            return 0;
+        };
+        let expected = last.f_cnt.wrapping_sub(first.f_cnt) as u64 + 1;
+        let lost = expected.saturating_sub(self.len as u64);
+        (lost * 100 / expected) as u32
+    }
+    /// Parameters for a device currently transmitting with `tx_power` and `nb_trans`, at the
+    /// data rate of its last uplink. `None` until the history is full or on a non-LoRa data rate.
+    pub fn adjust<R: Region>(&self, region: &R, tx_power: u8, nb_trans: u8) -> Option<AdrParams> {
+        if N == 0 || self.len < N {
+            return None;
+        }
+        let limits = region.limits();
+        let mut data_rate = self.history().last()?.data_rate;
+        let Modulation::LoRa {
+            spreading_factor,
+            bandwidth,
+        } = region.data_rate(data_rate)?.modulation
+        else {
+            return None;
+        };
+        let required_snr = -20.0 + 2.5 * (12 - spreading_factor.min(12)) as f32;
+        let max_snr = self.history().map(|u| u.snr).fold(f32::MIN, f32::max);
+        let mut steps = floor_div3(max_snr - required_snr - self.margin);
+
+        // only move to data rates of the same bandwidth, i.e. usable on the same channels
+        let next_data_rate = |data_rate: u8| {
+            let next = data_rate + 1;
+            let same_bandwidth = matches!(
+                region.data_rate(next).map(|dr| dr.modulation),
+                Some(Modulation::LoRa { bandwidth: b, .. }) if b == bandwidth
+            );
+            (next <= limits.max_data_rate && same_bandwidth).then_some(next)
+        };
+        let mut tx_power = tx_power.min(limits.max_tx_power);
+        while steps > 0 {
+            if let Some(next) = next_data_rate(data_rate) {
+                data_rate = next;
+            } else if tx_power < limits.max_tx_power {
+                tx_power += 1;
+            } else {
+                break;
+            }
+            steps -= 1;
+        }
+        while steps < 0 && tx_power > 0 {
+            tx_power -= 1;
+            steps += 1;
+        }
+
+        let loss = match self.packet_loss() {
+            0..5 => 0,
+            5..10 => 1,
+            10..30 => 2,
+            _ => 3,
+        };
+        let nb_trans = NB_TRANS[loss][nb_trans.clamp(1, 3) as usize - 1];
+        Some(AdrParams {
+            data_rate,
+            tx_power,
+            nb_trans,
+        })
+    }
+    /// LinkADRReq to send to a device transmitting with `tx_power` and `nb_trans`, with the
+    /// given channel mask, if its parameters have to change
+    pub fn link_adr_req<R: Region>(
+        &self,
+        region: &R,
+        tx_power: u8,
+        nb_trans: u8,
+        ch_mask_cntl: u8,
+        ch_mask: u16,
+    ) -> Option<LinkADRReq> {
+        let params = self.adjust(region, tx_power, nb_trans)?;
+        let data_rate = self.history().last()?.data_rate;
+        (params.data_rate != data_rate
+            || params.tx_power != tx_power
+            || params.nb_trans != nb_trans)
+            .then(|| params.link_adr_req(ch_mask_cntl, ch_mask))
+    }
+}
+
+/// `floor(x / 3)`, without relying on `std`
+fn floor_div3(x: f32) -> i32 {
+    let q = x / 3.0;
+    let n = q as i32;
+    if (n as f32) > q {
+        n - 1
+    } else {
+        n
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        mac_command::downlink::{encode_maccommands, DownlinkMacCommand},
+        region::eu868::Eu868,
+    };
+
+    use super::*;
+
+    fn adr(snr: f32, data_rate: u8, f_cnts: impl Iterator<Item = u32>) -> Adr {
+        let mut adr = Adr::default();
+        for f_cnt in f_cnts {
+            adr.uplink(UplinkMetadata {
+                f_cnt,
+                snr,
+                rssi: -100,
+                data_rate,
+                gateway_count: 1,
+            });
+        }
+        adr
+    }
+
+    #[test]
+    fn increase_data_rate() {
+        let region = Eu868;
+        assert_eq!(adr(5.0, 0, 0..19).adjust(&region, 0, 1), None);
+
+        // 15 dB of margin at SF12: 5 steps to SF7, which is as fast as 125 kHz goes
+        let adr = adr(5.0, 0, 0..20);
+        assert_eq!(adr.packet_loss(), 0);
+        assert_eq!(
+            adr.adjust(&region, 0, 1),
+            Some(AdrParams {
+                data_rate: 5,
+                tx_power: 0,
+                nb_trans: 1
+            })
+        );
+        let req = adr.link_adr_req(&region, 0, 1, 0, 0x0007).unwrap();
+        let mut buf = [0u8; 5];
+        assert_eq!(
+            encode_maccommands(&[DownlinkMacCommand::LinkADRReq(req)], &mut buf).unwrap(),
+            [0x03, 0x50, 0x07, 0x00, 0x01]
+        );
+        // 15 dB of margin at SF7: the steps lower the TX power
+        assert_eq!(
+            self::adr(17.5, 5, 0..20)
+                .adjust(&region, 0, 1)
+                .unwrap()
+                .tx_power,
+            5
+        );
+        assert_eq!(self::adr(4.0, 7, 0..20).adjust(&region, 0, 1), None);
+    }
+
+    #[test]
+    fn increase_tx_power() {
+        let region = Eu868;
+        // -7.5 dB of margin at SF7, a retransmission doesn't count as a lost uplink
+        let mut adr = adr(-5.0, 5, 0..20);
+        adr.uplink(UplinkMetadata {
+            f_cnt: 19,
+            snr: -20.0,
+            data_rate: 5,
+            ..Default::default()
+        });
+        assert_eq!(adr.history().count(), 20);
+        assert_eq!(
+            adr.adjust(&region, 4, 1),
+            Some(AdrParams {
+                data_rate: 5,
+                tx_power: 1,
+                nb_trans: 1
+            })
+        );
+        assert!(adr.link_adr_req(&region, 0, 1, 0, 0x0007).is_none());
+    }
+
+    #[test]
+    fn packet_loss() {
+        let region = Eu868;
+        // 20 of 25 uplinks received
+        let adr = adr(-10.0, 5, (0..25).filter(|f_cnt| f_cnt % 5 != 1));
+        assert_eq!(adr.packet_loss(), 20);
+        assert_eq!(adr.adjust(&region, 0, 1).unwrap().nb_trans, 2);
+        assert_eq!(adr.adjust(&region, 0, 3).unwrap().nb_trans, 3);
+
+        let mut adr: Adr<4> = Adr::new(15.0);
+        for f_cnt in [0, 1, 3, 4, 5] {
+            adr.uplink(UplinkMetadata {
+                f_cnt,
+                ..Default::default()
+            });
+        }
+        assert_eq!(adr.history().next().unwrap().f_cnt, 1);
+        assert_eq!(adr.packet_loss(), 20);
+        assert_eq!(adr.adjust(&region, 0, 2).unwrap().nb_trans, 3);
+    }
+}
@@ -0,0 +1,129 @@
+//! Duty-cycle accounting per regional sub-band and for the aggregated limit of DutyCycleReq.
+//!
+//! A transmission lasting `T` in a sub-band limited to 1 / `d` blocks the sub-band until `d * T`
+//! after its start.
+use core::time::Duration;
+
+use crate::{device::DeviceConfig, region::Region};
+
+/// Maximum number of sub-bands of a region that can be tracked
+pub const MAX_SUB_BANDS: usize = 8;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DutyCycle {
+    /// Earliest start of the next transmission in each sub-band of the region
+    sub_bands: [Duration; MAX_SUB_BANDS],
+    /// Earliest start of the next transmission for the aggregated duty cycle
+    aggregated: Duration,
+}
+
+impl DutyCycle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Records a transmission of a `len` bytes PHYPayload, as returned by `Uplink::build`, on
+    /// `frequency` at the data rate of `config`, started `at`. Returns its time on air.
+    pub fn transmitted<R: Region>(
+        &mut self,
+        region: &R,
+        config: &DeviceConfig,
+        at: Duration,
+        frequency: u32,
+        len: usize,
+    ) -> Duration {
+        let time_on_air = region
+            .data_rate(config.data_rate)
+            .map(|data_rate| data_rate.time_on_air(len, true))
+            .unwrap_or_default();
+        if let Some((index, sub_band)) = sub_band(region, frequency) {
+            self.sub_bands[index] = at + time_on_air * sub_band;
+        }
+        self.aggregated = at + time_on_air * (1 << config.max_duty_cycle.min(15));
+        time_on_air
+    }
+    /// Earliest time a transmission on `frequency` is allowed, `None` if it is outside the
+    /// sub-bands of the region
+    pub fn next_transmission<R: Region>(&self, region: &R, frequency: u32) -> Option<Duration> {
+        let (index, _) = sub_band(region, frequency)?;
+        Some(self.sub_bands[index].max(self.aggregated))
+    }
+    /// Earliest time a transmission is allowed on any of the enabled channels, with the channel
+    /// to use. `None` if no enabled channel exists.
+    pub fn next_channel<R: Region>(
+        &self,
+        region: &R,
+        config: &DeviceConfig,
+    ) -> Option<(Duration, usize)> {
+        config
+            .channel_mask
+            .iter()
+            .filter_map(|index| {
+                let channel = region.channel(config, index)?;
+                Some((self.next_transmission(region, channel.frequency)?, index))
+            })
+            .min()
+    }
+}
+
+/// Index and duty-cycle divisor of the sub-band of `frequency`
+fn sub_band<R: Region>(region: &R, frequency: u32) -> Option<(usize, u32)> {
+    region
+        .sub_bands()
+        .iter()
+        .take(MAX_SUB_BANDS)
+        .enumerate()
+        .find(|(_, sub_band)| sub_band.contains(frequency))
+        .map(|(index, sub_band)| (index, sub_band.duty_cycle.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{device::Channel, region::eu868::Eu868};
+
+    use super::*;
+    #[test]
+    fn sub_bands() {
+        let region = Eu868;
+        let mut config = region.default_config();
+        config.data_rate = 5;
+        let mut duty_cycle = DutyCycle::new();
+        assert_eq!(
+            duty_cycle.next_channel(&region, &config),
+            Some((Duration::ZERO, 0))
+        );
+
+        let at = Duration::from_secs(10);
+        let time_on_air = duty_cycle.transmitted(&region, &config, at, 868_100_000, 13);
+        assert_eq!(time_on_air, Duration::from_micros(46_336));
+        // 1% in 868.0-868.6 MHz, the RX2 sub-band is not affected
+        assert_eq!(
+            duty_cycle.next_transmission(&region, 868_500_000),
+            Some(at + time_on_air * 100)
+        );
+        assert_eq!(
+            duty_cycle.next_transmission(&region, 869_525_000),
+            Some(at + time_on_air)
+        );
+        assert_eq!(duty_cycle.next_transmission(&region, 869_300_000), None);
+
+        // 867.1 MHz is in another sub-band
+        config.set_channel(3, Some(Channel::new(867_100_000, 0, 5)));
+        assert_eq!(
+            duty_cycle.next_channel(&region, &config),
+            Some((at + time_on_air, 3))
+        );
+    }
+    #[test]
+    fn aggregated() {
+        let region = Eu868;
+        let mut config = region.default_config();
+        config.max_duty_cycle = 10;
+        let mut duty_cycle = DutyCycle::new();
+        let time_on_air = duty_cycle.transmitted(&region, &config, Duration::ZERO, 868_100_000, 13);
+        assert_eq!(
+            duty_cycle.next_transmission(&region, 869_525_000),
+            Some(time_on_air * 1024)
+        );
+    }
+}
@@ -0,0 +1,112 @@
+//! Time on air of LoRa and FSK frames, see Semtech AN1200.13 for the LoRa formula.
+use core::time::Duration;
+
+use crate::region::Bandwidth;
+
+pub mod duty_cycle;
+
+/// LoRa coding rate 4/5 to 4/8
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum CodingRate {
+    Cr4_5 = 1,
+    Cr4_6 = 2,
+    Cr4_7 = 3,
+    Cr4_8 = 4,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct LoRaPacket {
+    pub spreading_factor: u8,
+    pub bandwidth: Bandwidth,
+    pub coding_rate: CodingRate,
+    /// Number of programmed preamble symbols
+    pub preamble_len: u16,
+    pub explicit_header: bool,
+    pub crc: bool,
+    pub low_data_rate_optimize: bool,
+}
+
+impl LoRaPacket {
+    /// LoRaWAN uplink: CR 4/5, 8 preamble symbols, explicit header, CRC, and low data rate
+    /// optimization when symbols last at least 16 ms
+    pub fn uplink(spreading_factor: u8, bandwidth: Bandwidth) -> Self {
+        let mut packet = Self {
+            spreading_factor,
+            bandwidth,
+            coding_rate: CodingRate::Cr4_5,
+            preamble_len: 8,
+            explicit_header: true,
+            crc: true,
+            low_data_rate_optimize: false,
+        };
+        packet.low_data_rate_optimize = packet.symbol_time() >= Duration::from_millis(16);
+        packet
+    }
+    /// Like [`Self::uplink`], without CRC
+    pub fn downlink(spreading_factor: u8, bandwidth: Bandwidth) -> Self {
+        Self {
+            crc: false,
+            ..Self::uplink(spreading_factor, bandwidth)
+        }
+    }
+    pub fn symbol_time(&self) -> Duration {
+        Duration::from_micros(self.symbol_micros())
+    }
+    fn symbol_micros(&self) -> u64 {
+        (1_000_000u64 << self.spreading_factor) / self.bandwidth.hz() as u64
+    }
+    /// Number of symbols after the preamble for a payload of `len` bytes
+    pub fn payload_symbols(&self, len: usize) -> u64 {
+        let sf = self.spreading_factor as i64;
+        let bits =
+            8 * len as i64 - 4 * sf + 28 + 16 * self.crc as i64 - 20 * !self.explicit_header as i64;
+        let bits_per_block = 4 * (sf - 2 * self.low_data_rate_optimize as i64);
+        let blocks = if bits > 0 && bits_per_block > 0 {
+            (bits as u64).div_ceil(bits_per_block as u64)
+        } else {
+            0
+        };
+        8 + blocks * (self.coding_rate as u64 + 4)
+    }
+    /// Time on air of a payload of `len` bytes
+    pub fn time_on_air(&self, len: usize) -> Duration {
+        let symbol = self.symbol_micros();
+        // the preamble lasts 4.25 symbols more than programmed
+        let preamble = (4 * self.preamble_len as u64 + 17) * symbol / 4;
+        Duration::from_micros(preamble + self.payload_symbols(len) * symbol)
+    }
+}
+
+/// Time on air of a LoRaWAN FSK frame of `len` bytes: 5 bytes of preamble, 3 bytes of sync
+/// word, a length byte and a 2 bytes CRC
+pub fn fsk_time_on_air(bitrate: u32, len: usize) -> Duration {
+    let bits = 8 * (5 + 3 + 1 + len as u64 + 2);
+    Duration::from_micros((bits * 1_000_000).div_ceil(bitrate as u64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn lora_time_on_air() {
+        let sf7 = LoRaPacket::uplink(7, Bandwidth::Khz125);
+        assert!(!sf7.low_data_rate_optimize);
+        assert_eq!(sf7.payload_symbols(13), 33);
+        assert_eq!(sf7.time_on_air(13), Duration::from_micros(46_336));
+
+        let sf12 = LoRaPacket::uplink(12, Bandwidth::Khz125);
+        assert!(sf12.low_data_rate_optimize);
+        assert_eq!(sf12.time_on_air(13), Duration::from_micros(1_155_072));
+
+        let sf12 = LoRaPacket::downlink(12, Bandwidth::Khz500);
+        assert!(!sf12.low_data_rate_optimize);
+        assert_eq!(sf12.time_on_air(13), Duration::from_micros(247_808));
+    }
+    #[test]
+    fn fsk() {
+        assert_eq!(fsk_time_on_air(50_000, 13), Duration::from_micros(3_840));
+    }
+}
@@ -0,0 +1,399 @@
+//! Class A operation.
+//!
+//! [`ClassA`] schedules the two receive windows following every uplink and the
+//! retransmissions of an uplink without doing any I/O: it returns the frames to transmit and
+//! the windows to open, and consumes the end of transmission, the frames received and the
+//! end of the receive windows.
+use core::time::Duration;
+
+use crate::{
+    device::DeviceConfig,
+    mac_command::uplink::UplinkMacCommmand,
+    phy_payload::mac_payload::downlink::Downlink,
+    region::Region,
+    session::{FrameCodec, RxSettings, RxWindow},
+    Error,
+};
+
+/// Maximum number of MAC commands queued for the next uplink
+pub const MAX_MAC_ANSWERS: usize = 8;
+
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Event<'a> {
+    /// Downlink received in RX1 or RX2, ending the uplink. Its ACK bit tells whether a
+    /// confirmed uplink was acknowledged.
+    Downlink(&'a mut Downlink),
+    /// The unconfirmed uplink was transmitted NbTrans times without a downlink
+    UplinkDone,
+    /// The confirmed uplink was transmitted NbTrans times without being acknowledged
+    AckTimeout,
+}
+
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Output<'a> {
+    /// Transmit `bytes` at `at`, then report the end of the transmission with
+    /// [`ClassA::tx_done`]
+    Transmit {
+        bytes: &'a [u8],
+        at: Duration,
+    },
+    /// Open `window` at `at` with `data_rate`, then pass the frames received to
+    /// [`ClassA::received`] and report the end of the window with [`ClassA::rx_timeout`]
+    Receive {
+        window: RxWindow,
+        at: Duration,
+        data_rate: u8,
+    },
+    Event(Event<'a>),
+}
+
+#[derive(Clone, Copy)]
+enum State {
+    Idle,
+    Transmitting,
+    Rx1 { tx_done: Duration },
+    Rx2,
+}
+
+/// Class A end-device.
+///
+/// Every uplink is transmitted up to NbTrans times, until a downlink is received for an
+/// unconfirmed uplink, or until it is acknowledged for a confirmed uplink. The
+/// retransmissions reuse the frame counter of the first transmission.
+pub struct ClassA<S: FrameCodec> {
+    session: S,
+    rx_settings: RxSettings,
+    nb_trans: u8,
+    mac_answers: [UplinkMacCommmand; MAX_MAC_ANSWERS],
+    mac_answers_len: usize,
+    state: State,
+    confirmed: bool,
+    rx1_data_rate: u8,
+    tx_count: u8,
+    buf: [u8; 256],
+    len: usize,
+}
+
+impl<S: FrameCodec> ClassA<S> {
+    /// `session` is a [`crate::session::Session`], or an [`crate::session::abp::AbpSession`]
+    /// storing its frame counters
+    pub fn new(session: S, rx_settings: RxSettings) -> Self {
+        Self {
+            session,
+            rx_settings,
+            nb_trans: 1,
+            mac_answers: [UplinkMacCommmand::LinkCheckReq; MAX_MAC_ANSWERS],
+            mac_answers_len: 0,
+            state: State::Idle,
+            confirmed: false,
+            rx1_data_rate: 0,
+            tx_count: 0,
+            buf: [0; 256],
+            len: 0,
+        }
+    }
+    pub fn session(&self) -> &S {
+        &self.session
+    }
+    pub fn session_mut(&mut self) -> &mut S {
+        &mut self.session
+    }
+    pub fn rx_settings(&self) -> &RxSettings {
+        &self.rx_settings
+    }
+    pub fn rx_settings_mut(&mut self) -> &mut RxSettings {
+        &mut self.rx_settings
+    }
+    pub fn nb_trans(&self) -> u8 {
+        self.nb_trans
+    }
+    /// Number of transmissions of every uplink, 0 is treated as 1
+    pub fn set_nb_trans(&mut self, nb_trans: u8) {
+        self.nb_trans = nb_trans.max(1)
+    }
+    /// MAC commands sent in the next uplink
+    pub fn mac_answers(&self) -> &[UplinkMacCommmand] {
+        &self.mac_answers[..self.mac_answers_len]
+    }
+    /// Queues a MAC command for the next uplink. Fails with [`Error::FOptsLen`] if the queued
+    /// commands would not fit in FOpts.
+    pub fn queue_mac_answer(&mut self, cmd: UplinkMacCommmand) -> Result<(), Error> {
+        let len: usize = self
+            .mac_answers()
+            .iter()
+            .map(|c| c.as_bytes().len() + 1)
+            .sum();
+        let f_opts_len = len + cmd.as_bytes().len() + 1;
+        if self.mac_answers_len == MAX_MAC_ANSWERS || f_opts_len > 15 {
+            return Err(Error::FOptsLen {
+                f_opts_len,
+                available: 15,
+            });
+        }
+        self.mac_answers[self.mac_answers_len] = cmd;
+        self.mac_answers_len += 1;
+        Ok(())
+    }
+
+    /// RX1 data rate of an uplink at `data_rate` with the RX1DROffset of [`Self::rx_settings`],
+    /// `None` if either is invalid in `region`
+    pub fn rx1_data_rate<R: Region>(
+        &self,
+        region: &R,
+        config: &DeviceConfig,
+        data_rate: u8,
+    ) -> Option<u8> {
+        region.rx1_data_rate(config, data_rate, self.rx_settings.rx1_dr_offset)
+    }
+
+    /// Starts an uplink, abandoning any uplink in progress. RX1 is opened at
+    /// `rx1_data_rate`, see [`Self::rx1_data_rate`]. The queued MAC commands are sent in
+    /// FOpts, or in FRMPayload if `f_port` is 0.
+    pub fn send(
+        &mut self,
+        now: Duration,
+        rx1_data_rate: u8,
+        confirmed: bool,
+        f_port: u8,
+        payload: &[u8],
+    ) -> Result<Output<'_>, Error> {
+        let bytes = self.session.encode_uplink(
+            &mut self.buf,
+            confirmed,
+            f_port,
+            payload,
+            &self.mac_answers[..self.mac_answers_len],
+        )?;
+        self.len = bytes.len();
+        self.mac_answers_len = 0;
+        self.confirmed = confirmed;
+        self.rx1_data_rate = rx1_data_rate;
+        self.tx_count = 1;
+        self.state = State::Transmitting;
+        Ok(Output::Transmit {
+            bytes: &self.buf[..self.len],
+            at: now,
+        })
+    }
+    /// The uplink was transmitted until `at`. Returns the RX1 window to open.
+    pub fn tx_done(&mut self, at: Duration) -> Option<Output<'_>> {
+        let State::Transmitting = self.state else {
+            return None;
+        };
+        self.state = State::Rx1 { tx_done: at };
+        Some(Output::Receive {
+            window: RxWindow::Rx1,
+            at: at + self.rx_settings.rx_delay,
+            data_rate: self.rx1_data_rate,
+        })
+    }
+    /// The current receive window closed at `now` without a downlink. Returns the RX2 window
+    /// after RX1, then a retransmission or the end of the uplink after RX2.
+    pub fn rx_timeout(&mut self, now: Duration) -> Option<Output<'_>> {
+        match self.state {
+            State::Rx1 { tx_done } => {
+                self.state = State::Rx2;
+                Some(Output::Receive {
+                    window: RxWindow::Rx2,
+                    at: tx_done + self.rx_settings.rx_delay + Duration::from_secs(1),
+                    data_rate: self.rx_settings.rx2_data_rate,
+                })
+            }
+            State::Rx2 if self.tx_count < self.nb_trans => {
+                self.tx_count += 1;
+                self.state = State::Transmitting;
+                Some(Output::Transmit {
+                    bytes: &self.buf[..self.len],
+                    at: now,
+                })
+            }
+            State::Rx2 => {
+                self.state = State::Idle;
+                Some(Output::Event(match self.confirmed {
+                    true => Event::AckTimeout,
+                    false => Event::UplinkDone,
+                }))
+            }
+            State::Idle | State::Transmitting => None,
+        }
+    }
+    /// Accepts a frame received in RX1 or RX2.
+    ///
+    /// On error the frame is discarded and the receive window stays open.
+    pub fn received<'a>(&mut self, buf: &'a mut [u8]) -> Result<Event<'a>, Error> {
+        let (State::Rx1 { .. } | State::Rx2) = self.state else {
+            return Err(Error::State);
+        };
+        let downlink = self.session.decode_downlink(buf)?;
+        self.state = State::Idle;
+        Ok(Event::Downlink(downlink))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use zerocopy::FromBytes;
+
+    use crate::{
+        crypto::soft::SoftCrypto,
+        frame_counter::FrameCounter,
+        mac_command::uplink::{LinkADRAns, LinkAdrAnsStatus},
+        region::{as923::As923, eu868::Eu868, us915::Us915},
+        session::{Session, Version},
+        types::{AppSKey, DevAddr, NwkSKey},
+    };
+
+    use super::*;
+    fn get_device() -> ClassA<Session<SoftCrypto>> {
+        let session = Session::new(
+            Version::V1_0_4,
+            DevAddr::read_from_bytes(&[4, 3, 2, 1]).unwrap(),
+            NwkSKey::read_from_bytes(&[2; 16]).unwrap(),
+            AppSKey::read_from_bytes(&[1; 16]).unwrap(),
+        );
+        ClassA::new(
+            session,
+            RxSettings {
+                rx1_dr_offset: 1,
+                rx2_data_rate: 0,
+                rx_delay: Duration::from_secs(1),
+            },
+        )
+    }
+    fn secs(secs: u64) -> Duration {
+        Duration::from_secs(secs)
+    }
+    fn confirmed_downlink() -> [u8; 23] {
+        [
+            0xa0, 0x04, 0x03, 0x02, 0x01, 0x80, 0xff, 0x2a, 0x2a, 0x0a, 0xf1, 0xa3, 0x6a, 0x05,
+            0xd0, 0x12, 0x5f, 0x88, 0x5d, 0x88, 0x1d, 0x49, 0xe1,
+        ]
+    }
+    #[test]
+    fn schedule_receive_windows() {
+        let mut device = get_device();
+        device.rx_settings_mut().rx_delay = secs(5);
+        let rx1_data_rate = device
+            .rx1_data_rate(&Eu868, &Eu868.default_config(), 5)
+            .unwrap();
+        let Output::Transmit { bytes, at } = device
+            .send(secs(10), rx1_data_rate, false, 1, b"hi")
+            .unwrap()
+        else {
+            panic!("expected a transmission");
+        };
+        assert_eq!(bytes[0], 0x40);
+        assert_eq!(at, secs(10));
+        assert!(device.rx_timeout(secs(10)).is_none());
+        assert!(matches!(
+            device.tx_done(secs(11)),
+            Some(Output::Receive {
+                window: RxWindow::Rx1,
+                at,
+                data_rate: 4
+            }) if at == secs(16)
+        ));
+        assert!(matches!(
+            device.rx_timeout(secs(16)),
+            Some(Output::Receive {
+                window: RxWindow::Rx2,
+                at,
+                data_rate: 0
+            }) if at == secs(17)
+        ));
+        assert!(matches!(
+            device.rx_timeout(secs(17)),
+            Some(Output::Event(Event::UplinkDone))
+        ));
+        assert!(device.rx_timeout(secs(17)).is_none());
+    }
+    #[test]
+    fn rx1_data_rate_of_region() {
+        let mut device = get_device();
+        let config = Us915::default().default_config();
+        assert_eq!(device.rx1_data_rate(&Us915::default(), &config, 0), Some(9));
+        assert_eq!(device.rx1_data_rate(&Us915::default(), &config, 5), None);
+
+        let region = As923::As923_1;
+        let mut config = region.default_config();
+        config.downlink_dwell_time = false;
+        device.rx_settings_mut().rx1_dr_offset = 7;
+        assert_eq!(device.rx1_data_rate(&region, &config, 2), Some(4));
+        device.rx_settings_mut().rx1_dr_offset = 3;
+        assert_eq!(device.rx1_data_rate(&region, &config, 2), Some(0));
+        config.downlink_dwell_time = true;
+        assert_eq!(device.rx1_data_rate(&region, &config, 2), Some(2));
+
+        device.send(secs(0), 9, false, 1, b"hi").unwrap();
+        assert!(matches!(
+            device.tx_done(secs(1)),
+            Some(Output::Receive {
+                window: RxWindow::Rx1,
+                data_rate: 9,
+                ..
+            })
+        ));
+    }
+    #[test]
+    fn retransmit_confirmed_uplink() {
+        let mut device = get_device();
+        device.set_nb_trans(2);
+        let Output::Transmit { bytes, .. } = device.send(secs(0), 0, true, 1, b"hi").unwrap()
+        else {
+            panic!("expected a transmission");
+        };
+        let first = bytes.to_vec();
+        device.tx_done(secs(1)).unwrap();
+        device.rx_timeout(secs(2)).unwrap();
+        let Some(Output::Transmit { bytes, at }) = device.rx_timeout(secs(3)) else {
+            panic!("expected a retransmission");
+        };
+        assert_eq!(bytes, &first[..]);
+        assert_eq!(at, secs(3));
+        device.tx_done(secs(4)).unwrap();
+        device.rx_timeout(secs(5)).unwrap();
+        assert!(matches!(
+            device.rx_timeout(secs(6)),
+            Some(Output::Event(Event::AckTimeout))
+        ));
+        assert_eq!(device.session().counters().f_cnt_up.next(), 1);
+    }
+    #[test]
+    fn stop_on_downlink_and_acknowledge() {
+        let mut device = get_device();
+        device.set_nb_trans(3);
+        device.session_mut().counters_mut().n_f_cnt_down = FrameCounter::new(0x12A00);
+        device.send(secs(0), 0, false, 1, b"hi").unwrap();
+        assert!(device.received(&mut confirmed_downlink()).is_err());
+        device.tx_done(secs(1)).unwrap();
+        let mut packet = confirmed_downlink();
+        let Ok(Event::Downlink(downlink)) = device.received(&mut packet) else {
+            panic!("expected a downlink");
+        };
+        assert_eq!(&downlink.frm_payload().unwrap().data[..10], b"hello lora");
+        assert!(device.rx_timeout(secs(2)).is_none());
+        let Output::Transmit { bytes, .. } = device.send(secs(3), 0, false, 1, &[]).unwrap() else {
+            panic!("expected a transmission");
+        };
+        assert_eq!(bytes[5] & 0x20, 0x20);
+    }
+    #[test]
+    fn queue_mac_answers() {
+        let mut device = get_device();
+        let answer = UplinkMacCommmand::LinkADRAns(LinkADRAns {
+            status: LinkAdrAnsStatus::new().with_power_ack(true),
+        });
+        for _ in 0..7 {
+            device.queue_mac_answer(answer).unwrap();
+        }
+        assert!(device.queue_mac_answer(answer).is_err());
+        assert_eq!(device.mac_answers().len(), 7);
+        let Output::Transmit { bytes, .. } = device.send(secs(0), 0, false, 1, b"hi").unwrap()
+        else {
+            panic!("expected a transmission");
+        };
+        assert_eq!(bytes[5] & 0x0f, 14);
+        assert_eq!(&bytes[8..10], &[0x03, 0x04]);
+        assert!(device.mac_answers().is_empty());
+    }
+}
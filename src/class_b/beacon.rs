@@ -0,0 +1,269 @@
+use zerocopy::{
+    little_endian::{U16, U32},
+    FromBytes, Immutable, IntoBytes, KnownLayout, Unaligned,
+};
+
+/// Class B beacon payload. The RFU fields depend on the region, see [`EU868Beacon`] and
+/// [`US915Beacon`].
+#[derive(KnownLayout, FromBytes, IntoBytes, Immutable, Unaligned)]
+#[repr(C, packed)]
+pub struct Beacon<const RFU1: usize, const RFU2: usize> {
+    rfu1: [u8; RFU1],
+    time: U32,
+    crc1: U16,
+    gw_specific: GwSpecific,
+    rfu2: [u8; RFU2],
+    crc2: U16,
+}
+
+pub type EU868Beacon = Beacon<2, 0>;
+pub type US915Beacon = Beacon<5, 3>;
+
+impl<const RFU1: usize, const RFU2: usize> Beacon<RFU1, RFU2> {
+    /// Builds a beacon with valid CRCs, e.g. for a test gateway
+    pub fn new(time: u32, gw_specific: GwSpecific) -> Self {
+        let mut beacon = Self {
+            rfu1: [0; RFU1],
+            time: time.into(),
+            crc1: 0.into(),
+            gw_specific,
+            rfu2: [0; RFU2],
+            crc2: 0.into(),
+        };
+        beacon.crc1 = beacon.calculate_crc1().into();
+        beacon.crc2 = beacon.calculate_crc2().into();
+        beacon
+    }
+    /// Seconds since GPS epoch modulo 2^32
+    pub fn time(&self) -> u32 {
+        self.time.get()
+    }
+    pub fn gw_specific(&self) -> GwSpecific {
+        self.gw_specific
+    }
+    fn calculate_crc1(&self) -> u16 {
+        crc16(&self.as_bytes()[..RFU1 + 4])
+    }
+    fn calculate_crc2(&self) -> u16 {
+        crc16(&self.as_bytes()[RFU1 + 6..RFU1 + 13 + RFU2])
+    }
+    /// Validates the CRC covering the Time field
+    pub fn validate_time_crc(&self) -> bool {
+        self.crc1.get() == self.calculate_crc1()
+    }
+    /// Validates the CRC covering the GwSpecific field. The time can be used even if this
+    /// CRC is invalid.
+    pub fn validate_gw_specific_crc(&self) -> bool {
+        self.crc2.get() == self.calculate_crc2()
+    }
+}
+#[cfg(feature = "defmt")]
+impl<const RFU1: usize, const RFU2: usize> defmt::Format for Beacon<RFU1, RFU2> {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(
+            f,
+            "Beacon {{ time: {=u32}, time_crc_valid: {=bool}, gw_specific: {}, gw_specific_crc_valid: {=bool} }}",
+            self.time(),
+            self.validate_time_crc(),
+            self.gw_specific(),
+            self.validate_gw_specific_crc()
+        )
+    }
+}
+
+/// CRC-16/CCITT with initial value 0x0000
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc = 0u16;
+    for byte in data {
+        crc ^= (*byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+#[derive(Clone, Copy, KnownLayout, FromBytes, IntoBytes, Immutable, Unaligned)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(C)]
+pub struct GwSpecific {
+    info_desc: u8,
+    info: [u8; 6],
+}
+
+impl GwSpecific {
+    /// GPS coordinates of the gateway antenna `antenna` (0..=2), latitude and longitude in
+    /// degrees
+    pub fn coordinates(antenna: u8, latitude: f64, longitude: f64) -> Self {
+        let lat = round(latitude / 90.0 * (1 << 23) as f64);
+        let lng = round(longitude / 180.0 * (1 << 23) as f64);
+        let lat = lat.clamp(-(1 << 23), (1 << 23) - 1).to_le_bytes();
+        let lng = lng.clamp(-(1 << 23), (1 << 23) - 1).to_le_bytes();
+        Self {
+            info_desc: antenna,
+            info: [lat[0], lat[1], lat[2], lng[0], lng[1], lng[2]],
+        }
+    }
+    pub fn net_id_gateway_id(net_id: [u8; 3], gateway_id: [u8; 3]) -> Self {
+        Self {
+            info_desc: 3,
+            info: [
+                net_id[0],
+                net_id[1],
+                net_id[2],
+                gateway_id[0],
+                gateway_id[1],
+                gateway_id[2],
+            ],
+        }
+    }
+    pub fn info_desc(&self) -> u8 {
+        self.info_desc
+    }
+    pub fn info(&self) -> GwInfo {
+        match self.info_desc {
+            0..=2 => GwInfo::Coordinates {
+                antenna: self.info_desc,
+                latitude: i24(&self.info[0..3]),
+                longitude: i24(&self.info[3..6]),
+            },
+            3 => GwInfo::NetIdGatewayId {
+                net_id: [self.info[0], self.info[1], self.info[2]],
+                gateway_id: [self.info[3], self.info[4], self.info[5]],
+            },
+            _ => GwInfo::Other(self.info),
+        }
+    }
+}
+
+fn round(value: f64) -> i32 {
+    if value < 0.0 {
+        (value - 0.5) as i32
+    } else {
+        (value + 0.5) as i32
+    }
+}
+
+fn i24(bytes: &[u8]) -> i32 {
+    i32::from_le_bytes([0, bytes[0], bytes[1], bytes[2]]) >> 8
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum GwInfo {
+    /// Raw coordinates, see [`GwInfo::latitude`] and [`GwInfo::longitude`]
+    Coordinates {
+        antenna: u8,
+        latitude: i32,
+        longitude: i32,
+    },
+    NetIdGatewayId {
+        net_id: [u8; 3],
+        gateway_id: [u8; 3],
+    },
+    /// RFU or network specific
+    Other([u8; 6]),
+}
+
+impl GwInfo {
+    /// Latitude in degrees
+    pub fn latitude(&self) -> Option<f64> {
+        match self {
+            GwInfo::Coordinates { latitude, .. } => {
+                Some(*latitude as f64 * 90.0 / (1 << 23) as f64)
+            }
+            _ => None,
+        }
+    }
+    /// Longitude in degrees
+    pub fn longitude(&self) -> Option<f64> {
+        match self {
+            GwInfo::Coordinates { longitude, .. } => {
+                Some(*longitude as f64 * 180.0 / (1 << 23) as f64)
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn decode_eu868_beacon() {
+        let buf = [
+            0x00, 0x00, 0x00, 0x00, 0x02, 0xCC, 0xA2, 0x7E, 0x00, 0x01, 0x20, 0x00, 0x00, 0x81,
+            0x03, 0xDE, 0x55,
+        ];
+        let beacon = EU868Beacon::ref_from_bytes(&buf).unwrap();
+        assert!(beacon.validate_time_crc());
+        assert!(beacon.validate_gw_specific_crc());
+        assert_eq!(beacon.time(), 0xCC020000);
+        assert_eq!(
+            beacon.gw_specific().info(),
+            GwInfo::Coordinates {
+                antenna: 0,
+                latitude: 0x002001,
+                longitude: 0x038100
+            }
+        );
+    }
+    #[test]
+    fn detect_corrupted_beacon() {
+        let mut buf = [
+            0x00, 0x00, 0x00, 0x00, 0x02, 0xCC, 0xA2, 0x7E, 0x00, 0x01, 0x20, 0x00, 0x00, 0x81,
+            0x03, 0xDE, 0x55,
+        ];
+        buf[12] = 0xff;
+        let beacon = EU868Beacon::ref_from_bytes(&buf).unwrap();
+        assert!(beacon.validate_time_crc());
+        assert!(!beacon.validate_gw_specific_crc());
+        buf[3] = 0xff;
+        let beacon = EU868Beacon::ref_from_bytes(&buf).unwrap();
+        assert!(!beacon.validate_time_crc());
+    }
+    #[test]
+    fn encode_eu868_beacon() {
+        let beacon = EU868Beacon::new(0xCC020000, GwSpecific::coordinates(0, 0.087901, 4.927368));
+        assert_eq!(
+            beacon.as_bytes(),
+            &[
+                0x00, 0x00, 0x00, 0x00, 0x02, 0xCC, 0xA2, 0x7E, 0x00, 0x01, 0x20, 0x00, 0x00, 0x81,
+                0x03, 0xDE, 0x55,
+            ]
+        );
+    }
+    #[test]
+    fn encode_us915_beacon() {
+        let beacon = US915Beacon::new(
+            1234567890,
+            GwSpecific::net_id_gateway_id([1, 2, 3], [4, 5, 6]),
+        );
+        let bytes = beacon.as_bytes();
+        assert_eq!(bytes.len(), 23);
+        assert_eq!(&bytes[..5], &[0; 5]);
+        assert_eq!(&bytes[5..9], &1234567890u32.to_le_bytes());
+        assert_eq!(&bytes[11..18], &[3, 1, 2, 3, 4, 5, 6]);
+        let beacon = US915Beacon::ref_from_bytes(bytes).unwrap();
+        assert!(beacon.validate_time_crc());
+        assert!(beacon.validate_gw_specific_crc());
+        assert_eq!(beacon.time(), 1234567890);
+        assert_eq!(
+            beacon.gw_specific().info(),
+            GwInfo::NetIdGatewayId {
+                net_id: [1, 2, 3],
+                gateway_id: [4, 5, 6]
+            }
+        );
+    }
+    #[test]
+    fn negative_coordinates() {
+        let info = GwSpecific::coordinates(1, -45.0, -90.0).info();
+        assert_eq!(info.latitude(), Some(-45.0));
+        assert_eq!(info.longitude(), Some(-90.0));
+    }
+}
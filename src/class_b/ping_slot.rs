@@ -0,0 +1,120 @@
+use core::time::Duration;
+
+use zerocopy::IntoBytes;
+
+use crate::{
+    crypto::{Crypto, Encrypter as _, Key},
+    types::DevAddr,
+};
+
+pub const BEACON_PERIOD: Duration = Duration::from_secs(128);
+pub const BEACON_RESERVED: Duration = Duration::from_millis(2120);
+pub const BEACON_WINDOW: Duration = Duration::from_millis(122880);
+pub const SLOT_LEN: Duration = Duration::from_millis(30);
+
+/// Number of ping slots per beacon period, `periodicity` 0..=7 as sent in PingSlotInfoReq
+pub fn ping_nb(periodicity: u8) -> u16 {
+    1 << (7 - (periodicity & 0x07))
+}
+
+/// Number of slots between two ping slots
+pub fn ping_period(periodicity: u8) -> u16 {
+    1 << (5 + (periodicity & 0x07))
+}
+
+/// Randomized offset of the first ping slot in the beacon period starting at `beacon_time`
+/// (the Time field of the beacon)
+pub fn ping_offset<C: Crypto>(
+    crypto: &mut C,
+    beacon_time: u32,
+    dev_addr: DevAddr,
+    periodicity: u8,
+) -> u16 {
+    let mut block = [0u8; 16];
+    block[0..4].copy_from_slice(&beacon_time.to_le_bytes());
+    block[4..8].copy_from_slice(dev_addr.as_bytes());
+    crypto
+        .get_encrypter(Key::Raw([0; 16]))
+        .encrypt_block(&mut block);
+    u16::from_le_bytes([block[0], block[1]]) % ping_period(periodicity)
+}
+
+/// Start of every ping slot of the beacon period starting at `beacon_time`, relative to the
+/// start of the beacon
+pub fn ping_slots<C: Crypto>(
+    crypto: &mut C,
+    beacon_time: u32,
+    dev_addr: DevAddr,
+    periodicity: u8,
+) -> PingSlots {
+    PingSlots {
+        offset: ping_offset(crypto, beacon_time, dev_addr, periodicity),
+        period: ping_period(periodicity),
+        n: 0,
+        nb: ping_nb(periodicity),
+    }
+}
+
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PingSlots {
+    offset: u16,
+    period: u16,
+    n: u16,
+    nb: u16,
+}
+
+impl Iterator for PingSlots {
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.n >= self.nb {
+            return None;
+        }
+        let slot = self.offset as u32 + self.n as u32 * self.period as u32;
+        self.n += 1;
+        Some(BEACON_RESERVED + SLOT_LEN * slot)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use zerocopy::FromBytes;
+
+    use crate::{
+        crypto::soft::SoftCrypto,
+        types::{AppSKey, NwkSKey},
+    };
+
+    use super::*;
+    fn get_crypto() -> SoftCrypto {
+        let nwk_s_key = NwkSKey::read_from_bytes(&[1; 16]).unwrap();
+        let app_s_key = AppSKey::read_from_bytes(&[2; 16]).unwrap();
+        SoftCrypto::new(nwk_s_key, app_s_key)
+    }
+    #[test]
+    fn compute_ping_offset() {
+        let mut crypto = get_crypto();
+        let dev_addr = DevAddr::read_from_bytes(&[0x78, 0x56, 0x34, 0x12]).unwrap();
+        assert_eq!(ping_offset(&mut crypto, 0x4D2, dev_addr, 0), 25);
+        assert_eq!(ping_offset(&mut crypto, 0x4D2, dev_addr, 7), 761);
+        let dev_addr = DevAddr::read_from_bytes(&[4, 3, 2, 1]).unwrap();
+        assert_eq!(ping_offset(&mut crypto, 1400000000, dev_addr, 3), 228);
+    }
+    #[test]
+    fn compute_ping_slots() {
+        let mut crypto = get_crypto();
+        let dev_addr = DevAddr::read_from_bytes(&[4, 3, 2, 1]).unwrap();
+        let slots: Vec<_> = ping_slots(&mut crypto, 1400000000, dev_addr, 3).collect();
+        assert_eq!(slots.len(), 16);
+        assert_eq!(slots[0], Duration::from_millis(2120 + 228 * 30));
+        assert_eq!(slots[1], Duration::from_millis(2120 + (228 + 256) * 30));
+        assert!(slots
+            .iter()
+            .all(|slot| *slot + SLOT_LEN <= BEACON_RESERVED + BEACON_WINDOW));
+
+        let slots: Vec<_> = ping_slots(&mut crypto, 1400000000, dev_addr, 0).collect();
+        assert_eq!(slots.len(), 128);
+        let slots: Vec<_> = ping_slots(&mut crypto, 1400000000, dev_addr, 7).collect();
+        assert_eq!(slots.len(), 1);
+    }
+}
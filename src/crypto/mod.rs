@@ -6,13 +6,15 @@ pub mod soft;
 pub enum Key {
     Network,
     Application,
+    /// Key not held by the implementation, e.g. a root key used for key derivation
+    Raw([u8; 16]),
 }
 /// Trait for implementations of AES128 encryption.
 pub trait Crypto {
     type Encrypter: Encrypter;
     type Mac: Mac;
     fn get_encrypter(&mut self, key: Key) -> Self::Encrypter;
-    fn get_mac(&mut self) -> Self::Mac;
+    fn get_mac(&mut self, key: Key) -> Self::Mac;
 }
 
 pub trait Encrypter {
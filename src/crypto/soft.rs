@@ -3,6 +3,7 @@ use aes::{Aes128, Aes128Enc};
 use cmac::{Cmac, Mac};
 use zerocopy::{FromBytes, IntoBytes};
 
+use crate::session::SessionKeys;
 use crate::types::MIC;
 use crate::types::{AppSKey, NwkSKey};
 
@@ -21,6 +22,12 @@ impl SoftCrypto {
     }
 }
 
+impl From<SessionKeys> for SoftCrypto {
+    fn from(keys: SessionKeys) -> Self {
+        Self::new(keys.nwk_s_key, keys.app_s_key)
+    }
+}
+
 pub struct SoftEncrypter {
     inner: Aes128Enc,
 }
@@ -53,13 +60,19 @@ impl Crypto for SoftCrypto {
         let inner = match key {
             Key::Network => Aes128Enc::new(GenericArray::from_slice(self.nwk_s_key.as_bytes())),
             Key::Application => Aes128Enc::new(GenericArray::from_slice(self.app_s_key.as_bytes())),
+            Key::Raw(key) => Aes128Enc::new(GenericArray::from_slice(&key)),
         };
         Self::Encrypter { inner }
     }
 
-    fn get_mac(&mut self) -> Self::Mac {
+    fn get_mac(&mut self, key: Key) -> Self::Mac {
+        let key = match key {
+            Key::Network => self.nwk_s_key.as_bytes(),
+            Key::Application => self.app_s_key.as_bytes(),
+            Key::Raw(ref key) => key,
+        };
         Self::Mac {
-            inner: <Cmac<Aes128> as cmac::Mac>::new_from_slice(self.nwk_s_key.as_bytes()).unwrap(),
+            inner: <Cmac<Aes128> as cmac::Mac>::new_from_slice(key).unwrap(),
         }
     }
 }
@@ -0,0 +1,103 @@
+//! ADR backoff of an end-device with ADR enabled.
+//!
+//! After ADR_ACK_LIMIT uplinks without any downlink the device sets ADRACKReq. If the network
+//! still does not answer, every ADR_ACK_DELAY further uplinks it restores its maximum TX power,
+//! then lowers its data rate step by step and finally re-enables the default channels.
+use crate::region::Region;
+
+use super::DeviceConfig;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct AdrBackoff {
+    /// ADR_ACK_CNT: uplinks sent since the last downlink
+    adr_ack_cnt: u32,
+}
+
+impl AdrBackoff {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn adr_ack_cnt(&self) -> u32 {
+        self.adr_ack_cnt
+    }
+    /// To be called before each new uplink, not for its retransmissions. Applies the next
+    /// backoff step to `config` when due and returns whether ADRACKReq must be set.
+    pub fn uplink<R: Region>(&mut self, region: &R, config: &mut DeviceConfig) -> bool {
+        let limit = config.adr_ack_limit as u32;
+        let delay = config.adr_ack_delay.max(1) as u32;
+        let cnt = self.adr_ack_cnt;
+        if cnt >= limit + delay && (cnt - limit).is_multiple_of(delay) {
+            step(region, config);
+        }
+        self.adr_ack_cnt = cnt.saturating_add(1);
+        // the network cannot improve anything at the lowest data rate and the highest power
+        cnt >= limit && (config.tx_power > 0 || lower_data_rate(region, config).is_some())
+    }
+    /// To be called for every downlink received
+    pub fn downlink(&mut self) {
+        self.adr_ack_cnt = 0;
+    }
+}
+
+fn step<R: Region>(region: &R, config: &mut DeviceConfig) {
+    if config.tx_power > 0 {
+        config.tx_power = 0;
+        config.nb_trans = 1;
+    } else if let Some(data_rate) = lower_data_rate(region, config) {
+        config.data_rate = data_rate;
+    } else {
+        for channel in region.default_config().channel_mask.iter() {
+            config.channel_mask.set(channel, true);
+        }
+    }
+}
+
+/// Next lower data rate usable for uplinks
+fn lower_data_rate<R: Region>(region: &R, config: &DeviceConfig) -> Option<u8> {
+    (0..config.data_rate)
+        .rev()
+        .find(|data_rate| region.max_mac_payload_size(config, *data_rate).is_some())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{device::ChannelMask, region::eu868::Eu868};
+
+    use super::*;
+    #[test]
+    fn backoff() {
+        let region = Eu868;
+        let mut config = region.default_config();
+        config.data_rate = 2;
+        config.tx_power = 3;
+        config.nb_trans = 2;
+        config.channel_mask = ChannelMask::new([0b001, 0, 0, 0, 0]);
+        config.adr_ack_limit = 4;
+        config.adr_ack_delay = 2;
+        let mut backoff = AdrBackoff::new();
+        let adr_ack_reqs: Vec<_> = (0..6)
+            .map(|_| backoff.uplink(&region, &mut config))
+            .collect();
+        assert_eq!(adr_ack_reqs, [false, false, false, false, true, true]);
+        assert_eq!((config.data_rate, config.tx_power), (2, 3));
+
+        assert!(backoff.uplink(&region, &mut config));
+        assert_eq!((config.tx_power, config.nb_trans), (0, 1));
+        backoff.uplink(&region, &mut config);
+        assert!(backoff.uplink(&region, &mut config));
+        assert_eq!(config.data_rate, 1);
+        backoff.uplink(&region, &mut config);
+        assert!(!backoff.uplink(&region, &mut config));
+        assert_eq!(config.data_rate, 0);
+        assert_eq!(config.channel_mask.count(), 1);
+        backoff.uplink(&region, &mut config);
+        backoff.uplink(&region, &mut config);
+        assert_eq!(config.channel_mask.count(), 3);
+        assert_eq!(backoff.adr_ack_cnt(), 13);
+
+        backoff.downlink();
+        assert!(!backoff.uplink(&region, &mut config));
+        assert_eq!(backoff.adr_ack_cnt(), 1);
+    }
+}
@@ -0,0 +1,129 @@
+//! Uplink channel layouts used to interpret LinkADRReq.
+use super::{Channel, ChannelMask, DeviceConfig};
+
+/// Channel layout of a region, as seen by LinkADRReq
+pub trait ChannelPlan {
+    /// Uplink channel `index`, `None` if it does not exist
+    fn channel(&self, config: &DeviceConfig, index: usize) -> Option<Channel>;
+    /// Applies a ChMask with its ChMaskCntl to `mask`. Returns `false` if ChMaskCntl is RFU
+    /// in the region.
+    fn apply_ch_mask(
+        &self,
+        config: &DeviceConfig,
+        mask: &mut ChannelMask,
+        ch_mask_cntl: u8,
+        ch_mask: u16,
+    ) -> bool;
+    /// Whether channels can be defined with NewChannelReq and DlChannelReq, both are ignored
+    /// without an answer otherwise
+    fn supports_new_channel(&self) -> bool {
+        true
+    }
+}
+
+/// Channels defined by the network with NewChannelReq or the CFList, e.g. EU868.
+///
+/// ChMaskCntl 0 applies ChMask to channels 0 to 15 and 6 enables all defined channels.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DynamicChannelPlan;
+
+impl ChannelPlan for DynamicChannelPlan {
+    fn channel(&self, config: &DeviceConfig, index: usize) -> Option<Channel> {
+        config.channels.get(index).copied().flatten()
+    }
+    fn apply_ch_mask(
+        &self,
+        config: &DeviceConfig,
+        mask: &mut ChannelMask,
+        ch_mask_cntl: u8,
+        ch_mask: u16,
+    ) -> bool {
+        match ch_mask_cntl {
+            0 => mask.set_bank(0, ch_mask),
+            6 => {
+                for (index, channel) in config.channels.iter().enumerate() {
+                    mask.set(index, channel.is_some());
+                }
+            }
+            _ => return false,
+        }
+        true
+    }
+}
+
+/// 64 125 kHz channels followed by 8 500 kHz channels, e.g. US915.
+///
+/// ChMaskCntl 0 to 4 apply ChMask to the channels `16 * ChMaskCntl` to `16 * ChMaskCntl + 15`.
+/// 5 enables the sub-bands selected by the 8 LSBs of ChMask, each being 8 125 kHz channels
+/// and one 500 kHz channel. 6 and 7 enable, respectively disable, all 125 kHz channels and
+/// apply ChMask to the 500 kHz channels.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct FixedChannelPlan {
+    /// Frequency of channel 0 in Hz, the 125 kHz channels are 200 kHz apart
+    pub first_frequency_125: u32,
+    pub min_dr_125: u8,
+    pub max_dr_125: u8,
+    /// Frequency of channel 64 in Hz, the 500 kHz channels are 1.6 MHz apart
+    pub first_frequency_500: u32,
+    pub dr_500: u8,
+}
+
+impl FixedChannelPlan {
+    /// Mask enabling the sub-bands set in `sub_bands`, bit `n` enabling the 125 kHz channels
+    /// `8 * n` to `8 * n + 7` and the 500 kHz channel `64 + n`
+    pub fn sub_band_mask(sub_bands: u8) -> ChannelMask {
+        let mut mask = ChannelMask::default();
+        for sub_band in (0..8).filter(|sub_band| sub_bands & (1 << sub_band) != 0) {
+            for channel in 8 * sub_band..8 * sub_band + 8 {
+                mask.set(channel, true);
+            }
+            mask.set(64 + sub_band, true);
+        }
+        mask
+    }
+}
+
+impl ChannelPlan for FixedChannelPlan {
+    fn supports_new_channel(&self) -> bool {
+        false
+    }
+    fn channel(&self, _config: &DeviceConfig, index: usize) -> Option<Channel> {
+        match index {
+            0..64 => Some(Channel::new(
+                self.first_frequency_125 + 200_000 * index as u32,
+                self.min_dr_125,
+                self.max_dr_125,
+            )),
+            64..72 => Some(Channel::new(
+                self.first_frequency_500 + 1_600_000 * (index - 64) as u32,
+                self.dr_500,
+                self.dr_500,
+            )),
+            _ => None,
+        }
+    }
+    fn apply_ch_mask(
+        &self,
+        _config: &DeviceConfig,
+        mask: &mut ChannelMask,
+        ch_mask_cntl: u8,
+        ch_mask: u16,
+    ) -> bool {
+        match ch_mask_cntl {
+            0..=3 => mask.set_bank(ch_mask_cntl as usize, ch_mask),
+            4 => mask.set_bank(4, ch_mask & 0xff),
+            5 => *mask = Self::sub_band_mask(ch_mask as u8),
+            6 | 7 => {
+                let banks = if ch_mask_cntl == 6 { 0xffff } else { 0 };
+                for bank in 0..4 {
+                    mask.set_bank(bank, banks);
+                }
+                mask.set_bank(4, ch_mask & 0xff);
+            }
+            _ => return false,
+        }
+        true
+    }
+}
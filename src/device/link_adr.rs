@@ -0,0 +1,254 @@
+//! LinkADRReq block processing.
+//!
+//! Contiguous LinkADRReq commands form a block: their ChMasks are applied in order and the
+//! DataRate, TXPower and NbTrans of the last command are used. The block is accepted or
+//! rejected as a whole and every command is answered with the same LinkADRAns.
+use crate::mac_command::{downlink::LinkADRReq, uplink::LinkAdrAnsStatus};
+
+use super::{channel_plan::ChannelPlan, ChannelMask, DeviceConfig};
+
+/// Parameters of an accepted LinkADRReq block
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct LinkAdrParams {
+    pub channel_mask: ChannelMask,
+    pub data_rate: u8,
+    pub tx_power: u8,
+    pub nb_trans: u8,
+}
+
+impl LinkAdrParams {
+    pub fn apply(&self, config: &mut DeviceConfig) {
+        config.channel_mask = self.channel_mask;
+        config.data_rate = self.data_rate;
+        config.tx_power = self.tx_power;
+        config.nb_trans = self.nb_trans;
+    }
+}
+
+/// Accumulates a block command by command
+pub(crate) struct LinkAdrBlock {
+    channel_mask: ChannelMask,
+    ch_mask_cntl_ok: bool,
+    data_rate: u8,
+    tx_power: u8,
+    nb_trans: u8,
+    len: usize,
+}
+
+impl LinkAdrBlock {
+    pub(crate) fn new(config: &DeviceConfig) -> Self {
+        Self {
+            channel_mask: config.channel_mask,
+            ch_mask_cntl_ok: true,
+            data_rate: 0xf,
+            tx_power: 0xf,
+            nb_trans: 0,
+            len: 0,
+        }
+    }
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+    pub(crate) fn push<P: ChannelPlan>(
+        &mut self,
+        plan: &P,
+        config: &DeviceConfig,
+        req: &LinkADRReq,
+    ) {
+        self.ch_mask_cntl_ok &= plan.apply_ch_mask(
+            config,
+            &mut self.channel_mask,
+            req.redundancy.ch_mask_cntl(),
+            u16::from_le_bytes(req.ch_mask),
+        );
+        self.data_rate = req.data_rate_tx_power.data_rate();
+        self.tx_power = req.data_rate_tx_power.tx_power();
+        self.nb_trans = req.redundancy.nb_trans();
+        self.len += 1;
+    }
+    pub(crate) fn finish<P: ChannelPlan>(
+        self,
+        plan: &P,
+        config: &DeviceConfig,
+    ) -> Result<LinkAdrParams, LinkAdrAnsStatus> {
+        let channel_mask = self.channel_mask;
+        let channel_mask_ack = self.ch_mask_cntl_ok
+            && !channel_mask.is_empty()
+            && channel_mask
+                .iter()
+                .all(|index| plan.channel(config, index).is_some());
+        // 0xF keeps the current value
+        let data_rate = match self.data_rate {
+            0xf => config.data_rate,
+            data_rate => data_rate,
+        };
+        let data_rate_ack = self.data_rate == 0xf
+            || (data_rate <= config.limits.max_data_rate
+                && channel_mask.iter().any(|index| {
+                    plan.channel(config, index)
+                        .is_some_and(|channel| channel.supports(data_rate))
+                }));
+        let tx_power = match self.tx_power {
+            0xf => config.tx_power,
+            tx_power => tx_power,
+        };
+        let power_ack = tx_power <= config.limits.max_tx_power;
+        if channel_mask_ack && data_rate_ack && power_ack {
+            Ok(LinkAdrParams {
+                channel_mask,
+                data_rate,
+                tx_power,
+                nb_trans: match self.nb_trans {
+                    0 => config.nb_trans,
+                    nb_trans => nb_trans,
+                },
+            })
+        } else {
+            Err(LinkAdrAnsStatus::new()
+                .with_channel_mask_ack(channel_mask_ack)
+                .with_data_rate_ack(data_rate_ack)
+                .with_power_ack(power_ack))
+        }
+    }
+}
+
+/// Processes a block of contiguous LinkADRReq commands against the current configuration.
+///
+/// Returns the new parameters, or the status to answer with if any of them is rejected, in
+/// which case the configuration must be left unchanged.
+pub fn process_link_adr_block<'a, P, I>(
+    plan: &P,
+    config: &DeviceConfig,
+    reqs: I,
+) -> Result<LinkAdrParams, LinkAdrAnsStatus>
+where
+    P: ChannelPlan,
+    I: IntoIterator<Item = &'a LinkADRReq>,
+{
+    let mut block = LinkAdrBlock::new(config);
+    for req in reqs {
+        block.push(plan, config, req);
+    }
+    block.finish(plan, config)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        device::{
+            channel_plan::{DynamicChannelPlan, FixedChannelPlan},
+            Channel, Limits,
+        },
+        mac_command::downlink::{DataRateTXPower, Redundancy},
+    };
+
+    use super::*;
+    const US915: FixedChannelPlan = FixedChannelPlan {
+        first_frequency_125: 902_300_000,
+        min_dr_125: 0,
+        max_dr_125: 3,
+        first_frequency_500: 903_000_000,
+        dr_500: 4,
+    };
+    fn req(
+        data_rate: u8,
+        tx_power: u8,
+        ch_mask_cntl: u8,
+        ch_mask: u16,
+        nb_trans: u8,
+    ) -> LinkADRReq {
+        LinkADRReq {
+            data_rate_tx_power: DataRateTXPower::new()
+                .with_data_rate(data_rate)
+                .with_tx_power(tx_power),
+            ch_mask: ch_mask.to_le_bytes(),
+            redundancy: Redundancy::new()
+                .with_ch_mask_cntl(ch_mask_cntl)
+                .with_nb_trans(nb_trans),
+        }
+    }
+    fn eu_config() -> DeviceConfig {
+        let limits = Limits {
+            min_frequency: 863_000_000,
+            max_frequency: 870_000_000,
+            max_data_rate: 7,
+            max_dl_data_rate: 7,
+            max_tx_power: 7,
+            max_rx1_dr_offset: 5,
+            default_channels: 3,
+            tx_param_setup: false,
+        };
+        let mut config = DeviceConfig::new(limits, 0, 869_525_000);
+        for (index, frequency) in [868_100_000, 868_300_000, 868_500_000].iter().enumerate() {
+            config.set_channel(index, Some(Channel::new(*frequency, 0, 5)));
+        }
+        config
+    }
+    fn us_config() -> DeviceConfig {
+        let limits = Limits {
+            min_frequency: 902_000_000,
+            max_frequency: 928_000_000,
+            max_data_rate: 4,
+            max_dl_data_rate: 13,
+            max_tx_power: 14,
+            max_rx1_dr_offset: 3,
+            default_channels: 72,
+            tx_param_setup: false,
+        };
+        let mut config = DeviceConfig::new(limits, 8, 923_300_000);
+        config.channel_mask = ChannelMask::all(72);
+        config
+    }
+    #[test]
+    fn dynamic_block() {
+        let config = eu_config();
+        let reqs = [req(3, 0xf, 0, 0x0001, 0), req(5, 2, 0, 0x0006, 0)];
+        let params = process_link_adr_block(&DynamicChannelPlan, &config, &reqs).unwrap();
+        assert_eq!(
+            params,
+            LinkAdrParams {
+                channel_mask: ChannelMask::new([0x0006, 0, 0, 0, 0]),
+                data_rate: 5,
+                tx_power: 2,
+                nb_trans: 1,
+            }
+        );
+    }
+    #[test]
+    fn fixed_sub_band() {
+        let mut config = us_config();
+        let reqs = [req(0, 0, 7, 0x0000, 1), req(3, 5, 0, 0xff00, 2)];
+        let params = process_link_adr_block(&US915, &config, &reqs).unwrap();
+        assert_eq!(params.channel_mask, ChannelMask::new([0xff00, 0, 0, 0, 0]));
+        assert_eq!(
+            (params.data_rate, params.tx_power, params.nb_trans),
+            (3, 5, 2)
+        );
+        params.apply(&mut config);
+
+        let params = process_link_adr_block(&US915, &config, &[req(4, 0xf, 5, 0x0002, 0)]);
+        assert_eq!(
+            params.unwrap().channel_mask,
+            ChannelMask::new([0xff00, 0, 0, 0, 0x0002])
+        );
+    }
+    #[test]
+    fn reject_block() {
+        let config = us_config();
+        // disabling every channel rejects the whole block, including the valid TXPower
+        let reqs = [req(2, 5, 7, 0x0000, 1)];
+        assert_eq!(
+            process_link_adr_block(&US915, &config, &reqs),
+            Err(LinkAdrAnsStatus::new().with_power_ack(true))
+        );
+        // ChMaskCntl 5 is RFU in dynamic plans, even if the following ChMask is valid
+        let reqs = [req(2, 5, 5, 0x0001, 1), req(2, 5, 0, 0x0001, 1)];
+        assert_eq!(
+            process_link_adr_block(&DynamicChannelPlan, &eu_config(), &reqs),
+            Err(LinkAdrAnsStatus::new()
+                .with_data_rate_ack(true)
+                .with_power_ack(true))
+        );
+    }
+}
@@ -0,0 +1,167 @@
+//! End-device configuration, as changed by the network with MAC commands.
+use core::time::Duration;
+
+use crate::session::RxSettings;
+
+pub mod adr_backoff;
+pub mod channel_plan;
+pub mod link_adr;
+pub mod processor;
+
+/// Default ADR_ACK_LIMIT
+pub const ADR_ACK_LIMIT: u16 = 64;
+/// Default ADR_ACK_DELAY
+pub const ADR_ACK_DELAY: u16 = 32;
+
+/// Number of channels that can be defined with NewChannelReq
+pub const MAX_CHANNELS: usize = 16;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Channel {
+    /// Uplink frequency in Hz
+    pub frequency: u32,
+    /// RX1 frequency in Hz, the uplink frequency unless changed by DlChannelReq
+    pub dl_frequency: u32,
+    pub min_dr: u8,
+    pub max_dr: u8,
+}
+impl Channel {
+    pub const fn new(frequency: u32, min_dr: u8, max_dr: u8) -> Self {
+        Self {
+            frequency,
+            dl_frequency: frequency,
+            min_dr,
+            max_dr,
+        }
+    }
+    pub fn supports(&self, data_rate: u8) -> bool {
+        (self.min_dr..=self.max_dr).contains(&data_rate)
+    }
+}
+
+/// Enabled uplink channels, up to 80 for the regions with fixed channel plans
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ChannelMask([u16; 5]);
+impl ChannelMask {
+    pub fn new(banks: [u16; 5]) -> Self {
+        Self(banks)
+    }
+    /// Mask with channels `0..channels` enabled
+    pub fn all(channels: usize) -> Self {
+        let mut mask = Self::default();
+        for channel in 0..channels.min(80) {
+            mask.set(channel, true);
+        }
+        mask
+    }
+    pub fn is_enabled(&self, channel: usize) -> bool {
+        channel < 80 && self.0[channel / 16] & (1 << (channel % 16)) != 0
+    }
+    pub fn set(&mut self, channel: usize, enabled: bool) {
+        if channel >= 80 {
+            return;
+        }
+        match enabled {
+            true => self.0[channel / 16] |= 1 << (channel % 16),
+            false => self.0[channel / 16] &= !(1 << (channel % 16)),
+        }
+    }
+    /// Channels `16 * bank..16 * bank + 16`, as sent in ChMask
+    pub fn bank(&self, bank: usize) -> u16 {
+        self.0[bank]
+    }
+    pub fn set_bank(&mut self, bank: usize, mask: u16) {
+        self.0[bank] = mask
+    }
+    pub fn count(&self) -> u32 {
+        self.0.iter().map(|bank| bank.count_ones()).sum()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.0.iter().all(|bank| *bank == 0)
+    }
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..80).filter(|channel| self.is_enabled(*channel))
+    }
+}
+
+/// Regional limits used to validate MAC commands
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Limits {
+    pub min_frequency: u32,
+    pub max_frequency: u32,
+    /// Highest uplink data rate
+    pub max_data_rate: u8,
+    /// Highest downlink data rate, the same as the uplink one in most regions
+    pub max_dl_data_rate: u8,
+    /// Highest TXPower index, i.e. the lowest power
+    pub max_tx_power: u8,
+    pub max_rx1_dr_offset: u8,
+    /// Number of default channels, which cannot be changed by NewChannelReq
+    pub default_channels: usize,
+    /// Whether TxParamSetupReq is implemented
+    pub tx_param_setup: bool,
+}
+impl Limits {
+    pub fn frequency_ok(&self, frequency: u32) -> bool {
+        (self.min_frequency..=self.max_frequency).contains(&frequency)
+    }
+}
+
+/// MAC layer state of an end-device
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DeviceConfig {
+    pub limits: Limits,
+    pub channels: [Option<Channel>; MAX_CHANNELS],
+    pub channel_mask: ChannelMask,
+    pub data_rate: u8,
+    pub tx_power: u8,
+    pub nb_trans: u8,
+    /// The aggregated duty cycle is limited to 1 / 2^max_duty_cycle
+    pub max_duty_cycle: u8,
+    pub rx: RxSettings,
+    /// RX2 frequency in Hz
+    pub rx2_frequency: u32,
+    /// MaxEIRP index of TxParamSetupReq
+    pub max_eirp: u8,
+    pub uplink_dwell_time: bool,
+    pub downlink_dwell_time: bool,
+    /// Uplinks without downlink after which ADRACKReq is set, see [`adr_backoff`]
+    pub adr_ack_limit: u16,
+    /// Uplinks without downlink between two steps of the ADR backoff
+    pub adr_ack_delay: u16,
+}
+impl DeviceConfig {
+    pub fn new(limits: Limits, rx2_data_rate: u8, rx2_frequency: u32) -> Self {
+        Self {
+            limits,
+            channels: [None; MAX_CHANNELS],
+            channel_mask: ChannelMask::default(),
+            data_rate: 0,
+            tx_power: 0,
+            nb_trans: 1,
+            max_duty_cycle: 0,
+            rx: RxSettings {
+                rx1_dr_offset: 0,
+                rx2_data_rate,
+                rx_delay: Duration::from_secs(1),
+            },
+            rx2_frequency,
+            max_eirp: 0,
+            uplink_dwell_time: false,
+            downlink_dwell_time: false,
+            adr_ack_limit: ADR_ACK_LIMIT,
+            adr_ack_delay: ADR_ACK_DELAY,
+        }
+    }
+    /// Defines and enables channel `index`, or removes it with `None`
+    pub fn set_channel(&mut self, index: usize, channel: Option<Channel>) {
+        if index < MAX_CHANNELS {
+            self.channels[index] = channel;
+            self.channel_mask.set(index, channel.is_some());
+        }
+    }
+}
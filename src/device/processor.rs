@@ -0,0 +1,419 @@
+use core::time::Duration;
+
+use crate::{
+    mac_command::{
+        downlink::{DlChannelReq, DownlinkMacCommand, NewChannelReq, RXParamSetupReq},
+        uplink::{
+            DIChannelAns, DIChannelAnsStatus, DevStatusAns, LinkADRAns, LinkAdrAnsStatus,
+            NewChannelAns, NewChannelAnsStatus, RXParamSetupAns, RXParamSetupAnsStatus,
+            UplinkMacCommmand,
+        },
+    },
+    Error,
+};
+
+use super::{
+    channel_plan::ChannelPlan, link_adr::LinkAdrBlock, Channel, DeviceConfig, MAX_CHANNELS,
+};
+
+/// Maximum number of answers held for the next uplink
+pub const MAX_ANSWERS: usize = 16;
+
+/// Measurements reported in DevStatusAns
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DevStatus {
+    /// 0 for an external power source, 1..=254 for the battery level, 255 if unknown
+    pub battery: u8,
+    /// SNR in dB of the frame carrying DevStatusReq
+    pub margin: i8,
+}
+
+/// Applies downlink MAC commands to a [`DeviceConfig`] and collects the answers.
+///
+/// RXParamSetupAns, DlChannelAns and RXTimingSetupAns are sticky: they are sent in every
+/// uplink until a downlink is received.
+pub struct MacProcessor {
+    answers: [UplinkMacCommmand; MAX_ANSWERS],
+    len: usize,
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for MacProcessor {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(f, "MacProcessor {{ answers: {} }}", self.answers())
+    }
+}
+
+impl Default for MacProcessor {
+    fn default() -> Self {
+        Self {
+            answers: [UplinkMacCommmand::LinkCheckReq; MAX_ANSWERS],
+            len: 0,
+        }
+    }
+}
+
+fn is_sticky(cmd: &UplinkMacCommmand) -> bool {
+    matches!(
+        cmd,
+        UplinkMacCommmand::RXParamSetupAns(_)
+            | UplinkMacCommmand::DlChannelAns(_)
+            | UplinkMacCommmand::RXTimingSetupAns
+    )
+}
+
+impl MacProcessor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Answers to send in the next uplink
+    pub fn answers(&self) -> &[UplinkMacCommmand] {
+        &self.answers[..self.len]
+    }
+    /// Drops the answers sent in an uplink, except the sticky ones
+    pub fn answers_sent(&mut self) {
+        self.retain(is_sticky)
+    }
+    fn retain(&mut self, f: impl Fn(&UplinkMacCommmand) -> bool) {
+        let mut len = 0;
+        for i in 0..self.len {
+            if f(&self.answers[i]) {
+                self.answers[len] = self.answers[i];
+                len += 1;
+            }
+        }
+        self.len = len;
+    }
+    fn push(&mut self, cmd: UplinkMacCommmand) -> Result<(), Error> {
+        if self.len == MAX_ANSWERS {
+            return Err(Error::Size {
+                expected: MAX_ANSWERS + 1,
+                actual: MAX_ANSWERS,
+            });
+        }
+        self.answers[self.len] = cmd;
+        self.len += 1;
+        Ok(())
+    }
+
+    fn finish_link_adr_block<P: ChannelPlan>(
+        &mut self,
+        plan: &P,
+        config: &mut DeviceConfig,
+        block: LinkAdrBlock,
+    ) -> Result<(), Error> {
+        let len = block.len();
+        let status = match block.finish(plan, config) {
+            Ok(params) => {
+                params.apply(config);
+                LinkAdrAnsStatus::new()
+                    .with_channel_mask_ack(true)
+                    .with_data_rate_ack(true)
+                    .with_power_ack(true)
+            }
+            Err(status) => status,
+        };
+        for _ in 0..len {
+            self.push(UplinkMacCommmand::LinkADRAns(LinkADRAns { status }))?;
+        }
+        Ok(())
+    }
+
+    /// Processes the MAC commands of a downlink. Must be called for every downlink received,
+    /// even without MAC commands, to stop sending the sticky answers.
+    ///
+    /// Contiguous LinkADRReq commands are processed as a block following the ChMaskCntl
+    /// semantics of `plan`, see [`super::link_adr`]. NewChannelReq and DlChannelReq are ignored
+    /// if `plan` does not support them. LinkCheckAns and DeviceTimeAns are left
+    /// to the application. Fails with [`Error::Size`] if there are more than [`MAX_ANSWERS`]
+    /// answers, the remaining commands are not processed.
+    pub fn process<P, I>(
+        &mut self,
+        plan: &P,
+        config: &mut DeviceConfig,
+        cmds: I,
+        dev_status: DevStatus,
+    ) -> Result<(), Error>
+    where
+        P: ChannelPlan,
+        I: IntoIterator<Item = DownlinkMacCommand>,
+    {
+        self.retain(|cmd| !is_sticky(cmd));
+        let mut link_adr_block: Option<LinkAdrBlock> = None;
+        for cmd in cmds {
+            if let DownlinkMacCommand::LinkADRReq(req) = &cmd {
+                link_adr_block
+                    .get_or_insert_with(|| LinkAdrBlock::new(config))
+                    .push(plan, config, req);
+                continue;
+            }
+            if let Some(block) = link_adr_block.take() {
+                self.finish_link_adr_block(plan, config, block)?;
+            }
+            let answer = match cmd {
+                DownlinkMacCommand::LinkADRReq(_) => continue,
+                DownlinkMacCommand::DutyCycleReq(req) => {
+                    config.max_duty_cycle = req.duty_cycle_pl.max_duty_cycle();
+                    UplinkMacCommmand::DutyCycleAns
+                }
+                DownlinkMacCommand::RXParamSetupReq(req) => rx_param_setup(config, &req),
+                DownlinkMacCommand::DevStatusReq(_) => UplinkMacCommmand::DevStatusAns(
+                    DevStatusAns::new(dev_status.battery, dev_status.margin),
+                ),
+                DownlinkMacCommand::NewChannelReq(_) | DownlinkMacCommand::DIChannelReq(_)
+                    if !plan.supports_new_channel() =>
+                {
+                    continue
+                }
+                DownlinkMacCommand::NewChannelReq(req) => new_channel(config, &req),
+                DownlinkMacCommand::RXTimingSetupReq(req) => {
+                    let delay = req.rx_timings_settings.del().max(1);
+                    config.rx.rx_delay = Duration::from_secs(delay.into());
+                    UplinkMacCommmand::RXTimingSetupAns
+                }
+                DownlinkMacCommand::TxParamSetupReq(req) => {
+                    if !config.limits.tx_param_setup {
+                        continue;
+                    }
+                    let eirp_dwell_time = req.eirp_dwell_time;
+                    config.max_eirp = eirp_dwell_time.max_eirp();
+                    config.uplink_dwell_time = eirp_dwell_time.uplink_dwell_time();
+                    config.downlink_dwell_time = eirp_dwell_time.downlink_dwell_time();
+                    UplinkMacCommmand::TxParamSetupAns
+                }
+                DownlinkMacCommand::DIChannelReq(req) => dl_channel(config, &req),
+                DownlinkMacCommand::ADRParamSetupReq(req) => {
+                    config.adr_ack_limit = 1 << req.adr_param.limit_exp();
+                    config.adr_ack_delay = 1 << req.adr_param.delay_exp();
+                    UplinkMacCommmand::ADRParamSetupAns
+                }
+                DownlinkMacCommand::LinkCheckAns(_) | DownlinkMacCommand::DeviceTimeAns(_) => {
+                    continue
+                }
+            };
+            self.push(answer)?;
+        }
+        if let Some(block) = link_adr_block {
+            self.finish_link_adr_block(plan, config, block)?;
+        }
+        Ok(())
+    }
+}
+
+fn rx_param_setup(config: &mut DeviceConfig, req: &RXParamSetupReq) -> UplinkMacCommmand {
+    let frequency = req.frequency.hz();
+    let rx1_dr_offset = req.dl_settings.rx1_dr_offset();
+    let rx2_data_rate = req.dl_settings.rx2_data_rate();
+    let status = RXParamSetupAnsStatus::new()
+        .with_channel_ack(config.limits.frequency_ok(frequency))
+        .with_rx2_data_rate_ack(rx2_data_rate <= config.limits.max_dl_data_rate)
+        .with_rx1_data_rate_offset_ack(rx1_dr_offset <= config.limits.max_rx1_dr_offset);
+    if status.channel_ack() && status.rx2_data_rate_ack() && status.rx1_data_rate_offset_ack() {
+        config.rx2_frequency = frequency;
+        config.rx.rx1_dr_offset = rx1_dr_offset;
+        config.rx.rx2_data_rate = rx2_data_rate;
+    }
+    UplinkMacCommmand::RXParamSetupAns(RXParamSetupAns { status })
+}
+
+fn new_channel(config: &mut DeviceConfig, req: &NewChannelReq) -> UplinkMacCommmand {
+    let index = req.ch_index as usize;
+    let frequency = req.frequency.hz();
+    let (min_dr, max_dr) = (req.dr_range.min_dr(), req.dr_range.max_dr());
+    let mut status = NewChannelAnsStatus::new();
+    if index >= config.limits.default_channels && index < MAX_CHANNELS {
+        if frequency == 0 {
+            config.set_channel(index, None);
+            status = status
+                .with_channel_freq_ok(true)
+                .with_data_rate_range_ok(true);
+        } else {
+            status = status
+                .with_channel_freq_ok(config.limits.frequency_ok(frequency))
+                .with_data_rate_range_ok(min_dr <= max_dr && max_dr <= config.limits.max_data_rate);
+            if status.channel_freq_ok() && status.data_rate_range_ok() {
+                config.set_channel(index, Some(Channel::new(frequency, min_dr, max_dr)));
+            }
+        }
+    }
+    UplinkMacCommmand::NewChannelAns(NewChannelAns { status })
+}
+
+fn dl_channel(config: &mut DeviceConfig, req: &DlChannelReq) -> UplinkMacCommmand {
+    let frequency = req.frequency.hz();
+    let channel = config
+        .channels
+        .get_mut(req.ch_index as usize)
+        .and_then(|channel| channel.as_mut());
+    let status = DIChannelAnsStatus::new()
+        .with_channel_frequency_ok(config.limits.frequency_ok(frequency))
+        .with_uplink_frequency_exists(channel.is_some());
+    if let Some(channel) = channel {
+        if status.channel_frequency_ok() {
+            channel.dl_frequency = frequency;
+        }
+    }
+    UplinkMacCommmand::DlChannelAns(DIChannelAns { status })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        device::{channel_plan::DynamicChannelPlan, Limits},
+        mac_command::{downlink::DownlinkMacCommandDecoder, uplink::encode_maccommands},
+        region::{us915::Us915, Region},
+    };
+
+    use super::*;
+    fn get_config() -> DeviceConfig {
+        let limits = Limits {
+            min_frequency: 863_000_000,
+            max_frequency: 870_000_000,
+            max_data_rate: 7,
+            max_dl_data_rate: 7,
+            max_tx_power: 7,
+            max_rx1_dr_offset: 5,
+            default_channels: 3,
+            tx_param_setup: false,
+        };
+        let mut config = DeviceConfig::new(limits, 0, 869_525_000);
+        for (index, frequency) in [868_100_000, 868_300_000, 868_500_000].iter().enumerate() {
+            config.set_channel(index, Some(Channel::new(*frequency, 0, 5)));
+        }
+        config
+    }
+    const DEV_STATUS: DevStatus = DevStatus {
+        battery: 200,
+        margin: -3,
+    };
+    fn encode_answers(processor: &MacProcessor) -> Vec<u8> {
+        let mut buf = [0u8; 64];
+        encode_maccommands(processor.answers(), &mut buf)
+            .unwrap()
+            .to_vec()
+    }
+    #[test]
+    fn apply_mac_commands() {
+        let mut config = get_config();
+        let mut processor = MacProcessor::new();
+        let cmds = [
+            0x03, 0x52, 0x07, 0x00, 0x02, 0x04, 0x02, 0x06, 0x05, 0x23, 0xd2, 0xad, 0x84, 0x07,
+            0x03, 0x18, 0x4f, 0x84, 0x50, 0x08, 0x03,
+        ];
+        processor
+            .process(
+                &DynamicChannelPlan,
+                &mut config,
+                DownlinkMacCommandDecoder::new(&cmds),
+                DEV_STATUS,
+            )
+            .unwrap();
+        assert_eq!(
+            encode_answers(&processor),
+            &[0x03, 0x07, 0x04, 0x06, 200, 0x3d, 0x05, 0x07, 0x07, 0x03, 0x08]
+        );
+        assert_eq!(config.data_rate, 5);
+        assert_eq!(config.tx_power, 2);
+        assert_eq!(config.nb_trans, 2);
+        assert_eq!(config.max_duty_cycle, 2);
+        assert_eq!(config.rx.rx1_dr_offset, 2);
+        assert_eq!(config.rx.rx2_data_rate, 3);
+        assert_eq!(config.rx2_frequency, 869_525_000);
+        assert_eq!(config.rx.rx_delay, Duration::from_secs(3));
+        assert_eq!(config.channels[3], Some(Channel::new(867_100_000, 0, 5)));
+        assert!(config.channel_mask.is_enabled(3));
+
+        processor.answers_sent();
+        assert_eq!(encode_answers(&processor), &[0x05, 0x07, 0x08]);
+        processor.answers_sent();
+        assert_eq!(encode_answers(&processor), &[0x05, 0x07, 0x08]);
+        processor
+            .process(&DynamicChannelPlan, &mut config, [], DEV_STATUS)
+            .unwrap();
+        assert!(processor.answers().is_empty());
+    }
+    #[test]
+    fn adr_param_setup() {
+        let mut config = get_config();
+        let mut processor = MacProcessor::new();
+        // Limit_exp 10 and Delay_exp 3
+        let cmds = [0x0c, 0xa3];
+        processor
+            .process(
+                &DynamicChannelPlan,
+                &mut config,
+                DownlinkMacCommandDecoder::new(&cmds),
+                DEV_STATUS,
+            )
+            .unwrap();
+        assert_eq!(encode_answers(&processor), &[0x0c]);
+        assert_eq!(config.adr_ack_limit, 1024);
+        assert_eq!(config.adr_ack_delay, 8);
+        processor.answers_sent();
+        assert!(processor.answers().is_empty());
+    }
+    #[test]
+    fn ignore_channel_commands_in_fixed_plan() {
+        let region = Us915::default();
+        let mut config = region.default_config();
+        let mut processor = MacProcessor::new();
+        // NewChannelReq, DlChannelReq and DutyCycleReq
+        let cmds = [
+            0x07, 0x01, 0x18, 0x4f, 0x84, 0x50, 0x0a, 0x00, 0x18, 0x4f, 0x84, 0x04, 0x00,
+        ];
+        processor
+            .process(
+                &region,
+                &mut config,
+                DownlinkMacCommandDecoder::new(&cmds),
+                DEV_STATUS,
+            )
+            .unwrap();
+        assert_eq!(encode_answers(&processor), &[0x04]);
+        assert_eq!(config, region.default_config());
+    }
+    #[test]
+    fn reject_invalid_link_adr_req() {
+        let mut config = get_config();
+        let mut processor = MacProcessor::new();
+        // channel 4 is not defined, DR7 is not supported by any channel, TXPower 8 is too low,
+        // the DutyCycleReq splits the LinkADRReq commands into two blocks
+        let cmds = [
+            0x03, 0x72, 0x17, 0x00, 0x01, 0x04, 0x00, 0x03, 0x58, 0x00, 0x00, 0x61,
+        ];
+        processor
+            .process(
+                &DynamicChannelPlan,
+                &mut config,
+                DownlinkMacCommandDecoder::new(&cmds),
+                DEV_STATUS,
+            )
+            .unwrap();
+        assert_eq!(encode_answers(&processor), &[0x03, 0x04, 0x04, 0x03, 0x03]);
+        assert_eq!(config, get_config());
+    }
+    #[test]
+    fn reject_invalid_channels() {
+        let mut config = get_config();
+        let mut processor = MacProcessor::new();
+        let cmds = [
+            0x07, 0x01, 0x18, 0x4f, 0x84, 0x50, 0x07, 0x04, 0x18, 0x0d, 0x8f, 0x50, 0x0a, 0x05,
+            0x18, 0x4f, 0x84, 0x0a, 0x00, 0x18, 0x4f, 0x84,
+        ];
+        processor
+            .process(
+                &DynamicChannelPlan,
+                &mut config,
+                DownlinkMacCommandDecoder::new(&cmds),
+                DEV_STATUS,
+            )
+            .unwrap();
+        assert_eq!(
+            encode_answers(&processor),
+            &[0x07, 0x00, 0x07, 0x02, 0x0a, 0x01, 0x0a, 0x03]
+        );
+        assert!(config.channels[4].is_none());
+        assert_eq!(config.channels[0].unwrap().dl_frequency, 867_100_000);
+    }
+}
@@ -0,0 +1,142 @@
+use crate::Error;
+
+/// Maximum accepted difference between the expected and the received frame counter
+pub const MAX_FCNT_GAP: u32 = 16384;
+
+/// 32-bit frame counter of which only the 16 least significant bits are sent on air.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct FrameCounter {
+    next: u32,
+    exhausted: bool,
+}
+
+impl FrameCounter {
+    /// `next` is the value used by the next transmitted frame, or the lowest value accepted
+    /// for the next received frame
+    pub fn new(next: u32) -> Self {
+        Self {
+            next,
+            exhausted: false,
+        }
+    }
+    /// Counter of a session that has received 2^32 - 1, which rejects every frame
+    pub fn exhausted() -> Self {
+        Self {
+            next: u32::MAX,
+            exhausted: true,
+        }
+    }
+    pub fn next(&self) -> u32 {
+        self.next
+    }
+    /// Whether 2^32 - 1 has been accepted, `next` cannot tell it from 2^32 - 2
+    pub fn is_exhausted(&self) -> bool {
+        self.exhausted
+    }
+    /// Reserves the counter value of a frame to transmit. The last value, 2^32 - 1, is
+    /// never handed out, the session must be renewed before the counter wraps.
+    pub fn increment(&mut self) -> Result<u32, Error> {
+        if self.next == u32::MAX {
+            return Err(Error::FCntExhausted);
+        }
+        let f_cnt = self.next;
+        self.next += 1;
+        Ok(f_cnt)
+    }
+    /// Reconstructs the full counter of a received frame from its 16-bit FCnt field.
+    /// Replayed frames and gaps larger than [`MAX_FCNT_GAP`] are rejected, and every frame
+    /// once the counter is exhausted.
+    pub fn reconstruct(&self, f_cnt: u16) -> Result<u32, Error> {
+        if self.exhausted {
+            return Err(Error::FCntExhausted);
+        }
+        let error = Error::FCnt {
+            expected: self.next,
+            received: f_cnt,
+        };
+        let gap = (f_cnt as u32).wrapping_sub(self.next) & 0xffff;
+        if gap > MAX_FCNT_GAP {
+            return Err(error);
+        }
+        self.next.checked_add(gap).ok_or(error)
+    }
+    /// Marks `f_cnt` of an authenticated frame as received. The counter is exhausted after
+    /// accepting 2^32 - 1, the session must be renewed.
+    pub fn accept(&mut self, f_cnt: u32) {
+        self.next = f_cnt.saturating_add(1);
+        self.exhausted |= f_cnt == u32::MAX;
+    }
+}
+
+/// Frame counters of a session.
+///
+/// LoRaWAN 1.1 uses NFCntDown for downlinks without FRMPayload or on FPort 0 and AFCntDown
+/// for all other downlinks, see [`FrameCounters::down_mut`]. LoRaWAN 1.0.x has a single
+/// FCntDown, held in `n_f_cnt_down`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct FrameCounters {
+    pub f_cnt_up: FrameCounter,
+    pub n_f_cnt_down: FrameCounter,
+    pub a_f_cnt_down: FrameCounter,
+}
+
+impl FrameCounters {
+    /// LoRaWAN 1.1 downlink counter for a frame with `f_port`
+    pub fn down_mut(&mut self, f_port: Option<u8>) -> &mut FrameCounter {
+        match f_port {
+            None | Some(0) => &mut self.n_f_cnt_down,
+            Some(_) => &mut self.a_f_cnt_down,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn reconstruct_f_cnt() {
+        let counter = FrameCounter::new(0);
+        assert_eq!(counter.reconstruct(0).unwrap(), 0);
+        assert_eq!(counter.reconstruct(16384).unwrap(), 16384);
+        assert!(counter.reconstruct(16385).is_err());
+        let counter = FrameCounter::new(0x1fff0);
+        assert_eq!(counter.reconstruct(0xfff5).unwrap(), 0x1fff5);
+        assert_eq!(counter.reconstruct(0x0002).unwrap(), 0x20002);
+    }
+    #[test]
+    fn reject_replay() {
+        let mut counter = FrameCounter::new(0);
+        let f_cnt = counter.reconstruct(5).unwrap();
+        counter.accept(f_cnt);
+        assert_eq!(counter.next(), 6);
+        assert!(counter.reconstruct(5).is_err());
+        assert!(counter.reconstruct(4).is_err());
+        assert_eq!(counter.reconstruct(6).unwrap(), 6);
+    }
+    #[test]
+    fn reject_after_last_f_cnt() {
+        let mut counter = FrameCounter::new(u32::MAX - 1);
+        let f_cnt = counter.reconstruct(0xffff).unwrap();
+        assert_eq!(f_cnt, u32::MAX);
+        counter.accept(f_cnt);
+        assert_eq!(counter.reconstruct(0xffff), Err(Error::FCntExhausted));
+        assert_eq!(counter.reconstruct(0), Err(Error::FCntExhausted));
+    }
+    #[test]
+    fn increment_f_cnt() {
+        let mut counter = FrameCounter::new(u32::MAX - 1);
+        assert_eq!(counter.increment().unwrap(), u32::MAX - 1);
+        assert!(counter.increment().is_err());
+    }
+    #[test]
+    fn select_downlink_counter() {
+        let mut counters = FrameCounters::default();
+        counters.down_mut(Some(1)).accept(9);
+        counters.down_mut(None).accept(2);
+        assert_eq!(counters.a_f_cnt_down.next(), 10);
+        assert_eq!(counters.n_f_cnt_down.next(), 3);
+        assert_eq!(counters.f_cnt_up.next(), 0);
+    }
+}
@@ -1,12 +1,211 @@
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+pub mod adr;
+pub mod airtime;
+pub mod class_a;
+pub mod class_b;
 pub mod crypto;
+pub mod device;
+pub mod frame_counter;
 pub mod mac_command;
+#[cfg(feature = "multicast")]
+pub mod multicast;
 pub mod phy_payload;
+pub mod region;
+pub mod session;
 pub mod types;
 
-#[derive(Debug)]
+/// Errors of frame and MAC command encoding and decoding, and of the MAC layer engines
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum Error {
-    Size,
-    FPort,
-    Payload,
+    /// A buffer is smaller than required, `expected` and `actual` are lengths in bytes
+    Size {
+        expected: usize,
+        actual: usize,
+    },
+    /// The MHDR does not match the expected message type
+    Mhdr(u8),
+    /// FOpts longer than the frame or than the 15 bytes they can hold
+    FOptsLen {
+        f_opts_len: usize,
+        available: usize,
+    },
+    /// Unknown MAC command identifier at `offset` of the commands
+    UnknownCid {
+        cid: u8,
+        offset: usize,
+    },
+    /// MAC command at `offset` with fewer payload bytes than its CID requires
+    TruncatedCommand {
+        cid: u8,
+        offset: usize,
+        expected: usize,
+        actual: usize,
+    },
+    /// MAC command at `offset` with invalid field values
+    InvalidCommand {
+        cid: u8,
+        offset: usize,
+    },
+    /// FPort not allowed for the frame
+    FPort(Option<u8>),
+    /// The frame is addressed to another device or multicast group
+    DevAddr,
+    /// The frame is not allowed on a multicast address
+    Multicast,
+    /// No receive window is open
+    State,
     MIC,
+    /// Received frame counter out of the accepted window
+    FCnt {
+        expected: u32,
+        received: u16,
+    },
+    /// The frame counter reached its maximum, the session must be renewed
+    FCntExhausted,
+    Storage,
+    Nonce,
+    /// The MACPayload exceeds the regional maximum for the data rate and dwell time, `max` is
+    /// 0 for a data rate that cannot be used
+    MaxPayloadSize {
+        max: usize,
+        actual: usize,
+    },
+    /// A string that is not `expected` bytes of hex, optionally separated by `'-'` or `':'`
+    Hex {
+        expected: usize,
+    },
+}
+
+#[cfg(feature = "multicast")]
+impl Error {
+    /// Moves the offset of a MAC command error by `offset` bytes
+    pub(crate) fn offset_by(self, offset: usize) -> Self {
+        match self {
+            Error::UnknownCid { cid, offset: o } => Error::UnknownCid {
+                cid,
+                offset: o + offset,
+            },
+            Error::TruncatedCommand {
+                cid,
+                offset: o,
+                expected,
+                actual,
+            } => Error::TruncatedCommand {
+                cid,
+                offset: o + offset,
+                expected,
+                actual,
+            },
+            Error::InvalidCommand { cid, offset: o } => Error::InvalidCommand {
+                cid,
+                offset: o + offset,
+            },
+            e => e,
+        }
+    }
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::Size { expected, actual } => {
+                write!(f, "buffer of {actual} bytes, {expected} required")
+            }
+            Error::Mhdr(mhdr) => write!(f, "unexpected MHDR {mhdr:#04x}"),
+            Error::FOptsLen {
+                f_opts_len,
+                available,
+            } => write!(f, "{f_opts_len} bytes of FOpts, {available} available"),
+            Error::UnknownCid { cid, offset } => {
+                write!(f, "unknown MAC command {cid:#04x} at offset {offset}")
+            }
+            Error::TruncatedCommand {
+                cid,
+                offset,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "MAC command {cid:#04x} at offset {offset} truncated to {actual} of {expected} bytes"
+            ),
+            Error::InvalidCommand { cid, offset } => {
+                write!(f, "invalid MAC command {cid:#04x} at offset {offset}")
+            }
+            Error::FPort(Some(f_port)) => write!(f, "FPort {f_port} not allowed"),
+            Error::FPort(None) => write!(f, "missing FPort"),
+            Error::DevAddr => write!(f, "frame addressed to another device"),
+            Error::Multicast => write!(f, "frame not allowed on a multicast address"),
+            Error::State => write!(f, "no receive window open"),
+            Error::MIC => write!(f, "invalid MIC"),
+            Error::FCnt { expected, received } => {
+                write!(f, "FCnt {received:#06x} rejected, expected from {expected}")
+            }
+            Error::FCntExhausted => write!(f, "frame counter exhausted"),
+            Error::Storage => write!(f, "storage failure"),
+            Error::Nonce => write!(f, "invalid or exhausted nonce"),
+            Error::MaxPayloadSize { max, actual } => {
+                write!(f, "MACPayload of {actual} bytes, {max} allowed")
+            }
+            Error::Hex { expected } => write!(f, "invalid hex string, expected {expected} bytes"),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
+
+#[cfg(all(test, feature = "defmt"))]
+mod tests {
+    use crate::{
+        class_a::{Event, Output},
+        mac_command::downlink::DownlinkMacCommandDecoder,
+        mac_command::{downlink::DownlinkMacCommand, uplink::UplinkMacCommmand},
+        phy_payload::{
+            join_accept::{CFList, JoinAccept},
+            join_request::JoinRequest,
+            mac_payload::{downlink::Downlink, uplink::Uplink, FRMPayload, FHDR},
+        },
+        session::{otaa::JoinAction, SessionKeys, SessionState},
+        types::{AppKey, DevAddr, DevEui, JoinEui, NwkSKey},
+    };
+
+    fn format<T: defmt::Format + ?Sized>() {}
+
+    #[test]
+    fn defmt_format() {
+        format::<crate::Error>();
+        format::<crate::adr::Adr>();
+        format::<Uplink>();
+        format::<Downlink>();
+        format::<FHDR>();
+        format::<FRMPayload>();
+        format::<JoinAccept>();
+        format::<JoinRequest>();
+        format::<CFList>();
+        format::<UplinkMacCommmand>();
+        format::<DownlinkMacCommand>();
+        format::<Event>();
+        format::<Output>();
+        format::<JoinAction>();
+        format::<crate::device::processor::MacProcessor>();
+        format::<crate::class_b::ping_slot::PingSlots>();
+        format::<DownlinkMacCommandDecoder>();
+        format::<SessionKeys>();
+        format::<SessionState>();
+        format::<DevEui>();
+        format::<JoinEui>();
+        format::<DevAddr>();
+        format::<NwkSKey>();
+        format::<AppKey>();
+        #[cfg(feature = "certification")]
+        format::<crate::mac_command::certification::downlink::CertificationDownlinkMacCommand>();
+        #[cfg(feature = "multicast")]
+        format::<crate::multicast::downlink::MulticastDownlinkCommand>();
+        #[cfg(feature = "multicast")]
+        format::<crate::multicast::downlink::MulticastDownlinkCommandDecoder>();
+    }
 }
@@ -1,6 +1,6 @@
 use zerocopy::{little_endian::U16, Immutable, KnownLayout, TryFromBytes, Unaligned};
 
-use crate::Error;
+use crate::{mac_command::command_payload, Error};
 
 use super::cid::*;
 
@@ -44,42 +44,68 @@ pub enum CertificationDownlinkMacCommand<'a> {
     DutVersionsReq = DUT_VERSION_CID,
 }
 
+#[cfg(feature = "defmt")]
+impl defmt::Format for CertificationDownlinkMacCommand<'_> {
+    fn format(&self, f: defmt::Formatter) {
+        match self {
+            Self::PackageVersionReq => defmt::write!(f, "PackageVersionReq"),
+            Self::DutResetReq => defmt::write!(f, "DutResetReq"),
+            Self::DutJoinReq => defmt::write!(f, "DutJoinReq"),
+            Self::SwitchClassReq(req) => defmt::write!(f, "SwitchClassReq({})", req),
+            Self::AdrBitChangeReq(adr) => defmt::write!(f, "AdrBitChangeReq({=bool})", adr),
+            Self::RegionalDutyCycleCtrlReq(enabled) => {
+                defmt::write!(f, "RegionalDutyCycleCtrlReq({=bool})", enabled)
+            }
+            Self::TxPeriodicityChangeReq(periodicity) => {
+                defmt::write!(f, "TxPeriodicityChangeReq({=u8})", periodicity)
+            }
+            Self::TxFramesCtrlReq(req) => defmt::write!(f, "TxFramesCtrlReq({})", req),
+            Self::EchoPayloadReq(payload) => {
+                defmt::write!(f, "EchoPayloadReq({=[u8]:02x})", payload)
+            }
+            Self::RxAppCntReq(cnt) => defmt::write!(f, "RxAppCntReq({=u16})", cnt.get()),
+            Self::RxAppCntResetReq => defmt::write!(f, "RxAppCntResetReq"),
+            Self::LinkCheckReq => defmt::write!(f, "LinkCheckReq"),
+            Self::DeviceTimeReq => defmt::write!(f, "DeviceTimeReq"),
+            Self::PingSlotInfoReq(periodicity) => {
+                defmt::write!(f, "PingSlotInfoReq({=u8})", periodicity)
+            }
+            Self::TxCwReq => defmt::write!(f, "TxCwReq"),
+            Self::DutFPort224DisableReq => defmt::write!(f, "DutFPort224DisableReq"),
+            Self::DutVersionsReq => defmt::write!(f, "DutVersionsReq"),
+        }
+    }
+}
+
 impl<'a> CertificationDownlinkMacCommand<'a> {
     pub fn decode(buf: &'a [u8]) -> Result<Self, Error> {
-        if buf.is_empty() {
-            return Err(Error::Size);
-        }
-        match buf[0] {
+        let Some(&cid) = buf.first() else {
+            return Err(Error::Size {
+                expected: 1,
+                actual: 0,
+            });
+        };
+        match cid {
             PACKET_VERSION_CID => Ok(Self::PackageVersionReq),
             DUT_RESET_CID => Ok(Self::DutResetReq),
             DUT_JOIN_CID => Ok(Self::DutJoinReq),
-            SWITCH_CLASS_CID => Ok(Self::SwitchClassReq(
-                &TryFromBytes::try_ref_from_prefix(&buf[1..])
-                    .map_err(|_| Error::Payload)?
-                    .0,
-            )),
-            ADR_BIT_CHANGE_CID => Ok(Self::AdrBitChangeReq(buf[1] == 1)),
-            REGIONAL_DUTY_CYCLE_CID => Ok(Self::RegionalDutyCycleCtrlReq(buf[1] == 1)),
-            TX_PERIODICITY_CHANGE_CID => Ok(Self::TxPeriodicityChangeReq(buf[1])),
-            TX_FRAMES_CTRL_CID => Ok(Self::TxFramesCtrlReq(
-                TryFromBytes::try_ref_from_prefix(&buf[1..])
-                    .map_err(|_| Error::Payload)?
-                    .0,
+            SWITCH_CLASS_CID => Ok(Self::SwitchClassReq(command_payload(buf)?)),
+            ADR_BIT_CHANGE_CID => Ok(Self::AdrBitChangeReq(*command_payload::<u8>(buf)? == 1)),
+            REGIONAL_DUTY_CYCLE_CID => Ok(Self::RegionalDutyCycleCtrlReq(
+                *command_payload::<u8>(buf)? == 1,
             )),
+            TX_PERIODICITY_CHANGE_CID => Ok(Self::TxPeriodicityChangeReq(*command_payload(buf)?)),
+            TX_FRAMES_CTRL_CID => Ok(Self::TxFramesCtrlReq(command_payload(buf)?)),
             ECHO_PAYLOAD_CID => Ok(Self::EchoPayloadReq(&buf[1..])),
-            RX_APP_CNT_CID => Ok(Self::RxAppCntReq(
-                TryFromBytes::try_ref_from_prefix(&buf[1..])
-                    .map_err(|_| Error::Payload)?
-                    .0,
-            )),
+            RX_APP_CNT_CID => Ok(Self::RxAppCntReq(command_payload(buf)?)),
             RX_APP_CNT_RESET_CID => Ok(Self::RxAppCntResetReq),
             LINK_CHECK_CID => Ok(Self::LinkCheckReq),
             DEVICE_TIME_CID => Ok(Self::DeviceTimeReq),
-            PING_SLOT_INFO_CID => Ok(Self::PingSlotInfoReq(buf[1])),
+            PING_SLOT_INFO_CID => Ok(Self::PingSlotInfoReq(*command_payload(buf)?)),
             TX_CW_CID => Ok(Self::TxCwReq),
             DUT_FPORT_224_DISABLE_CID => Ok(Self::DutFPort224DisableReq),
             DUT_VERSION_CID => Ok(Self::DutVersionsReq),
-            _ => Err(Error::Payload),
+            _ => Err(Error::UnknownCid { cid, offset: 0 }),
         }
     }
     // pub fn len(&self) -> usize {
@@ -106,6 +132,7 @@ impl<'a> CertificationDownlinkMacCommand<'a> {
 }
 
 #[derive(Clone, Debug, PartialEq, TryFromBytes, Immutable, KnownLayout, Unaligned)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[repr(u8)]
 pub enum SwitchClassReq {
     A = 0,
@@ -114,12 +141,14 @@ pub enum SwitchClassReq {
 }
 
 #[derive(Clone, Debug, PartialEq, TryFromBytes, Immutable, KnownLayout, Unaligned)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[repr(C)]
 pub struct TxFramesCtrlReq {
     frame_type: TxFramesCtrlReqFrameType,
 }
 
 #[derive(Clone, Debug, PartialEq, TryFromBytes, Immutable, KnownLayout, Unaligned)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[repr(u8)]
 pub enum TxFramesCtrlReqFrameType {
     NoChange = 0,
@@ -145,7 +174,19 @@ mod tests {
         if let CertificationDownlinkMacCommand::EchoPayloadReq(payload) = command {
             assert_eq!(payload, &[0x01, 0x02, 0x03]);
         } else {
-            assert!(false, "Wrong command type")
+            panic!("Wrong command type")
         }
     }
+    #[test]
+    fn decode_truncated() {
+        assert_eq!(
+            CertificationDownlinkMacCommand::decode(&[PING_SLOT_INFO_CID]).err(),
+            Some(Error::TruncatedCommand {
+                cid: PING_SLOT_INFO_CID,
+                offset: 0,
+                expected: 1,
+                actual: 0
+            })
+        );
+    }
 }
@@ -1,6 +1,7 @@
 use zerocopy::{Immutable, IntoBytes, KnownLayout, Unaligned};
 
 #[repr(u8)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum CertificationUplinkMacCommand {
     /// Conveys the answer to PackageVersionReq
     PackageVersionAns(PackageVersionAns) = 0x00,
@@ -13,6 +14,7 @@ pub enum CertificationUplinkMacCommand {
 }
 
 #[derive(Clone, Debug, IntoBytes, Immutable, KnownLayout, Unaligned)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[repr(C)]
 pub struct PackageVersionAns {
     package_identifier: u8,
@@ -1,13 +1,18 @@
 #![allow(non_camel_case_types)]
 use bitfield_struct::bitfield;
-use zerocopy::{Immutable, KnownLayout, TryFromBytes, Unaligned};
+use zerocopy::{Immutable, IntoBytes, KnownLayout, TryFromBytes, Unaligned};
+
+use crate::Error;
 
 use super::{
-    DEVICE_TIME_CID, DEV_STATUS_CID, DI_CHANNEL_CID, DUTY_CYCLE_CID, LINK_ADR_CID, LINK_CHECK_CID,
-    NEW_CHANNEL_CID, RX_PARAM_SETUP_CID, RX_TIMING_SETUP_CID, TX_PARAM_SETUP_CID,
+    ADR_PARAM_SETUP_CID, DEVICE_TIME_CID, DEV_STATUS_CID, DI_CHANNEL_CID, DUTY_CYCLE_CID,
+    LINK_ADR_CID, LINK_CHECK_CID, NEW_CHANNEL_CID, RX_PARAM_SETUP_CID, RX_TIMING_SETUP_CID,
+    TX_PARAM_SETUP_CID,
 };
 
 #[derive(Clone, Debug, TryFromBytes, Immutable, KnownLayout, Unaligned)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 pub enum DownlinkMacCommand {
     LinkCheckAns(LinkCheckAns) = LINK_CHECK_CID,
@@ -19,9 +24,29 @@ pub enum DownlinkMacCommand {
     RXTimingSetupReq(RXTimingSetupReq) = RX_TIMING_SETUP_CID,
     TxParamSetupReq(TxParamSetupReq) = TX_PARAM_SETUP_CID,
     DIChannelReq(DlChannelReq) = DI_CHANNEL_CID,
+    ADRParamSetupReq(ADRParamSetupReq) = ADR_PARAM_SETUP_CID,
     DeviceTimeAns(DeviceTimeAns) = DEVICE_TIME_CID,
 }
 impl DownlinkMacCommand {
+    // https://doc.rust-lang.org/reference/items/enumerations.html#pointer-casting
+    fn cid(&self) -> u8 {
+        unsafe { *((self as *const Self) as *const u8) }
+    }
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            DownlinkMacCommand::LinkCheckAns(cmd) => cmd.as_bytes(),
+            DownlinkMacCommand::LinkADRReq(cmd) => cmd.as_bytes(),
+            DownlinkMacCommand::DutyCycleReq(cmd) => cmd.as_bytes(),
+            DownlinkMacCommand::RXParamSetupReq(cmd) => cmd.as_bytes(),
+            DownlinkMacCommand::DevStatusReq(cmd) => cmd.as_bytes(),
+            DownlinkMacCommand::NewChannelReq(cmd) => cmd.as_bytes(),
+            DownlinkMacCommand::RXTimingSetupReq(cmd) => cmd.as_bytes(),
+            DownlinkMacCommand::TxParamSetupReq(cmd) => cmd.as_bytes(),
+            DownlinkMacCommand::DIChannelReq(cmd) => cmd.as_bytes(),
+            DownlinkMacCommand::ADRParamSetupReq(cmd) => cmd.as_bytes(),
+            DownlinkMacCommand::DeviceTimeAns(cmd) => cmd.as_bytes(),
+        }
+    }
     #[allow(clippy::len_without_is_empty)]
     pub fn len(&self) -> usize {
         match self {
@@ -34,19 +59,26 @@ impl DownlinkMacCommand {
             DownlinkMacCommand::RXTimingSetupReq(cmd) => size_of_val(cmd),
             DownlinkMacCommand::TxParamSetupReq(cmd) => size_of_val(cmd),
             DownlinkMacCommand::DIChannelReq(cmd) => size_of_val(cmd),
+            DownlinkMacCommand::ADRParamSetupReq(cmd) => size_of_val(cmd),
             DownlinkMacCommand::DeviceTimeAns(cmd) => size_of_val(cmd),
         }
     }
 }
 
-#[derive(Clone, Debug, TryFromBytes, Immutable, KnownLayout, Unaligned)]
+#[derive(Clone, Debug, TryFromBytes, IntoBytes, Immutable, KnownLayout, Unaligned)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
 #[repr(C)]
 pub struct LinkCheckAns {
     pub gw_cnt: u8,
     pub margin: u8,
 }
 
-#[derive(Clone, Debug, TryFromBytes, Immutable, KnownLayout, Unaligned)]
+#[derive(Clone, Debug, TryFromBytes, IntoBytes, Immutable, KnownLayout, Unaligned)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
 #[repr(C)]
 pub struct LinkADRReq {
     pub data_rate_tx_power: DataRateTXPower,
@@ -54,137 +86,225 @@ pub struct LinkADRReq {
     pub redundancy: Redundancy,
 }
 
-#[bitfield(u8)]
-#[derive(PartialEq, TryFromBytes, Immutable, KnownLayout, Unaligned)]
+#[bitfield(u8, defmt = cfg(feature = "defmt"))]
+#[derive(PartialEq, TryFromBytes, IntoBytes, Immutable, KnownLayout, Unaligned)]
 pub struct DataRateTXPower {
     #[bits(4)]
-    tx_power: u8,
+    pub tx_power: u8,
     #[bits(4)]
-    data_rate: u8,
+    pub data_rate: u8,
 }
-#[bitfield(u8)]
-#[derive(PartialEq, TryFromBytes, Immutable, KnownLayout, Unaligned)]
+#[cfg(feature = "serde")]
+crate::types::bitfield_serde!(DataRateTXPower {
+    tx_power: u8 = set_tx_power_checked,
+    data_rate: u8 = set_data_rate_checked,
+});
+#[bitfield(u8, defmt = cfg(feature = "defmt"))]
+#[derive(PartialEq, TryFromBytes, IntoBytes, Immutable, KnownLayout, Unaligned)]
 pub struct Redundancy {
     #[bits(4)]
-    nb_trans: u8,
+    pub nb_trans: u8,
     #[bits(3)]
-    ch_mask_cntl: u8,
+    pub ch_mask_cntl: u8,
     _rfu: bool,
 }
-#[derive(Clone, Debug, PartialEq, TryFromBytes, Immutable, KnownLayout, Unaligned)]
+#[cfg(feature = "serde")]
+crate::types::bitfield_serde!(Redundancy {
+    nb_trans: u8 = set_nb_trans_checked,
+    ch_mask_cntl: u8 = set_ch_mask_cntl_checked,
+});
+#[derive(Clone, Debug, PartialEq, TryFromBytes, IntoBytes, Immutable, KnownLayout, Unaligned)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
 #[repr(C)]
 pub struct DutyCycleReq {
-    duty_cycle_pl: DutyCyclePl,
+    pub duty_cycle_pl: DutyCyclePl,
 }
-#[bitfield(u8)]
-#[derive(PartialEq, TryFromBytes, Immutable, KnownLayout, Unaligned)]
+#[bitfield(u8, defmt = cfg(feature = "defmt"))]
+#[derive(PartialEq, TryFromBytes, IntoBytes, Immutable, KnownLayout, Unaligned)]
 pub struct DutyCyclePl {
     #[bits(4)]
-    max_duty_cycle: u8,
+    pub max_duty_cycle: u8,
     #[bits(4)]
     _rfu: u8,
 }
+#[cfg(feature = "serde")]
+crate::types::bitfield_serde!(DutyCyclePl {
+    max_duty_cycle: u8 = set_max_duty_cycle_checked,
+});
 
-#[derive(Clone, Debug, PartialEq, TryFromBytes, Immutable, KnownLayout, Unaligned)]
+#[derive(Clone, Debug, PartialEq, TryFromBytes, IntoBytes, Immutable, KnownLayout, Unaligned)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
 #[repr(C)]
 pub struct RXParamSetupReq {
-    dl_settings: DlSettings,
-    frequency: Frequency,
+    pub dl_settings: DlSettings,
+    pub frequency: Frequency,
 }
 
-#[derive(Clone, Debug, PartialEq, TryFromBytes, Immutable, KnownLayout, Unaligned)]
+#[derive(Clone, Debug, PartialEq, TryFromBytes, IntoBytes, Immutable, KnownLayout, Unaligned)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[repr(C)]
 pub struct Frequency([u8; 3]);
 
 impl Frequency {
+    pub fn from_hz(hz: u32) -> Self {
+        let [bytes @ .., _] = (hz / 100).to_le_bytes();
+        Self(bytes)
+    }
+    /// Frequency in Hz, the field holds it in units of 100 Hz, little-endian
     pub fn hz(&self) -> u32 {
-        u32::from_le_bytes([self.0[2], self.0[1], self.0[0], 0]) * 100
+        u32::from_le_bytes([self.0[0], self.0[1], self.0[2], 0]) * 100
+    }
+}
+/// Frequency in Hz
+#[cfg(feature = "serde")]
+impl serde::Serialize for Frequency {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u32(self.hz())
+    }
+}
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Frequency {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        u32::deserialize(deserializer).map(Self::from_hz)
     }
 }
 
-#[bitfield(u8)]
-#[derive(PartialEq, TryFromBytes, Immutable, KnownLayout, Unaligned)]
+#[bitfield(u8, defmt = cfg(feature = "defmt"))]
+#[derive(PartialEq, TryFromBytes, IntoBytes, Immutable, KnownLayout, Unaligned)]
 pub struct DlSettings {
     #[bits(4)]
-    rx2_data_rate: u8,
+    pub rx2_data_rate: u8,
     #[bits(3)]
-    rx1_dr_offset: u8,
+    pub rx1_dr_offset: u8,
     _rfu: bool,
 }
+#[cfg(feature = "serde")]
+crate::types::bitfield_serde!(DlSettings {
+    rx2_data_rate: u8 = set_rx2_data_rate_checked,
+    rx1_dr_offset: u8 = set_rx1_dr_offset_checked,
+});
 
-#[derive(Clone, Debug, PartialEq, TryFromBytes, Immutable, KnownLayout, Unaligned)]
+#[derive(Clone, Debug, PartialEq, TryFromBytes, IntoBytes, Immutable, KnownLayout, Unaligned)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(C)]
-pub struct DevStatusReq {
-    pub battery: u8,
-    pub radio_status: RadioStatus,
-}
-#[bitfield(u8)]
-#[derive(PartialEq, TryFromBytes, Immutable, KnownLayout, Unaligned)]
-pub struct RadioStatus {
-    #[bits(6)]
-    snr: u8,
-    #[bits(2)]
-    _rfu: u8,
-}
+pub struct DevStatusReq {}
 
-#[derive(Clone, Debug, PartialEq, TryFromBytes, Immutable, KnownLayout, Unaligned)]
+#[derive(Clone, Debug, PartialEq, TryFromBytes, IntoBytes, Immutable, KnownLayout, Unaligned)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
 #[repr(C)]
 pub struct NewChannelReq {
-    ch_index: u8,
-    frequency: Frequency,
-    dr_range: DRRange,
+    pub ch_index: u8,
+    pub frequency: Frequency,
+    pub dr_range: DRRange,
 }
-#[bitfield(u8)]
-#[derive(PartialEq, TryFromBytes, Immutable, KnownLayout, Unaligned)]
+#[bitfield(u8, defmt = cfg(feature = "defmt"))]
+#[derive(PartialEq, TryFromBytes, IntoBytes, Immutable, KnownLayout, Unaligned)]
 pub struct DRRange {
     #[bits(4)]
-    min_dr: u8,
+    pub min_dr: u8,
     #[bits(4)]
-    max_dr: u8,
+    pub max_dr: u8,
 }
+#[cfg(feature = "serde")]
+crate::types::bitfield_serde!(DRRange {
+    min_dr: u8 = set_min_dr_checked,
+    max_dr: u8 = set_max_dr_checked,
+});
 
-#[derive(Clone, Debug, PartialEq, TryFromBytes, Immutable, KnownLayout, Unaligned)]
+#[derive(Clone, Debug, PartialEq, TryFromBytes, IntoBytes, Immutable, KnownLayout, Unaligned)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
 #[repr(C)]
 pub struct RXTimingSetupReq {
-    rx_timings_settings: RxTimingSettings,
+    pub rx_timings_settings: RxTimingSettings,
 }
-#[bitfield(u8)]
-#[derive(PartialEq, TryFromBytes, Immutable, KnownLayout, Unaligned)]
+#[bitfield(u8, defmt = cfg(feature = "defmt"))]
+#[derive(PartialEq, TryFromBytes, IntoBytes, Immutable, KnownLayout, Unaligned)]
 pub struct RxTimingSettings {
-    #[bits(3)]
-    del: u8,
-    #[bits(5)]
+    #[bits(4)]
+    pub del: u8,
+    #[bits(4)]
     _rfu: u8,
 }
+#[cfg(feature = "serde")]
+crate::types::bitfield_serde!(RxTimingSettings {
+    del: u8 = set_del_checked,
+});
 
-#[derive(Clone, Debug, PartialEq, TryFromBytes, Immutable, KnownLayout, Unaligned)]
+#[derive(Clone, Debug, PartialEq, TryFromBytes, IntoBytes, Immutable, KnownLayout, Unaligned)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
 #[repr(C)]
 pub struct TxParamSetupReq {
-    eirp_dwell_time: EirpDwellTime,
+    pub eirp_dwell_time: EirpDwellTime,
 }
-#[bitfield(u8)]
-#[derive(PartialEq, TryFromBytes, Immutable, KnownLayout, Unaligned)]
+#[bitfield(u8, defmt = cfg(feature = "defmt"))]
+#[derive(PartialEq, TryFromBytes, IntoBytes, Immutable, KnownLayout, Unaligned)]
 pub struct EirpDwellTime {
     #[bits(4)]
-    max_eirp: u8,
-    uplink_dwell_time: bool,
-    downlink_dwell_time: bool,
+    pub max_eirp: u8,
+    pub uplink_dwell_time: bool,
+    pub downlink_dwell_time: bool,
     #[bits(2)]
     _rfu: u8,
 }
+#[cfg(feature = "serde")]
+crate::types::bitfield_serde!(EirpDwellTime {
+    max_eirp: u8 = set_max_eirp_checked,
+    uplink_dwell_time: bool = set_uplink_dwell_time_checked,
+    downlink_dwell_time: bool = set_downlink_dwell_time_checked,
+});
 
-#[derive(Clone, Debug, PartialEq, TryFromBytes, Immutable, KnownLayout, Unaligned)]
+#[derive(Clone, Debug, PartialEq, TryFromBytes, IntoBytes, Immutable, KnownLayout, Unaligned)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
 #[repr(C)]
 pub struct DlChannelReq {
-    ch_index: u8,
-    frequency: Frequency,
+    pub ch_index: u8,
+    pub frequency: Frequency,
+}
+
+#[derive(Clone, Debug, PartialEq, TryFromBytes, IntoBytes, Immutable, KnownLayout, Unaligned)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+#[repr(C)]
+pub struct ADRParamSetupReq {
+    pub adr_param: AdrParam,
+}
+#[bitfield(u8, defmt = cfg(feature = "defmt"))]
+#[derive(PartialEq, TryFromBytes, IntoBytes, Immutable, KnownLayout, Unaligned)]
+pub struct AdrParam {
+    /// ADR_ACK_DELAY is 2^delay_exp
+    #[bits(4)]
+    pub delay_exp: u8,
+    /// ADR_ACK_LIMIT is 2^limit_exp
+    #[bits(4)]
+    pub limit_exp: u8,
 }
+#[cfg(feature = "serde")]
+crate::types::bitfield_serde!(AdrParam {
+    delay_exp: u8 = set_delay_exp_checked,
+    limit_exp: u8 = set_limit_exp_checked,
+});
 
-#[derive(Clone, Debug, PartialEq, TryFromBytes, Immutable, KnownLayout, Unaligned)]
+#[derive(Clone, Debug, PartialEq, TryFromBytes, IntoBytes, Immutable, KnownLayout, Unaligned)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(C)]
 pub struct DeviceTimeAns {
-    seconds: [u8; 4],
-    fractions: u8,
+    pub seconds: [u8; 4],
+    pub fractions: u8,
 }
 impl DeviceTimeAns {
     pub fn gps_epoch_nano_seconds(&self) -> u64 {
@@ -193,11 +313,34 @@ impl DeviceTimeAns {
 }
 pub struct DownlinkMacCommandDecoder<'a> {
     buf: &'a [u8],
+    offset: usize,
+    error: Option<Error>,
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for DownlinkMacCommandDecoder<'_> {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(
+            f,
+            "DownlinkMacCommandDecoder {{ buf: {=[u8]:02x}, offset: {=usize}, error: {} }}",
+            self.buf,
+            self.offset,
+            self.error
+        )
+    }
 }
 
 impl<'a> DownlinkMacCommandDecoder<'a> {
     pub fn new(buf: &'a [u8]) -> Self {
-        Self { buf }
+        Self {
+            buf,
+            offset: 0,
+            error: None,
+        }
+    }
+    /// Why decoding stopped before the end of the commands, if it did
+    pub fn error(&self) -> Option<Error> {
+        self.error
     }
 }
 impl Iterator for DownlinkMacCommandDecoder<'_> {
@@ -211,12 +354,24 @@ impl Iterator for DownlinkMacCommandDecoder<'_> {
             match DownlinkMacCommand::try_read_from_bytes(&tmp) {
                 Ok(cmd) => {
                     if self.buf.len() < cmd.len() + 1 {
+                        self.error = Some(Error::TruncatedCommand {
+                            cid: self.buf[0],
+                            offset: self.offset,
+                            expected: cmd.len(),
+                            actual: self.buf.len() - 1,
+                        });
+                        self.buf = &[];
                         return None;
                     }
                     self.buf = &self.buf[cmd.len() + 1..];
+                    self.offset += cmd.len() + 1;
                     Some(cmd)
                 }
                 Err(_) => {
+                    self.error = Some(Error::UnknownCid {
+                        cid: self.buf[0],
+                        offset: self.offset,
+                    });
                     self.buf = &[];
                     None
                 }
@@ -227,6 +382,38 @@ impl Iterator for DownlinkMacCommandDecoder<'_> {
     }
 }
 
+pub fn encode_maccommands<'a>(
+    cmds: &[DownlinkMacCommand],
+    buf: &'a mut [u8],
+) -> Result<&'a [u8], Error> {
+    let mut pos = 0usize;
+    for cmd in cmds {
+        let bytes = cmd.as_bytes();
+        let len = bytes.len() + 1;
+        if pos + len > buf.len() {
+            return Err(Error::Size {
+                expected: pos + len,
+                actual: buf.len(),
+            });
+        }
+        buf[pos] = cmd.cid();
+        buf[pos + 1..pos + len].copy_from_slice(bytes);
+        pos += len
+    }
+    Ok(&buf[..pos])
+}
+
+/// Like [`encode_maccommands`], into a buffer of the exact size
+#[cfg(feature = "alloc")]
+pub fn encode_maccommands_to_vec(cmds: &[DownlinkMacCommand]) -> alloc::vec::Vec<u8> {
+    let mut buf = alloc::vec::Vec::with_capacity(cmds.iter().map(|cmd| 1 + cmd.len()).sum());
+    for cmd in cmds {
+        buf.push(cmd.cid());
+        buf.extend_from_slice(cmd.as_bytes());
+    }
+    buf
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -237,7 +424,7 @@ mod tests {
         let cmds: Vec<_> = decoder.collect();
         assert_eq!(2, cmds.len());
         assert!(matches!(
-            cmds.get(0),
+            cmds.first(),
             Some(DownlinkMacCommand::LinkCheckAns(LinkCheckAns {
                 gw_cnt: 3,
                 margin: 4
@@ -259,9 +446,91 @@ mod tests {
                 &Redundancy::new().with_ch_mask_cntl(4).with_nb_trans(5)
             );
         } else {
-            assert!(false, "Wrong command type: {:?}", cmds.get(1))
+            panic!("Wrong command type: {:?}", cmds.get(1))
         }
-        assert!(matches!(cmds.get(2), None));
+        assert!(cmds.get(2).is_none());
+    }
+    #[test]
+    fn decode_errors() {
+        let mut decoder = DownlinkMacCommandDecoder::new(&[0x06, 0x03, 0x21, 0x02]);
+        assert_eq!(decoder.by_ref().count(), 1);
+        assert_eq!(
+            decoder.error(),
+            Some(Error::TruncatedCommand {
+                cid: 0x03,
+                offset: 1,
+                expected: 4,
+                actual: 2
+            })
+        );
+        let mut decoder = DownlinkMacCommandDecoder::new(&[0x04, 0x02, 0x7f]);
+        assert_eq!(decoder.by_ref().count(), 1);
+        let error = decoder.error().unwrap();
+        assert_eq!(
+            error,
+            Error::UnknownCid {
+                cid: 0x7f,
+                offset: 2
+            }
+        );
+        assert_eq!(error.to_string(), "unknown MAC command 0x7f at offset 2");
+        assert_eq!(DownlinkMacCommandDecoder::new(&[0x06]).error(), None);
+    }
+    #[test]
+    fn encode_downlink_cmds() {
+        let cmds = [
+            DownlinkMacCommand::LinkADRReq(LinkADRReq {
+                data_rate_tx_power: DataRateTXPower::new().with_tx_power(1).with_data_rate(2),
+                ch_mask: [0x02, 0x03],
+                redundancy: Redundancy::new().with_ch_mask_cntl(4).with_nb_trans(5),
+            }),
+            DownlinkMacCommand::DevStatusReq(DevStatusReq {}),
+            DownlinkMacCommand::DIChannelReq(DlChannelReq {
+                ch_index: 3,
+                frequency: Frequency::from_hz(867_100_000),
+            }),
+        ];
+        let mut buf = [0u8; 11];
+        let encoded = encode_maccommands(&cmds, &mut buf).unwrap();
+        assert_eq!(
+            encoded,
+            [0x03, 0x21, 0x02, 0x03, 0x45, 0x06, 0x0a, 0x03, 0x18, 0x4f, 0x84]
+        );
+        let mut decoder = DownlinkMacCommandDecoder::new(encoded).skip(2);
+        assert!(matches!(
+            decoder.next(),
+            Some(DownlinkMacCommand::DIChannelReq(DlChannelReq { frequency, .. }))
+                if frequency.hz() == 867_100_000
+        ));
+        assert!(encode_maccommands(&cmds, &mut [0u8; 10]).is_err());
+        #[cfg(feature = "alloc")]
+        assert_eq!(encode_maccommands_to_vec(&cmds), encoded);
+    }
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_downlink_cmds() {
+        let cmd = DownlinkMacCommand::NewChannelReq(NewChannelReq {
+            ch_index: 3,
+            frequency: Frequency::from_hz(867_100_000),
+            dr_range: DRRange::new().with_max_dr(5),
+        });
+        let json = serde_json::json!({
+            "NewChannelReq": {
+                "chIndex": 3,
+                "frequency": 867_100_000,
+                "drRange": { "minDr": 0, "maxDr": 5 }
+            }
+        });
+        assert_eq!(serde_json::to_value(&cmd).unwrap(), json);
+        let DownlinkMacCommand::NewChannelReq(req) = serde_json::from_value(json).unwrap() else {
+            panic!("expected a NewChannelReq");
+        };
+        assert_eq!(req.dr_range, DRRange::new().with_max_dr(5));
+        assert!(serde_json::from_value::<DRRange>(serde_json::json!({
+            "minDr": 16,
+            "maxDr": 0
+        }))
+        .is_err());
     }
     #[test]
     fn decode_duty_cycle_req() {
@@ -269,7 +538,7 @@ mod tests {
         let decoder = DownlinkMacCommandDecoder::new(&buf);
         let cmds: Vec<_> = decoder.collect();
         assert_eq!(1, cmds.len());
-        let Some(DownlinkMacCommand::DutyCycleReq(cmd)) = cmds.get(0) else {
+        let Some(DownlinkMacCommand::DutyCycleReq(cmd)) = cmds.first() else {
             panic!("decode error");
         };
         assert_eq!(
@@ -281,11 +550,11 @@ mod tests {
     }
     #[test]
     fn decode_rx_param_setup_req() {
-        let buf = [0x05, 0x5C, 0x84, 0x76, 0x2A];
+        let buf = [0x05, 0x5C, 0x2A, 0x76, 0x84];
         let decoder = DownlinkMacCommandDecoder::new(&buf);
         let cmds: Vec<_> = decoder.collect();
         assert_eq!(1, cmds.len());
-        let Some(DownlinkMacCommand::RXParamSetupReq(cmd)) = cmds.get(0) else {
+        let Some(DownlinkMacCommand::RXParamSetupReq(cmd)) = cmds.first() else {
             panic!("decode error");
         };
         assert_eq!(cmd.frequency.hz(), 868100200);
@@ -298,23 +567,26 @@ mod tests {
     }
     #[test]
     fn decode_dev_status_req() {
-        let buf = [0x06, 200, 5];
+        let buf = [0x06, 0x02, 0x03, 0x04];
         let decoder = DownlinkMacCommandDecoder::new(&buf);
         let cmds: Vec<_> = decoder.collect();
-        assert_eq!(1, cmds.len());
-        let Some(DownlinkMacCommand::DevStatusReq(cmd)) = cmds.get(0) else {
-            panic!("decode error");
-        };
-        assert_eq!(cmd.battery, 200);
-        assert_eq!(cmd.radio_status, RadioStatus::new().with_snr(5));
+        assert_eq!(2, cmds.len());
+        assert!(matches!(cmds[0], DownlinkMacCommand::DevStatusReq(_)));
+        assert!(matches!(
+            cmds[1],
+            DownlinkMacCommand::LinkCheckAns(LinkCheckAns {
+                gw_cnt: 3,
+                margin: 4
+            })
+        ));
     }
     #[test]
     fn decode_new_channel_req() {
-        let buf = [0x07, 0x01, 0x84, 0x76, 0x2A, 0xA1];
+        let buf = [0x07, 0x01, 0x2A, 0x76, 0x84, 0xA1];
         let decoder = DownlinkMacCommandDecoder::new(&buf);
         let cmds: Vec<_> = decoder.collect();
         assert_eq!(1, cmds.len());
-        let Some(DownlinkMacCommand::NewChannelReq(cmd)) = cmds.get(0) else {
+        let Some(DownlinkMacCommand::NewChannelReq(cmd)) = cmds.first() else {
             panic!("decode error");
         };
         assert_eq!(cmd.ch_index, 1);
@@ -330,7 +602,7 @@ mod tests {
         let decoder = DownlinkMacCommandDecoder::new(&buf);
         let cmds: Vec<_> = decoder.collect();
         assert_eq!(1, cmds.len());
-        let Some(DownlinkMacCommand::RXTimingSetupReq(cmd)) = cmds.get(0) else {
+        let Some(DownlinkMacCommand::RXTimingSetupReq(cmd)) = cmds.first() else {
             panic!("decode error");
         };
         assert_eq!(cmd.rx_timings_settings.del(), 3);
@@ -341,20 +613,20 @@ mod tests {
         let decoder = DownlinkMacCommandDecoder::new(&buf);
         let cmds: Vec<_> = decoder.collect();
         assert_eq!(1, cmds.len());
-        let Some(DownlinkMacCommand::TxParamSetupReq(cmd)) = cmds.get(0) else {
+        let Some(DownlinkMacCommand::TxParamSetupReq(cmd)) = cmds.first() else {
             panic!("decode error");
         };
         assert_eq!(cmd.eirp_dwell_time.max_eirp(), 12);
-        assert_eq!(cmd.eirp_dwell_time.uplink_dwell_time(), true);
-        assert_eq!(cmd.eirp_dwell_time.downlink_dwell_time(), false);
+        assert!(cmd.eirp_dwell_time.uplink_dwell_time());
+        assert!(!cmd.eirp_dwell_time.downlink_dwell_time());
     }
     #[test]
     fn decode_dl_channel_req() {
-        let buf = [0x0A, 0x08, 0x84, 0x76, 0x2A];
+        let buf = [0x0A, 0x08, 0x2A, 0x76, 0x84];
         let decoder = DownlinkMacCommandDecoder::new(&buf);
         let cmds: Vec<_> = decoder.collect();
         assert_eq!(1, cmds.len());
-        let Some(DownlinkMacCommand::DIChannelReq(cmd)) = cmds.get(0) else {
+        let Some(DownlinkMacCommand::DIChannelReq(cmd)) = cmds.first() else {
             panic!("decode error");
         };
         assert_eq!(cmd.ch_index, 8);
@@ -366,7 +638,7 @@ mod tests {
         let decoder = DownlinkMacCommandDecoder::new(&buf);
         let cmds: Vec<_> = decoder.collect();
         assert_eq!(1, cmds.len());
-        let Some(DownlinkMacCommand::DeviceTimeAns(cmd)) = cmds.get(0) else {
+        let Some(DownlinkMacCommand::DeviceTimeAns(cmd)) = cmds.first() else {
             panic!("decode error");
         };
         assert_eq!(cmd.gps_epoch_nano_seconds(), 16909060019531250);
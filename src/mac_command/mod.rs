@@ -7,6 +7,7 @@ const NEW_CHANNEL_CID: u8 = 0x07;
 const RX_TIMING_SETUP_CID: u8 = 0x08;
 const TX_PARAM_SETUP_CID: u8 = 0x09;
 const DI_CHANNEL_CID: u8 = 0x0A;
+const ADR_PARAM_SETUP_CID: u8 = 0x0C;
 const DEVICE_TIME_CID: u8 = 0x0D;
 
 pub mod downlink;
@@ -14,3 +15,23 @@ pub mod uplink;
 
 #[cfg(feature = "certification")]
 pub mod certification;
+
+/// Payload of the MAC command whose CID is `buf[0]`
+#[cfg(any(feature = "certification", feature = "multicast"))]
+pub(crate) fn command_payload<T>(buf: &[u8]) -> Result<&T, crate::Error>
+where
+    T: zerocopy::TryFromBytes + zerocopy::KnownLayout + zerocopy::Immutable,
+{
+    let cid = buf.first().copied().unwrap_or_default();
+    let payload = buf.get(1..).unwrap_or_default();
+    match T::try_ref_from_prefix(payload) {
+        Ok((cmd, _)) => Ok(cmd),
+        Err(_) if payload.len() < size_of::<T>() => Err(crate::Error::TruncatedCommand {
+            cid,
+            offset: 0,
+            expected: size_of::<T>(),
+            actual: payload.len(),
+        }),
+        Err(_) => Err(crate::Error::InvalidCommand { cid, offset: 0 }),
+    }
+}
@@ -5,10 +5,14 @@ use zerocopy::{Immutable, IntoBytes, KnownLayout};
 use crate::Error;
 
 use super::{
-    DEVICE_TIME_CID, DEV_STATUS_CID, DI_CHANNEL_CID, DUTY_CYCLE_CID, LINK_ADR_CID, LINK_CHECK_CID,
-    NEW_CHANNEL_CID, RX_PARAM_SETUP_CID, RX_TIMING_SETUP_CID, TX_PARAM_SETUP_CID,
+    ADR_PARAM_SETUP_CID, DEVICE_TIME_CID, DEV_STATUS_CID, DI_CHANNEL_CID, DUTY_CYCLE_CID,
+    LINK_ADR_CID, LINK_CHECK_CID, NEW_CHANNEL_CID, RX_PARAM_SETUP_CID, RX_TIMING_SETUP_CID,
+    TX_PARAM_SETUP_CID,
 };
 
+#[derive(Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 pub enum UplinkMacCommmand {
     LinkCheckReq = LINK_CHECK_CID,
@@ -17,9 +21,10 @@ pub enum UplinkMacCommmand {
     RXParamSetupAns(RXParamSetupAns) = RX_PARAM_SETUP_CID,
     DevStatusAns(DevStatusAns) = DEV_STATUS_CID,
     NewChannelAns(NewChannelAns) = NEW_CHANNEL_CID,
-    RXTimingSetupAns(RXTimingSetupAns) = RX_TIMING_SETUP_CID,
+    RXTimingSetupAns = RX_TIMING_SETUP_CID,
     TxParamSetupAns = TX_PARAM_SETUP_CID,
     DlChannelAns(DIChannelAns) = DI_CHANNEL_CID,
+    ADRParamSetupAns = ADR_PARAM_SETUP_CID,
     DeviceTimeReq = DEVICE_TIME_CID,
 }
 #[allow(clippy::len_without_is_empty)]
@@ -36,22 +41,25 @@ impl UplinkMacCommmand {
             UplinkMacCommmand::RXParamSetupAns(cmd) => cmd.as_bytes(),
             UplinkMacCommmand::DevStatusAns(cmd) => cmd.as_bytes(),
             UplinkMacCommmand::NewChannelAns(cmd) => cmd.as_bytes(),
-            UplinkMacCommmand::RXTimingSetupAns(cmd) => cmd.as_bytes(),
+            UplinkMacCommmand::RXTimingSetupAns => &[],
             UplinkMacCommmand::TxParamSetupAns => &[],
             UplinkMacCommmand::DlChannelAns(cmd) => cmd.as_bytes(),
+            UplinkMacCommmand::ADRParamSetupAns => &[],
             UplinkMacCommmand::DeviceTimeReq => &[],
         }
     }
 }
 
-#[derive(Default, IntoBytes, Immutable, KnownLayout)]
+#[derive(Clone, Copy, Default, IntoBytes, Immutable, KnownLayout)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(C)]
 pub struct LinkADRAns {
     pub status: LinkAdrAnsStatus,
 }
 
-#[bitfield(u8)]
-#[derive(IntoBytes, Immutable, KnownLayout)]
+#[bitfield(u8, defmt = cfg(feature = "defmt"))]
+#[derive(PartialEq, IntoBytes, Immutable, KnownLayout)]
 pub struct LinkAdrAnsStatus {
     pub channel_mask_ack: bool,
     pub data_rate_ack: bool,
@@ -59,13 +67,21 @@ pub struct LinkAdrAnsStatus {
     #[bits(5)]
     _rfu: u8,
 }
+#[cfg(feature = "serde")]
+crate::types::bitfield_serde!(LinkAdrAnsStatus {
+    channel_mask_ack: bool = set_channel_mask_ack_checked,
+    data_rate_ack: bool = set_data_rate_ack_checked,
+    power_ack: bool = set_power_ack_checked,
+});
 
-#[derive(IntoBytes, Immutable, KnownLayout)]
+#[derive(Clone, Copy, IntoBytes, Immutable, KnownLayout)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(C)]
 pub struct RXParamSetupAns {
-    status: RXParamSetupAnsStatus,
+    pub status: RXParamSetupAnsStatus,
 }
-#[bitfield(u8)]
+#[bitfield(u8, defmt = cfg(feature = "defmt"))]
 #[derive(IntoBytes, Immutable, KnownLayout)]
 pub struct RXParamSetupAnsStatus {
     pub channel_ack: bool,
@@ -74,14 +90,33 @@ pub struct RXParamSetupAnsStatus {
     #[bits(5)]
     _rfu: u8,
 }
+#[cfg(feature = "serde")]
+crate::types::bitfield_serde!(RXParamSetupAnsStatus {
+    channel_ack: bool = set_channel_ack_checked,
+    rx2_data_rate_ack: bool = set_rx2_data_rate_ack_checked,
+    rx1_data_rate_offset_ack: bool = set_rx1_data_rate_offset_ack_checked,
+});
 
-#[derive(IntoBytes, Immutable, KnownLayout)]
+#[derive(Clone, Copy, IntoBytes, Immutable, KnownLayout)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
 #[repr(C)]
 pub struct DevStatusAns {
-    radio_status: DevStatusAnsRadioStatus,
     battery: u8,
+    radio_status: DevStatusAnsRadioStatus,
+}
+impl DevStatusAns {
+    /// `battery` is 0 for an external power source, 1..=254 for the battery level and 255 if
+    /// unknown. `margin` is the SNR in dB of the last DevStatusReq, -32..=31.
+    pub fn new(battery: u8, margin: i8) -> Self {
+        Self {
+            battery,
+            radio_status: DevStatusAnsRadioStatus::new().with_snr(margin.clamp(-32, 31)),
+        }
+    }
 }
-#[bitfield(u8)]
+#[bitfield(u8, defmt = cfg(feature = "defmt"))]
 #[derive(IntoBytes, Immutable, KnownLayout)]
 pub struct DevStatusAnsRadioStatus {
     #[bits(6)]
@@ -89,12 +124,18 @@ pub struct DevStatusAnsRadioStatus {
     #[bits(2)]
     _rfu: u8,
 }
-#[derive(IntoBytes, Immutable, KnownLayout)]
+#[cfg(feature = "serde")]
+crate::types::bitfield_serde!(DevStatusAnsRadioStatus {
+    snr: i8 = set_snr_checked,
+});
+#[derive(Clone, Copy, IntoBytes, Immutable, KnownLayout)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(C)]
 pub struct NewChannelAns {
-    status: NewChannelAnsStatus,
+    pub status: NewChannelAnsStatus,
 }
-#[bitfield(u8)]
+#[bitfield(u8, defmt = cfg(feature = "defmt"))]
 #[derive(IntoBytes, Immutable, KnownLayout)]
 pub struct NewChannelAnsStatus {
     pub channel_freq_ok: bool,
@@ -102,28 +143,21 @@ pub struct NewChannelAnsStatus {
     #[bits(6)]
     _rfu: u8,
 }
+#[cfg(feature = "serde")]
+crate::types::bitfield_serde!(NewChannelAnsStatus {
+    channel_freq_ok: bool = set_channel_freq_ok_checked,
+    data_rate_range_ok: bool = set_data_rate_range_ok_checked,
+});
 
-#[derive(IntoBytes, Immutable, KnownLayout)]
-#[repr(C)]
-pub struct RXTimingSetupAns {
-    rx_timings_settings: RxTimingsSetting,
-}
-#[bitfield(u8)]
-#[derive(IntoBytes, Immutable, KnownLayout)]
-pub struct RxTimingsSetting {
-    #[bits(3)]
-    pub del: u8,
-    #[bits(5)]
-    _rfu: u8,
-}
-
-#[derive(IntoBytes, Immutable, KnownLayout)]
+#[derive(Clone, Copy, IntoBytes, Immutable, KnownLayout)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(C)]
 pub struct DIChannelAns {
-    status: DIChannelAnsStatus,
+    pub status: DIChannelAnsStatus,
 }
 
-#[bitfield(u8)]
+#[bitfield(u8, defmt = cfg(feature = "defmt"))]
 #[derive(IntoBytes, Immutable, KnownLayout)]
 pub struct DIChannelAnsStatus {
     pub channel_frequency_ok: bool,
@@ -131,6 +165,11 @@ pub struct DIChannelAnsStatus {
     #[bits(6)]
     _rfu: u8,
 }
+#[cfg(feature = "serde")]
+crate::types::bitfield_serde!(DIChannelAnsStatus {
+    channel_frequency_ok: bool = set_channel_frequency_ok_checked,
+    uplink_frequency_exists: bool = set_uplink_frequency_exists_checked,
+});
 
 pub fn encode_maccommands<'a>(
     cmds: &[UplinkMacCommmand],
@@ -141,7 +180,10 @@ pub fn encode_maccommands<'a>(
         let bytes = cmd.as_bytes();
         let len = bytes.len() + 1;
         if pos + len > buf.len() {
-            return Err(Error::Size);
+            return Err(Error::Size {
+                expected: pos + len,
+                actual: buf.len(),
+            });
         }
         buf[pos] = cmd.cid();
         if len > 1 {
@@ -151,6 +193,18 @@ pub fn encode_maccommands<'a>(
     }
     Ok(&buf[..pos])
 }
+
+/// Like [`encode_maccommands`], into a buffer of the exact size
+#[cfg(feature = "alloc")]
+pub fn encode_maccommands_to_vec(cmds: &[UplinkMacCommmand]) -> alloc::vec::Vec<u8> {
+    let mut buf =
+        alloc::vec::Vec::with_capacity(cmds.iter().map(|cmd| 1 + cmd.as_bytes().len()).sum());
+    for cmd in cmds {
+        buf.push(cmd.cid());
+        buf.extend_from_slice(cmd.as_bytes());
+    }
+    buf
+}
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -172,9 +226,7 @@ mod tests {
             UplinkMacCommmand::NewChannelAns(NewChannelAns {
                 status: NewChannelAnsStatus::new(),
             }),
-            UplinkMacCommmand::RXTimingSetupAns(RXTimingSetupAns {
-                rx_timings_settings: RxTimingsSetting::new().with_del(3),
-            }),
+            UplinkMacCommmand::RXTimingSetupAns,
             UplinkMacCommmand::TxParamSetupAns,
             UplinkMacCommmand::DlChannelAns(DIChannelAns {
                 status: DIChannelAnsStatus::new(),
@@ -186,8 +238,8 @@ mod tests {
         assert_eq!(
             cmd_buf,
             &[
-                0x02, 0x03, 0x00, 0x04, 0x05, 0x00, 0x06, 0, 112, 0x07, 0x00, 0x08, 0x03, 0x09,
-                0x0A, 0x00, 0x0D
+                0x02, 0x03, 0x00, 0x04, 0x05, 0x00, 0x06, 112, 0, 0x07, 0x00, 0x08, 0x09, 0x0A,
+                0x00, 0x0D
             ]
         )
     }
@@ -209,7 +261,7 @@ mod tests {
         ];
         let mut buf = [0u8; 255];
         let cmd_buf = encode_maccommands(&cmds, &mut buf).unwrap();
-        assert_eq!(cmd_buf, &[0x06, 49, 112, 0x06, 0, 225, 0x06, 15, 0])
+        assert_eq!(cmd_buf, &[0x06, 112, 49, 0x06, 225, 0, 0x06, 0, 15])
     }
     #[test]
     fn encode_uplink_rx_param_setup_ans() {
@@ -0,0 +1,6 @@
+pub const PACKAGE_VERSION_CID: u8 = 0x00;
+pub const MC_GROUP_STATUS_CID: u8 = 0x01;
+pub const MC_GROUP_SETUP_CID: u8 = 0x02;
+pub const MC_GROUP_DELETE_CID: u8 = 0x03;
+pub const MC_CLASS_C_SESSION_CID: u8 = 0x04;
+pub const MC_CLASS_B_SESSION_CID: u8 = 0x05;
@@ -0,0 +1,345 @@
+use bitfield_struct::bitfield;
+use zerocopy::{little_endian::U32, Immutable, KnownLayout, TryFromBytes, Unaligned};
+
+use crate::{
+    mac_command::{command_payload, downlink::Frequency},
+    types::DevAddr,
+    Error,
+};
+
+use super::cid::*;
+
+#[derive(PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum MulticastDownlinkCommand<'a> {
+    /// Used by the AS to request the package version implemented by the end-device
+    PackageVersionReq = PACKAGE_VERSION_CID,
+    /// Asks the end-device to list the multicast groups it is currently member of
+    McGroupStatusReq(&'a McGroupStatusReq) = MC_GROUP_STATUS_CID,
+    /// Creates or modifies the parameters of a multicast group
+    McGroupSetupReq(&'a McGroupSetupReq) = MC_GROUP_SETUP_CID,
+    /// Deletes a multicast group from the end-device
+    McGroupDeleteReq(&'a McGroupDeleteReq) = MC_GROUP_DELETE_CID,
+    /// Sets up a temporary Class C session for a multicast group
+    McClassCSessionReq(&'a McClassCSessionReq) = MC_CLASS_C_SESSION_CID,
+    /// Sets up a temporary Class B session for a multicast group
+    McClassBSessionReq(&'a McClassBSessionReq) = MC_CLASS_B_SESSION_CID,
+}
+
+impl<'a> MulticastDownlinkCommand<'a> {
+    pub fn decode(buf: &'a [u8]) -> Result<Self, Error> {
+        let Some(&cid) = buf.first() else {
+            return Err(Error::Size {
+                expected: 1,
+                actual: 0,
+            });
+        };
+        match cid {
+            PACKAGE_VERSION_CID => Ok(Self::PackageVersionReq),
+            MC_GROUP_STATUS_CID => Ok(Self::McGroupStatusReq(command_payload(buf)?)),
+            MC_GROUP_SETUP_CID => Ok(Self::McGroupSetupReq(command_payload(buf)?)),
+            MC_GROUP_DELETE_CID => Ok(Self::McGroupDeleteReq(command_payload(buf)?)),
+            MC_CLASS_C_SESSION_CID => Ok(Self::McClassCSessionReq(command_payload(buf)?)),
+            MC_CLASS_B_SESSION_CID => Ok(Self::McClassBSessionReq(command_payload(buf)?)),
+            _ => Err(Error::UnknownCid { cid, offset: 0 }),
+        }
+    }
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> usize {
+        match self {
+            MulticastDownlinkCommand::PackageVersionReq => 0,
+            MulticastDownlinkCommand::McGroupStatusReq(cmd) => size_of_val(*cmd),
+            MulticastDownlinkCommand::McGroupSetupReq(cmd) => size_of_val(*cmd),
+            MulticastDownlinkCommand::McGroupDeleteReq(cmd) => size_of_val(*cmd),
+            MulticastDownlinkCommand::McClassCSessionReq(cmd) => size_of_val(*cmd),
+            MulticastDownlinkCommand::McClassBSessionReq(cmd) => size_of_val(*cmd),
+        }
+    }
+}
+
+#[bitfield(u8, defmt = cfg(feature = "defmt"))]
+#[derive(PartialEq, TryFromBytes, Immutable, KnownLayout, Unaligned)]
+pub struct McGroupIdHeader {
+    #[bits(2)]
+    pub mc_group_id: u8,
+    #[bits(6)]
+    _rfu: u8,
+}
+
+#[derive(Clone, Debug, PartialEq, TryFromBytes, Immutable, KnownLayout, Unaligned)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(C)]
+pub struct McGroupStatusReq {
+    pub cmd_mask: McGroupStatusReqCmdMask,
+}
+#[bitfield(u8, defmt = cfg(feature = "defmt"))]
+#[derive(PartialEq, TryFromBytes, Immutable, KnownLayout, Unaligned)]
+pub struct McGroupStatusReqCmdMask {
+    #[bits(4)]
+    pub req_group_mask: u8,
+    #[bits(4)]
+    _rfu: u8,
+}
+
+#[derive(Clone, Debug, PartialEq, TryFromBytes, Immutable, KnownLayout, Unaligned)]
+#[repr(C)]
+pub struct McGroupSetupReq {
+    pub mc_group_id_header: McGroupIdHeader,
+    mc_addr: [u8; 4],
+    pub mc_key_encrypted: [u8; 16],
+    min_mc_f_count: U32,
+    max_mc_f_count: U32,
+}
+impl McGroupSetupReq {
+    pub fn mc_addr(&self) -> DevAddr {
+        zerocopy::transmute!(self.mc_addr)
+    }
+    pub fn min_mc_f_count(&self) -> u32 {
+        self.min_mc_f_count.get()
+    }
+    pub fn max_mc_f_count(&self) -> u32 {
+        self.max_mc_f_count.get()
+    }
+}
+#[cfg(feature = "defmt")]
+impl defmt::Format for McGroupSetupReq {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(
+            f,
+            "McGroupSetupReq {{ mc_group_id_header: {}, mc_addr: {}, mc_key_encrypted: {=[u8]:02x}, min_mc_f_count: {=u32}, max_mc_f_count: {=u32} }}",
+            self.mc_group_id_header,
+            self.mc_addr(),
+            self.mc_key_encrypted,
+            self.min_mc_f_count(),
+            self.max_mc_f_count()
+        )
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, TryFromBytes, Immutable, KnownLayout, Unaligned)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(C)]
+pub struct McGroupDeleteReq {
+    pub mc_group_id_header: McGroupIdHeader,
+}
+
+#[derive(Clone, Debug, PartialEq, TryFromBytes, Immutable, KnownLayout, Unaligned)]
+#[repr(C)]
+pub struct McClassCSessionReq {
+    pub mc_group_id_header: McGroupIdHeader,
+    session_time: U32,
+    pub session_time_out: SessionTimeOut,
+    pub dl_frequency: Frequency,
+    pub dr: u8,
+}
+impl McClassCSessionReq {
+    /// Start of the session as GPS time in seconds modulo 2^32
+    pub fn session_time(&self) -> u32 {
+        self.session_time.get()
+    }
+    /// Maximum duration of the session in seconds
+    pub fn session_time_out_seconds(&self) -> u32 {
+        1 << self.session_time_out.time_out()
+    }
+}
+#[cfg(feature = "defmt")]
+impl defmt::Format for McClassCSessionReq {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(
+            f,
+            "McClassCSessionReq {{ mc_group_id_header: {}, session_time: {=u32}, session_time_out: {}, dl_frequency: {}, dr: {=u8} }}",
+            self.mc_group_id_header,
+            self.session_time(),
+            self.session_time_out,
+            self.dl_frequency,
+            self.dr
+        )
+    }
+}
+#[bitfield(u8, defmt = cfg(feature = "defmt"))]
+#[derive(PartialEq, TryFromBytes, Immutable, KnownLayout, Unaligned)]
+pub struct SessionTimeOut {
+    #[bits(4)]
+    pub time_out: u8,
+    #[bits(4)]
+    _rfu: u8,
+}
+
+#[derive(Clone, Debug, PartialEq, TryFromBytes, Immutable, KnownLayout, Unaligned)]
+#[repr(C)]
+pub struct McClassBSessionReq {
+    pub mc_group_id_header: McGroupIdHeader,
+    session_time: U32,
+    pub time_out_periodicity: TimeOutPeriodicity,
+    pub dl_frequency: Frequency,
+    pub dr: u8,
+}
+impl McClassBSessionReq {
+    /// Start of the session as GPS time in seconds modulo 2^32
+    pub fn session_time(&self) -> u32 {
+        self.session_time.get()
+    }
+    /// Maximum duration of the session in beacon periods
+    pub fn session_time_out_beacon_periods(&self) -> u32 {
+        1 << self.time_out_periodicity.time_out()
+    }
+}
+#[cfg(feature = "defmt")]
+impl defmt::Format for McClassBSessionReq {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(
+            f,
+            "McClassBSessionReq {{ mc_group_id_header: {}, session_time: {=u32}, time_out_periodicity: {}, dl_frequency: {}, dr: {=u8} }}",
+            self.mc_group_id_header,
+            self.session_time(),
+            self.time_out_periodicity,
+            self.dl_frequency,
+            self.dr
+        )
+    }
+}
+#[bitfield(u8, defmt = cfg(feature = "defmt"))]
+#[derive(PartialEq, TryFromBytes, Immutable, KnownLayout, Unaligned)]
+pub struct TimeOutPeriodicity {
+    #[bits(4)]
+    pub time_out: u8,
+    #[bits(3)]
+    pub periodicity: u8,
+    _rfu: bool,
+}
+
+pub struct MulticastDownlinkCommandDecoder<'a> {
+    buf: &'a [u8],
+    offset: usize,
+    error: Option<Error>,
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for MulticastDownlinkCommandDecoder<'_> {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(
+            f,
+            "MulticastDownlinkCommandDecoder {{ buf: {=[u8]:02x}, offset: {=usize}, error: {} }}",
+            self.buf,
+            self.offset,
+            self.error
+        )
+    }
+}
+
+impl<'a> MulticastDownlinkCommandDecoder<'a> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self {
+            buf,
+            offset: 0,
+            error: None,
+        }
+    }
+    /// Why decoding stopped before the end of the commands, if it did
+    pub fn error(&self) -> Option<Error> {
+        self.error
+    }
+}
+impl<'a> Iterator for MulticastDownlinkCommandDecoder<'a> {
+    type Item = MulticastDownlinkCommand<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.buf.is_empty() {
+            return None;
+        }
+        match MulticastDownlinkCommand::decode(self.buf) {
+            Ok(cmd) => {
+                self.buf = &self.buf[cmd.len() + 1..];
+                self.offset += cmd.len() + 1;
+                Some(cmd)
+            }
+            Err(error) => {
+                self.buf = &[];
+                self.error = Some(error.offset_by(self.offset));
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use zerocopy::IntoBytes;
+
+    use super::*;
+    #[test]
+    fn decode_mc_group_setup_req() {
+        let buf = [
+            0x02, 0x01, 0x04, 0x03, 0x02, 0x01, 0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07,
+            0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e, 0x0f, 0x10, 0x00, 0x00, 0x00, 0xff, 0xff,
+            0x00, 0x00,
+        ];
+        let Ok(MulticastDownlinkCommand::McGroupSetupReq(cmd)) =
+            MulticastDownlinkCommand::decode(&buf)
+        else {
+            panic!("decode error");
+        };
+        assert_eq!(cmd.mc_group_id_header.mc_group_id(), 1);
+        assert_eq!(cmd.mc_addr().as_bytes(), &[4, 3, 2, 1]);
+        assert_eq!(cmd.mc_key_encrypted[15], 0x0f);
+        assert_eq!(cmd.min_mc_f_count(), 16);
+        assert_eq!(cmd.max_mc_f_count(), 0xffff);
+    }
+    #[test]
+    fn decode_mc_class_b_session_req() {
+        let buf = [
+            0x05, 0x02, 0x00, 0x01, 0x02, 0x03, 0x35, 0x2A, 0x76, 0x84, 0x03,
+        ];
+        let Ok(MulticastDownlinkCommand::McClassBSessionReq(cmd)) =
+            MulticastDownlinkCommand::decode(&buf)
+        else {
+            panic!("decode error");
+        };
+        assert_eq!(cmd.mc_group_id_header.mc_group_id(), 2);
+        assert_eq!(cmd.session_time(), 0x03020100);
+        assert_eq!(cmd.time_out_periodicity.periodicity(), 3);
+        assert_eq!(cmd.session_time_out_beacon_periods(), 32);
+        assert_eq!(cmd.dl_frequency.hz(), 868100200);
+        assert_eq!(cmd.dr, 3);
+    }
+    #[test]
+    fn decode_multiple_cmds() {
+        let buf = [
+            0x00, 0x01, 0x0f, 0x03, 0x01, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0x08, 0x2A, 0x76,
+            0x84, 0x05,
+        ];
+        let cmds: Vec<_> = MulticastDownlinkCommandDecoder::new(&buf).collect();
+        assert_eq!(cmds.len(), 4);
+        assert_eq!(cmds[0], MulticastDownlinkCommand::PackageVersionReq);
+        let MulticastDownlinkCommand::McGroupStatusReq(cmd) = cmds[1] else {
+            panic!("decode error");
+        };
+        assert_eq!(cmd.cmd_mask.req_group_mask(), 0x0f);
+        let MulticastDownlinkCommand::McGroupDeleteReq(cmd) = cmds[2] else {
+            panic!("decode error");
+        };
+        assert_eq!(cmd.mc_group_id_header.mc_group_id(), 1);
+        let MulticastDownlinkCommand::McClassCSessionReq(cmd) = cmds[3] else {
+            panic!("decode error");
+        };
+        assert_eq!(cmd.session_time_out_seconds(), 256);
+        assert_eq!(cmd.dr, 5);
+    }
+    #[test]
+    fn decode_truncated_cmd() {
+        let buf = [0x01, 0x0f, 0x02, 0x01, 0x04];
+        let mut decoder = MulticastDownlinkCommandDecoder::new(&buf);
+        assert_eq!(decoder.by_ref().count(), 1);
+        assert_eq!(
+            decoder.error(),
+            Some(Error::TruncatedCommand {
+                cid: 0x02,
+                offset: 2,
+                expected: size_of::<McGroupSetupReq>(),
+                actual: 2
+            })
+        );
+        assert!(MulticastDownlinkCommand::decode(&buf[2..]).is_err());
+    }
+}
@@ -0,0 +1,144 @@
+//! Multicast key derivation.
+//!
+//! McRootKey -> McKEKey decrypts the McKey received in McGroupSetupReq, from which the
+//! McAppSKey and McNwkSKey of the group are derived.
+use zerocopy::transmute;
+
+use crate::{
+    crypto::{Crypto, Encrypter as _, Key},
+    types::{AppKey, AppSKey, DevAddr, GenAppKey, McKEKey, McKey, McRootKey, NwkSKey},
+};
+
+fn aes128_encrypt<C: Crypto>(crypto: &mut C, key: [u8; 16], mut block: [u8; 16]) -> [u8; 16] {
+    crypto
+        .get_encrypter(Key::Raw(key))
+        .encrypt_block(&mut block);
+    block
+}
+
+/// McRootKey of a LoRaWAN 1.0.x end-device, derived from GenAppKey
+pub fn mc_root_key_from_gen_app_key<C: Crypto>(
+    crypto: &mut C,
+    gen_app_key: &GenAppKey,
+) -> McRootKey {
+    transmute!(aes128_encrypt(crypto, transmute!(*gen_app_key), [0; 16]))
+}
+
+/// McRootKey of a LoRaWAN 1.1 end-device, derived from AppKey
+pub fn mc_root_key_from_app_key<C: Crypto>(crypto: &mut C, app_key: &AppKey) -> McRootKey {
+    let mut block = [0u8; 16];
+    block[0] = 0x20;
+    transmute!(aes128_encrypt(crypto, transmute!(*app_key), block))
+}
+
+pub fn mc_ke_key<C: Crypto>(crypto: &mut C, mc_root_key: &McRootKey) -> McKEKey {
+    transmute!(aes128_encrypt(crypto, transmute!(*mc_root_key), [0; 16]))
+}
+
+/// Decrypts the `mc_key_encrypted` field of McGroupSetupReq
+pub fn decrypt_mc_key<C: Crypto>(
+    crypto: &mut C,
+    mc_ke_key: &McKEKey,
+    mc_key_encrypted: &[u8; 16],
+) -> McKey {
+    transmute!(aes128_encrypt(
+        crypto,
+        transmute!(*mc_ke_key),
+        *mc_key_encrypted
+    ))
+}
+
+fn mc_session_key<C: Crypto>(
+    crypto: &mut C,
+    mc_key: &McKey,
+    mc_addr: DevAddr,
+    prefix: u8,
+) -> [u8; 16] {
+    let mut block = [0u8; 16];
+    block[0] = prefix;
+    block[1..5].copy_from_slice(zerocopy::IntoBytes::as_bytes(&mc_addr));
+    aes128_encrypt(crypto, transmute!(*mc_key), block)
+}
+
+/// McAppSKey of the multicast group with address `mc_addr`
+pub fn mc_app_s_key<C: Crypto>(crypto: &mut C, mc_key: &McKey, mc_addr: DevAddr) -> AppSKey {
+    transmute!(mc_session_key(crypto, mc_key, mc_addr, 0x01))
+}
+
+/// McNwkSKey of the multicast group with address `mc_addr`
+pub fn mc_nwk_s_key<C: Crypto>(crypto: &mut C, mc_key: &McKey, mc_addr: DevAddr) -> NwkSKey {
+    transmute!(mc_session_key(crypto, mc_key, mc_addr, 0x02))
+}
+
+#[cfg(test)]
+mod tests {
+    use zerocopy::{FromBytes, IntoBytes};
+
+    use crate::crypto::soft::SoftCrypto;
+
+    use super::*;
+    fn get_crypto() -> SoftCrypto {
+        let nwk_s_key = NwkSKey::read_from_bytes(&[0; 16]).unwrap();
+        let app_s_key = AppSKey::read_from_bytes(&[0; 16]).unwrap();
+        SoftCrypto::new(nwk_s_key, app_s_key)
+    }
+    #[test]
+    fn derive_mc_root_key() {
+        let mut crypto = get_crypto();
+        let key: [u8; 16] = core::array::from_fn(|i| i as u8);
+        let gen_app_key = GenAppKey::read_from_bytes(&key).unwrap();
+        assert_eq!(
+            mc_root_key_from_gen_app_key(&mut crypto, &gen_app_key).as_bytes(),
+            &[
+                0xc6, 0xa1, 0x3b, 0x37, 0x87, 0x8f, 0x5b, 0x82, 0x6f, 0x4f, 0x81, 0x62, 0xa1, 0xc8,
+                0xd8, 0x79
+            ]
+        );
+        let app_key = AppKey::read_from_bytes(&key).unwrap();
+        assert_eq!(
+            mc_root_key_from_app_key(&mut crypto, &app_key).as_bytes(),
+            &[
+                0x43, 0x0b, 0xff, 0x9b, 0x04, 0x9f, 0x19, 0x27, 0x94, 0x55, 0xbd, 0x56, 0x41, 0x33,
+                0xc7, 0x3b
+            ]
+        );
+    }
+    #[test]
+    fn derive_mc_session_keys() {
+        let mut crypto = get_crypto();
+        let key: [u8; 16] = core::array::from_fn(|i| i as u8);
+        let gen_app_key = GenAppKey::read_from_bytes(&key).unwrap();
+        let mc_root_key = mc_root_key_from_gen_app_key(&mut crypto, &gen_app_key);
+        let mc_ke_key = mc_ke_key(&mut crypto, &mc_root_key);
+        assert_eq!(
+            mc_ke_key.as_bytes(),
+            &[
+                0x2c, 0x57, 0x8f, 0x79, 0x27, 0xa9, 0x49, 0xd3, 0xb5, 0x11, 0xae, 0x8f, 0xb6, 0x91,
+                0x45, 0xc6
+            ]
+        );
+        let mc_key = decrypt_mc_key(&mut crypto, &mc_ke_key, &[0xff; 16]);
+        assert_eq!(
+            mc_key.as_bytes(),
+            &[
+                0x9d, 0x93, 0x7e, 0x27, 0x2d, 0x34, 0x02, 0x1a, 0xaa, 0xe9, 0x15, 0xc6, 0x97, 0x3a,
+                0xd6, 0xd1
+            ]
+        );
+        let mc_addr = DevAddr::read_from_bytes(&[4, 3, 2, 1]).unwrap();
+        assert_eq!(
+            mc_app_s_key(&mut crypto, &mc_key, mc_addr).as_bytes(),
+            &[
+                0xd0, 0xc0, 0x27, 0x1f, 0xb3, 0x36, 0x2a, 0x4e, 0xaf, 0x70, 0x8e, 0x42, 0x2e, 0xf0,
+                0x3e, 0x3d
+            ]
+        );
+        assert_eq!(
+            mc_nwk_s_key(&mut crypto, &mc_key, mc_addr).as_bytes(),
+            &[
+                0xb3, 0x7c, 0x7d, 0x77, 0xf4, 0x87, 0xc9, 0x69, 0x68, 0x9d, 0x43, 0xaa, 0xd0, 0xb9,
+                0x33, 0x9d
+            ]
+        );
+    }
+}
@@ -0,0 +1,13 @@
+//! Remote Multicast Setup package (LoRaWAN TS005-1.0.0)
+pub mod cid;
+pub mod downlink;
+pub mod keys;
+pub mod session;
+pub mod uplink;
+
+/// FPort used by the Remote Multicast Setup package
+pub const PORT: u8 = 200;
+/// Package identifier reported in PackageVersionAns
+pub const PACKAGE_IDENTIFIER: u8 = 2;
+/// Package version reported in PackageVersionAns
+pub const PACKAGE_VERSION: u8 = 1;
@@ -0,0 +1,212 @@
+use zerocopy::TryFromBytes;
+
+use crate::{crypto::Crypto, phy_payload::mac_payload::downlink::Downlink, types::DevAddr, Error};
+
+/// Multicast group session.
+///
+/// `crypto` must be built from the McNwkSKey and McAppSKey of the group, see
+/// [`super::keys`]. Frames are accepted only if they are unconfirmed, carry no MAC
+/// commands, have FPort > 0 and a McFCount within `min_mc_f_count..=max_mc_f_count` that is
+/// higher than any previously received. Every frame is rejected once McFCount 2^32 - 1 has
+/// been received.
+pub struct McSession<C: Crypto> {
+    mc_group_id: u8,
+    mc_addr: DevAddr,
+    crypto: C,
+    next_f_cnt: u32,
+    max_mc_f_count: u32,
+    exhausted: bool,
+}
+
+impl<C: Crypto> McSession<C> {
+    pub fn new(
+        mc_group_id: u8,
+        mc_addr: DevAddr,
+        crypto: C,
+        min_mc_f_count: u32,
+        max_mc_f_count: u32,
+    ) -> Self {
+        Self {
+            mc_group_id,
+            mc_addr,
+            crypto,
+            next_f_cnt: min_mc_f_count,
+            max_mc_f_count,
+            exhausted: false,
+        }
+    }
+    pub fn mc_group_id(&self) -> u8 {
+        self.mc_group_id
+    }
+    pub fn mc_addr(&self) -> DevAddr {
+        self.mc_addr
+    }
+    /// Lowest McFCount accepted for the next frame
+    pub fn next_f_cnt(&self) -> u32 {
+        self.next_f_cnt
+    }
+
+    fn full_f_cnt(&self, f_cnt: u16) -> Result<u32, Error> {
+        if self.exhausted {
+            return Err(Error::FCntExhausted);
+        }
+        let error = Error::FCnt {
+            expected: self.next_f_cnt,
+            received: f_cnt,
+        };
+        let mut full = (self.next_f_cnt & !0xffff) | f_cnt as u32;
+        if full < self.next_f_cnt {
+            full = full.checked_add(0x10000).ok_or(error)?;
+        }
+        if full > self.max_mc_f_count {
+            return Err(error);
+        }
+        Ok(full)
+    }
+
+    /// Verifies and decrypts a multicast downlink addressed to this group
+    pub fn decode<'a>(&mut self, buf: &'a mut [u8]) -> Result<&'a mut Downlink, Error> {
+        let downlink = Downlink::from_bytes(buf)?;
+        if downlink.dev_addr() != self.mc_addr {
+            return Err(Error::DevAddr);
+        }
+        let f_ctrl = downlink.f_ctrl();
+        if downlink.confirmed() || f_ctrl.ack() || f_ctrl.f_opts_len() > 0 {
+            return Err(Error::Multicast);
+        }
+        match downlink.f_port() {
+            Some(f_port) if f_port > 0 => {}
+            f_port => return Err(Error::FPort(f_port)),
+        }
+        let f_cnt = self.full_f_cnt(downlink.f_cnt())?;
+        let downlink = Downlink::new_from_encrypted(buf, f_cnt, &mut self.crypto)?;
+        self.next_f_cnt = f_cnt.saturating_add(1);
+        self.exhausted |= f_cnt == u32::MAX;
+        Ok(downlink)
+    }
+}
+
+/// Selects the session a downlink is addressed to by its DevAddr
+pub fn find_session<'s, C: Crypto>(
+    sessions: &'s mut [McSession<C>],
+    buf: &[u8],
+) -> Option<&'s mut McSession<C>> {
+    let dev_addr = Downlink::try_ref_from_bytes(buf).ok()?.dev_addr();
+    sessions
+        .iter_mut()
+        .find(|session| session.mc_addr == dev_addr)
+}
+
+#[cfg(test)]
+mod tests {
+    use zerocopy::{FromBytes, IntoBytes};
+
+    use crate::{
+        crypto::soft::SoftCrypto,
+        types::{AppSKey, NwkSKey},
+    };
+
+    use super::*;
+    fn get_crypto() -> SoftCrypto {
+        let nwk_s_key = NwkSKey::read_from_bytes(&[3; 16]).unwrap();
+        let app_s_key = AppSKey::read_from_bytes(&[4; 16]).unwrap();
+        SoftCrypto::new(nwk_s_key, app_s_key)
+    }
+    fn get_session(mc_addr: [u8; 4]) -> McSession<SoftCrypto> {
+        McSession::new(
+            0,
+            DevAddr::read_from_bytes(&mc_addr).unwrap(),
+            get_crypto(),
+            0x10000,
+            0x1ffff,
+        )
+    }
+    fn unconfirmed_downlink() -> [u8; 18] {
+        [
+            0x60, 0x11, 0x22, 0x33, 0x44, 0x00, 0x05, 0x00, 0xc9, 0x33, 0x6e, 0xe9, 0xdd, 0x30,
+            0x62, 0x18, 0x83, 0xd2,
+        ]
+    }
+    #[test]
+    fn decode_multicast_downlink() {
+        let mut sessions = [
+            get_session([0x01, 0x02, 0x03, 0x04]),
+            get_session([0x11, 0x22, 0x33, 0x44]),
+        ];
+        let mut packet = unconfirmed_downlink();
+        let session = find_session(&mut sessions, &packet).unwrap();
+        assert_eq!(session.mc_addr().as_bytes(), &[0x11, 0x22, 0x33, 0x44]);
+        let downlink = session.decode(&mut packet).unwrap();
+        assert_eq!(downlink.f_port(), Some(201));
+        assert_eq!(&downlink.frm_payload().unwrap().data[..5], b"fuota");
+        assert_eq!(session.next_f_cnt(), 0x10006);
+    }
+    #[test]
+    fn reject_replayed_downlink() {
+        let mut session = get_session([0x11, 0x22, 0x33, 0x44]);
+        let mut packet = unconfirmed_downlink();
+        session.decode(&mut packet).unwrap();
+        let mut packet = unconfirmed_downlink();
+        assert!(matches!(
+            session.decode(&mut packet),
+            Err(Error::FCnt { .. })
+        ));
+    }
+    #[test]
+    fn reject_after_last_mc_f_count() {
+        // the downlink with McFCount `f_cnt` and a valid MIC
+        let downlink = |f_cnt: u32| {
+            let mut packet = unconfirmed_downlink();
+            packet[6..8].copy_from_slice(&(f_cnt as u16).to_le_bytes());
+            let mic = Downlink::try_ref_from_bytes(&packet)
+                .unwrap()
+                .calculate_mic(&mut get_crypto(), f_cnt, packet.len())
+                .unwrap();
+            packet[14..].copy_from_slice(mic.as_bytes());
+            packet
+        };
+        let mut session = McSession::new(
+            0,
+            DevAddr::read_from_bytes(&[0x11, 0x22, 0x33, 0x44]).unwrap(),
+            get_crypto(),
+            0xffff_0000,
+            u32::MAX,
+        );
+        session.decode(&mut downlink(0xffff_fff0)).unwrap();
+        session.decode(&mut downlink(u32::MAX)).unwrap();
+        assert!(matches!(
+            session.decode(&mut downlink(0xffff_fff0)),
+            Err(Error::FCntExhausted)
+        ));
+        assert!(matches!(
+            session.decode(&mut downlink(u32::MAX)),
+            Err(Error::FCntExhausted)
+        ));
+    }
+    #[test]
+    fn reject_non_multicast_downlinks() {
+        let mut session = get_session([0x11, 0x22, 0x33, 0x44]);
+        let mut confirmed = [
+            0xa0, 0x11, 0x22, 0x33, 0x44, 0x00, 0x06, 0x00, 0xc9, 0x85, 0xef, 0x42, 0xa5, 0xb8,
+            0x80, 0x69, 0xc1, 0x49,
+        ];
+        assert!(matches!(
+            session.decode(&mut confirmed),
+            Err(Error::Multicast)
+        ));
+        let mut mac_commands = [
+            0x60, 0x11, 0x22, 0x33, 0x44, 0x00, 0x06, 0x00, 0x00, 0xbf, 0xd9, 0x91, 0xe8, 0xd3,
+        ];
+        assert!(matches!(
+            session.decode(&mut mac_commands),
+            Err(Error::FPort(Some(0)))
+        ));
+        let mut other_group = get_session([0x01, 0x02, 0x03, 0x04]);
+        let mut packet = unconfirmed_downlink();
+        assert!(matches!(
+            other_group.decode(&mut packet),
+            Err(Error::DevAddr)
+        ));
+        assert_eq!(session.next_f_cnt(), 0x10000);
+    }
+}
@@ -0,0 +1,263 @@
+use bitfield_struct::bitfield;
+use zerocopy::{Immutable, IntoBytes, KnownLayout, Unaligned};
+
+use crate::{types::DevAddr, Error};
+
+use super::{cid::*, PACKAGE_IDENTIFIER, PACKAGE_VERSION};
+
+#[repr(u8)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum MulticastUplinkCommand {
+    /// Conveys the answer to PackageVersionReq
+    PackageVersionAns(PackageVersionAns) = PACKAGE_VERSION_CID,
+    /// Conveys the answer to McGroupStatusReq
+    McGroupStatusAns(McGroupStatusAns) = MC_GROUP_STATUS_CID,
+    /// Conveys the answer to McGroupSetupReq
+    McGroupSetupAns(McGroupSetupAns) = MC_GROUP_SETUP_CID,
+    /// Conveys the answer to McGroupDeleteReq
+    McGroupDeleteAns(McGroupDeleteAns) = MC_GROUP_DELETE_CID,
+    /// Conveys the answer to McClassCSessionReq
+    McClassCSessionAns(McSessionAns) = MC_CLASS_C_SESSION_CID,
+    /// Conveys the answer to McClassBSessionReq
+    McClassBSessionAns(McSessionAns) = MC_CLASS_B_SESSION_CID,
+}
+impl MulticastUplinkCommand {
+    // https://doc.rust-lang.org/reference/items/enumerations.html#pointer-casting
+    fn cid(&self) -> u8 {
+        unsafe { *((self as *const Self) as *const u8) }
+    }
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            MulticastUplinkCommand::PackageVersionAns(cmd) => cmd.as_bytes(),
+            MulticastUplinkCommand::McGroupStatusAns(cmd) => cmd.as_bytes(),
+            MulticastUplinkCommand::McGroupSetupAns(cmd) => cmd.as_bytes(),
+            MulticastUplinkCommand::McGroupDeleteAns(cmd) => cmd.as_bytes(),
+            MulticastUplinkCommand::McClassCSessionAns(cmd) => cmd.as_bytes(),
+            MulticastUplinkCommand::McClassBSessionAns(cmd) => cmd.as_bytes(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, IntoBytes, Immutable, KnownLayout, Unaligned)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(C)]
+pub struct PackageVersionAns {
+    package_identifier: u8,
+    package_version: u8,
+}
+impl Default for PackageVersionAns {
+    fn default() -> Self {
+        Self {
+            package_identifier: PACKAGE_IDENTIFIER,
+            package_version: PACKAGE_VERSION,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, IntoBytes, Immutable, KnownLayout, Unaligned)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(C)]
+pub struct McGroupStatusAnsGroup {
+    mc_group_id: u8,
+    mc_addr: [u8; 4],
+}
+
+#[derive(Clone, Debug, IntoBytes, Immutable, KnownLayout, Unaligned)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(C)]
+pub struct McGroupStatusAns {
+    status: McGroupStatusAnsStatus,
+    groups: [McGroupStatusAnsGroup; 4],
+}
+impl McGroupStatusAns {
+    /// `groups` holds the McAddr of every group defined on the end-device, indexed by McGroupID.
+    /// Only groups selected by `req_group_mask` are reported.
+    pub fn new(req_group_mask: u8, groups: &[Option<DevAddr>; 4]) -> Self {
+        let mut ans = Self {
+            status: McGroupStatusAnsStatus::new(),
+            groups: Default::default(),
+        };
+        let mut nb_total_groups = 0;
+        let mut ans_group_mask = 0;
+        let mut pos = 0;
+        for (id, group) in groups.iter().enumerate() {
+            let Some(mc_addr) = group else {
+                continue;
+            };
+            nb_total_groups += 1;
+            if req_group_mask & (1 << id) != 0 {
+                ans_group_mask |= 1 << id;
+                ans.groups[pos] = McGroupStatusAnsGroup {
+                    mc_group_id: id as u8,
+                    mc_addr: zerocopy::transmute!(*mc_addr),
+                };
+                pos += 1;
+            }
+        }
+        ans.status = ans
+            .status
+            .with_ans_group_mask(ans_group_mask)
+            .with_nb_total_groups(nb_total_groups);
+        ans
+    }
+    pub fn as_bytes(&self) -> &[u8] {
+        let len = 1 + 5 * self.status.ans_group_mask().count_ones() as usize;
+        &IntoBytes::as_bytes(self)[..len]
+    }
+}
+#[bitfield(u8, defmt = cfg(feature = "defmt"))]
+#[derive(IntoBytes, Immutable, KnownLayout, Unaligned)]
+pub struct McGroupStatusAnsStatus {
+    #[bits(4)]
+    pub ans_group_mask: u8,
+    #[bits(3)]
+    pub nb_total_groups: u8,
+    _rfu: bool,
+}
+
+#[derive(Clone, Debug, IntoBytes, Immutable, KnownLayout, Unaligned)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(C)]
+pub struct McGroupSetupAns {
+    pub status: McGroupSetupAnsStatus,
+}
+#[bitfield(u8, defmt = cfg(feature = "defmt"))]
+#[derive(IntoBytes, Immutable, KnownLayout, Unaligned)]
+pub struct McGroupSetupAnsStatus {
+    #[bits(2)]
+    pub mc_group_id: u8,
+    pub id_error: bool,
+    #[bits(5)]
+    _rfu: u8,
+}
+
+#[derive(Clone, Debug, IntoBytes, Immutable, KnownLayout, Unaligned)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(C)]
+pub struct McGroupDeleteAns {
+    pub status: McGroupDeleteAnsStatus,
+}
+#[bitfield(u8, defmt = cfg(feature = "defmt"))]
+#[derive(IntoBytes, Immutable, KnownLayout, Unaligned)]
+pub struct McGroupDeleteAnsStatus {
+    #[bits(2)]
+    pub mc_group_id: u8,
+    pub mc_group_undefined: bool,
+    #[bits(5)]
+    _rfu: u8,
+}
+
+/// Answer to both McClassCSessionReq and McClassBSessionReq
+#[derive(Clone, Debug, IntoBytes, Immutable, KnownLayout, Unaligned)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(C)]
+pub struct McSessionAns {
+    status: McSessionAnsStatus,
+    time_to_start: [u8; 3],
+}
+impl McSessionAns {
+    /// Session accepted, starting in `time_to_start` seconds
+    pub fn accepted(mc_group_id: u8, time_to_start: u32) -> Self {
+        let bytes = time_to_start.to_le_bytes();
+        Self {
+            status: McSessionAnsStatus::new().with_mc_group_id(mc_group_id),
+            time_to_start: [bytes[0], bytes[1], bytes[2]],
+        }
+    }
+    /// Session rejected, TimeToStart is omitted
+    pub fn rejected(status: McSessionAnsStatus) -> Self {
+        Self {
+            status,
+            time_to_start: [0; 3],
+        }
+    }
+    pub fn as_bytes(&self) -> &[u8] {
+        let status = self.status;
+        let len = if status.dr_error() || status.freq_error() || status.mc_group_undefined() {
+            1
+        } else {
+            4
+        };
+        &IntoBytes::as_bytes(self)[..len]
+    }
+}
+#[bitfield(u8, defmt = cfg(feature = "defmt"))]
+#[derive(IntoBytes, Immutable, KnownLayout, Unaligned)]
+pub struct McSessionAnsStatus {
+    #[bits(2)]
+    pub mc_group_id: u8,
+    pub dr_error: bool,
+    pub freq_error: bool,
+    pub mc_group_undefined: bool,
+    #[bits(3)]
+    _rfu: u8,
+}
+
+pub fn encode_multicast_commands<'a>(
+    cmds: &[MulticastUplinkCommand],
+    buf: &'a mut [u8],
+) -> Result<&'a [u8], Error> {
+    let mut pos = 0usize;
+    for cmd in cmds {
+        let bytes = cmd.as_bytes();
+        let len = bytes.len() + 1;
+        if pos + len > buf.len() {
+            return Err(Error::Size {
+                expected: pos + len,
+                actual: buf.len(),
+            });
+        }
+        buf[pos] = cmd.cid();
+        buf[pos + 1..pos + len].copy_from_slice(bytes);
+        pos += len
+    }
+    Ok(&buf[..pos])
+}
+
+#[cfg(test)]
+mod tests {
+    use zerocopy::FromBytes;
+
+    use super::*;
+    #[test]
+    fn encode_multicast_cmds() {
+        let cmds = [
+            MulticastUplinkCommand::PackageVersionAns(PackageVersionAns::default()),
+            MulticastUplinkCommand::McGroupSetupAns(McGroupSetupAns {
+                status: McGroupSetupAnsStatus::new()
+                    .with_mc_group_id(1)
+                    .with_id_error(true),
+            }),
+            MulticastUplinkCommand::McGroupDeleteAns(McGroupDeleteAns {
+                status: McGroupDeleteAnsStatus::new().with_mc_group_id(2),
+            }),
+            MulticastUplinkCommand::McClassCSessionAns(McSessionAns::accepted(3, 0x010203)),
+            MulticastUplinkCommand::McClassBSessionAns(McSessionAns::rejected(
+                McSessionAnsStatus::new()
+                    .with_mc_group_id(1)
+                    .with_freq_error(true),
+            )),
+        ];
+        let mut buf = [0u8; 255];
+        let cmd_buf = encode_multicast_commands(&cmds, &mut buf).unwrap();
+        assert_eq!(
+            cmd_buf,
+            &[0x00, 0x02, 0x01, 0x02, 0x05, 0x03, 0x02, 0x04, 0x03, 0x03, 0x02, 0x01, 0x05, 0x09]
+        )
+    }
+    #[test]
+    fn encode_mc_group_status_ans() {
+        let groups = [
+            Some(DevAddr::read_from_bytes(&[1, 2, 3, 4]).unwrap()),
+            None,
+            Some(DevAddr::read_from_bytes(&[5, 6, 7, 8]).unwrap()),
+            Some(DevAddr::read_from_bytes(&[9, 10, 11, 12]).unwrap()),
+        ];
+        let cmds = [MulticastUplinkCommand::McGroupStatusAns(
+            McGroupStatusAns::new(0b0111, &groups),
+        )];
+        let mut buf = [0u8; 255];
+        let cmd_buf = encode_multicast_commands(&cmds, &mut buf).unwrap();
+        assert_eq!(cmd_buf, &[0x01, 0x35, 0x00, 1, 2, 3, 4, 0x02, 5, 6, 7, 8])
+    }
+}
@@ -1,13 +1,25 @@
 use bitfield_struct::bitfield;
-use zerocopy::{FromBytes, Immutable, IntoBytes, KnownLayout, TryFromBytes, Unaligned};
+use zerocopy::{
+    little_endian::U16, transmute, FromBytes, Immutable, IntoBytes, KnownLayout, TryFromBytes,
+    Unaligned,
+};
+
+use crate::{
+    crypto::{Crypto, Encrypter as _, Key, Mac as _},
+    mac_command::downlink::Frequency,
+    types::{AppKey, DevAddr},
+    Error,
+};
 
 #[derive(TryFromBytes, IntoBytes, KnownLayout, Immutable, Unaligned)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[repr(u8)]
 pub enum JoinAcceptHeader {
     JoinAccept = 0b00100000,
 }
 
 #[derive(TryFromBytes, KnownLayout, Immutable)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[repr(C)]
 pub struct JoinAccept {
     mhdr: JoinAcceptHeader,
@@ -19,19 +31,127 @@ pub struct JoinAccept {
     cf_list: [u8],
 }
 
-#[bitfield(u8)]
+impl JoinAccept {
+    /// Decrypts a join-accept in place and verifies its MIC with the AppKey.
+    ///
+    /// The returned join-accept does not include the MIC.
+    pub fn new_from_encrypted<'a, C: Crypto>(
+        buf: &'a mut [u8],
+        crypto: &mut C,
+        app_key: &AppKey,
+    ) -> Result<&'a Self, Error> {
+        let len = buf.len();
+        if len != 17 && len != 33 {
+            return Err(Error::Size {
+                expected: if len > 17 { 33 } else { 17 },
+                actual: len,
+            });
+        }
+        Self::try_ref_from_bytes(&buf[..len - 4]).map_err(|_| Error::Mhdr(buf[0]))?;
+        let key: [u8; 16] = transmute!(*app_key);
+        // The network encrypts with AES decrypt, so the end-device decrypts with AES encrypt
+        let mut encrypter = crypto.get_encrypter(Key::Raw(key));
+        for block in buf[1..].chunks_mut(16) {
+            encrypter.encrypt_block(block);
+        }
+        let (data, mic) = buf.split_at(len - 4);
+        let mut mac = crypto.get_mac(Key::Raw(key));
+        if mac.calculate_mic(&[data]).as_bytes() != mic {
+            return Err(Error::MIC);
+        }
+        Self::try_ref_from_bytes(data).map_err(|_| Error::Mhdr(data[0]))
+    }
+    pub fn join_nonce(&self) -> u32 {
+        let [b0, b1, b2] = self.join_nonce;
+        u32::from_le_bytes([b0, b1, b2, 0])
+    }
+    pub fn net_id(&self) -> u32 {
+        let [b0, b1, b2] = self.net_id;
+        u32::from_le_bytes([b0, b1, b2, 0])
+    }
+    pub fn dev_addr(&self) -> DevAddr {
+        transmute!(self.dev_addr)
+    }
+    pub fn dl_settings(&self) -> DlSettings {
+        self.dl_settings
+    }
+    /// Delay of RX1 in seconds
+    pub fn rx_delay(&self) -> u8 {
+        match self.rx_delay & 0x0f {
+            0 => 1,
+            delay => delay,
+        }
+    }
+    /// `None` if the join-accept has no CFList or its CFListType is unknown
+    pub fn cf_list(&self) -> Option<CFList<'_>> {
+        if self.cf_list.len() != 16 {
+            return None;
+        }
+        match self.cf_list[15] {
+            0 => <[Frequency; 5]>::try_ref_from_bytes(&self.cf_list[..15])
+                .ok()
+                .map(CFList::Frequencies),
+            1 => <[U16; 5]>::ref_from_bytes(&self.cf_list[..10])
+                .ok()
+                .map(CFList::ChannelMasks),
+            _ => None,
+        }
+    }
+}
+
+#[bitfield(u8, defmt = cfg(feature = "defmt"))]
 #[derive(PartialEq, FromBytes, IntoBytes, KnownLayout, Immutable, Unaligned)]
 pub struct DlSettings {
     #[bits(4)]
-    rx2_dr: u8,
+    pub rx2_dr: u8,
     #[bits(3)]
-    rx1_dr_offset: u8,
+    pub rx1_dr_offset: u8,
     #[bits(1)]
     _rfu: bool,
 }
+#[cfg(feature = "serde")]
+crate::types::bitfield_serde!(DlSettings {
+    rx2_dr: u8 = set_rx2_dr_checked,
+    rx1_dr_offset: u8 = set_rx1_dr_offset_checked,
+});
+
+#[derive(Debug, PartialEq)]
+pub enum CFList<'a> {
+    /// Frequencies of channels 3 to 7, a frequency of 0 disables the channel
+    Frequencies(&'a [Frequency; 5]),
+    /// ChMask0 to ChMask4, each enabling 16 channels
+    ChannelMasks(&'a [U16; 5]),
+}
+#[cfg(feature = "defmt")]
+impl defmt::Format for CFList<'_> {
+    fn format(&self, f: defmt::Formatter) {
+        match self {
+            Self::Frequencies(frequencies) => defmt::write!(f, "Frequencies({})", frequencies),
+            Self::ChannelMasks(masks) => {
+                let masks: [u16; 5] = core::array::from_fn(|i| masks[i].get());
+                defmt::write!(f, "ChannelMasks({:04x})", masks)
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use crate::{
+        crypto::soft::SoftCrypto,
+        types::{AppSKey, NwkSKey},
+    };
+
     use super::*;
+    fn get_crypto() -> SoftCrypto {
+        SoftCrypto::new(
+            NwkSKey::read_from_bytes(&[0; 16]).unwrap(),
+            AppSKey::read_from_bytes(&[0; 16]).unwrap(),
+        )
+    }
+    fn get_app_key() -> AppKey {
+        AppKey::read_from_bytes(&core::array::from_fn::<u8, 16, _>(|i| i as u8)).unwrap()
+    }
     #[test]
     fn decode_join_accept() {
         let (join_accept, _) = JoinAccept::try_ref_from_prefix(&[
@@ -47,4 +167,55 @@ mod tests {
         );
         assert_eq!(join_accept.rx_delay, 0x0c);
     }
+    #[test]
+    fn decrypt_join_accept() {
+        let mut buf = [
+            0x20, 0x55, 0x25, 0xdd, 0x4c, 0x27, 0xc7, 0x82, 0xa9, 0xe9, 0xb4, 0x0b, 0x47, 0x38,
+            0xfc, 0x84, 0xbc,
+        ];
+        let join_accept =
+            JoinAccept::new_from_encrypted(&mut buf, &mut get_crypto(), &get_app_key()).unwrap();
+        assert_eq!(join_accept.join_nonce(), 0x030201);
+        assert_eq!(join_accept.net_id(), 0x000013);
+        assert_eq!(join_accept.dev_addr().as_bytes(), &[0x04, 0x03, 0x02, 0x01]);
+        assert_eq!(join_accept.dl_settings().rx1_dr_offset(), 1);
+        assert_eq!(join_accept.dl_settings().rx2_dr(), 3);
+        assert_eq!(join_accept.rx_delay(), 1);
+        assert_eq!(join_accept.cf_list(), None);
+    }
+    #[test]
+    fn decrypt_join_accept_with_cf_list() {
+        let mut buf = [
+            0x20, 0xe2, 0x8f, 0x0d, 0xf9, 0xa6, 0xb9, 0xf4, 0x71, 0xf3, 0x48, 0x72, 0x93, 0x1a,
+            0x44, 0x63, 0x56, 0x36, 0x38, 0x7b, 0x4e, 0x6a, 0x8b, 0x8c, 0x1e, 0xe7, 0x4b, 0xe3,
+            0x5f, 0x10, 0x7c, 0xe0, 0x20,
+        ];
+        let join_accept =
+            JoinAccept::new_from_encrypted(&mut buf, &mut get_crypto(), &get_app_key()).unwrap();
+        assert_eq!(join_accept.rx_delay(), 5);
+        let Some(CFList::ChannelMasks(masks)) = join_accept.cf_list() else {
+            panic!("decode error");
+        };
+        assert_eq!(masks[0].get(), 0xff00);
+        assert_eq!(masks[4].get(), 0x0001);
+    }
+    #[test]
+    fn reject_invalid_join_accept() {
+        let mut buf = [
+            0x20, 0x55, 0x25, 0xdd, 0x4c, 0x27, 0xc7, 0x82, 0xa9, 0xe9, 0xb4, 0x0b, 0x47, 0x38,
+            0xfc, 0x84, 0xbd,
+        ];
+        assert!(matches!(
+            JoinAccept::new_from_encrypted(&mut buf, &mut get_crypto(), &get_app_key()),
+            Err(Error::MIC)
+        ));
+        let mut buf = [0x20; 16];
+        assert!(matches!(
+            JoinAccept::new_from_encrypted(&mut buf, &mut get_crypto(), &get_app_key()),
+            Err(Error::Size {
+                expected: 17,
+                actual: 16
+            })
+        ));
+    }
 }
@@ -1,13 +1,19 @@
-use zerocopy::{Immutable, IntoBytes, KnownLayout};
+use zerocopy::{transmute, Immutable, IntoBytes, KnownLayout};
 
-use crate::types::{DevEui, DevNonce, JoinEui};
+use crate::{
+    crypto::{Crypto, Key, Mac as _},
+    types::{AppKey, DevEui, DevNonce, JoinEui},
+    Error,
+};
 
 #[derive(IntoBytes, KnownLayout, Immutable)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[repr(u8)]
 pub enum JoinRequestHeader {
     JoinRequest = 0b00000000,
 }
 #[derive(IntoBytes, KnownLayout, Immutable)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct JoinRequest {
     _mhdr: JoinRequestHeader,
     pub join_eui: JoinEui,
@@ -24,12 +30,39 @@ impl JoinRequest {
             dev_nonce,
         }
     }
+    /// Writes the join-request and its MIC, computed with the AppKey, into `buf`
+    pub fn build<'a, C: Crypto>(
+        &self,
+        buf: &'a mut [u8],
+        crypto: &mut C,
+        app_key: &AppKey,
+    ) -> Result<&'a [u8], Error> {
+        let bytes = self.as_bytes();
+        let len = bytes.len() + 4;
+        if buf.len() < len {
+            return Err(Error::Size {
+                expected: len,
+                actual: buf.len(),
+            });
+        }
+        let mic = crypto
+            .get_mac(Key::Raw(transmute!(*app_key)))
+            .calculate_mic(&[bytes]);
+        buf[..bytes.len()].copy_from_slice(bytes);
+        buf[bytes.len()..len].copy_from_slice(mic.as_bytes());
+        Ok(&buf[..len])
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use zerocopy::FromBytes;
 
+    use crate::{
+        crypto::soft::SoftCrypto,
+        types::{AppSKey, NwkSKey},
+    };
+
     use super::*;
     #[test]
     fn encode_join_request() {
@@ -47,4 +80,25 @@ mod tests {
         );
         assert_eq!(join_request.dev_nonce.as_bytes(), &[0x11, 0x22]);
     }
+    #[test]
+    fn build_join_request() {
+        let join_request = JoinRequest::new(
+            JoinEui::read_from_bytes(&[0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08]).unwrap(),
+            DevEui::read_from_bytes(&[0x10, 0x20, 0x30, 0x40, 0x50, 0x60, 0x70, 0x80]).unwrap(),
+            DevNonce::from(0x2211),
+        );
+        let app_key =
+            AppKey::read_from_bytes(&core::array::from_fn::<u8, 16, _>(|i| i as u8)).unwrap();
+        let mut crypto = SoftCrypto::new(
+            NwkSKey::read_from_bytes(&[0; 16]).unwrap(),
+            AppSKey::read_from_bytes(&[0; 16]).unwrap(),
+        );
+        let mut buf = [0u8; 23];
+        let bytes = join_request.build(&mut buf, &mut crypto, &app_key).unwrap();
+        assert_eq!(&bytes[..19], join_request.as_bytes());
+        assert_eq!(&bytes[19..], &[0xb3, 0x03, 0xc7, 0x65]);
+        assert!(join_request
+            .build(&mut buf[..22], &mut crypto, &app_key)
+            .is_err());
+    }
 }
@@ -0,0 +1,233 @@
+//! Serialization of decoded frames, with the JSON shape used by network servers such as
+//! ChirpStack. Identifiers are MSB-first hex strings, payloads are hex strings and field names
+//! are camelCase. A data frame, with its FRMPayload as found in the buffer, i.e. decrypted if it
+//! was decoded with `new_from_encrypted`:
+//!
+//! ```json
+//! {
+//!   "mhdr": { "fType": "UnconfirmedDataUp", "major": "LoRaWANR1" },
+//!   "payload": {
+//!     "fhdr": {
+//!       "devAddr": "01020304",
+//!       "fCtrl": { "adr": true, "adrAckReq": false, "ack": false, "classB": false, "fOptsLen": 0 },
+//!       "fCnt": 1,
+//!       "fOpts": ""
+//!     },
+//!     "fPort": 1,
+//!     "frmPayload": "0a0b"
+//!   },
+//!   "mic": "01020304"
+//! }
+//! ```
+//!
+//! `fPort` and `frmPayload` are `null` without FRMPayload. A join-request has the payload
+//! `{ "joinEui": "…", "devEui": "…", "devNonce": 1 }` and a decrypted join-accept
+//! `{ "joinNonce": 1, "homeNetId": "000013", "devAddr": "…", "dlSettings": { "rx2Dr": 0,
+//! "rx1DrOffset": 0 }, "rxDelay": 1, "cfList": null }`. Neither has a MIC in this
+//! representation, as it is not kept in the decoded frame.
+use serde::{ser::SerializeStruct, Serialize, Serializer};
+use zerocopy::IntoBytes;
+
+use crate::types::{hex::Hex, DevEui, DevNonce, JoinEui};
+
+use super::{
+    join_accept::{CFList, JoinAccept},
+    join_request::JoinRequest,
+    mac_payload::{FCtrl as _, MacPayload, Mhdr},
+};
+
+struct HexStr<'a>(&'a [u8]);
+
+impl Serialize for HexStr<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&Hex::new(self.0, false))
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct MhdrJson {
+    f_type: &'static str,
+    major: &'static str,
+}
+
+impl MhdrJson {
+    fn new(mhdr: u8) -> Self {
+        let f_type = match mhdr >> 5 {
+            0 => "JoinRequest",
+            1 => "JoinAccept",
+            2 => "UnconfirmedDataUp",
+            3 => "UnconfirmedDataDown",
+            4 => "ConfirmedDataUp",
+            5 => "ConfirmedDataDown",
+            6 => "RejoinRequest",
+            _ => "Proprietary",
+        };
+        Self {
+            f_type,
+            major: "LoRaWANR1",
+        }
+    }
+}
+
+struct Fhdr<'a, MHDR: Mhdr>(&'a MacPayload<MHDR>);
+
+impl<MHDR> Serialize for Fhdr<'_, MHDR>
+where
+    MHDR: Mhdr,
+    MHDR::FCtrl: Serialize,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let frame = self.0;
+        let f_opts_len = frame.f_ctrl().f_opts_len();
+        let f_opts = frame.as_bytes().get(8..8 + f_opts_len).unwrap_or_default();
+        let mut state = serializer.serialize_struct("Fhdr", 4)?;
+        state.serialize_field("devAddr", &frame.dev_addr())?;
+        state.serialize_field("fCtrl", &frame.f_ctrl())?;
+        state.serialize_field("fCnt", &frame.f_cnt())?;
+        state.serialize_field("fOpts", &HexStr(f_opts))?;
+        state.end()
+    }
+}
+
+struct MacPayloadJson<'a, MHDR: Mhdr>(&'a MacPayload<MHDR>);
+
+impl<MHDR> Serialize for MacPayloadJson<'_, MHDR>
+where
+    MHDR: Mhdr,
+    MHDR::FCtrl: Serialize,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let frame = self.0;
+        // FRMPayload without FPort and MIC
+        let frm_payload = frame.f_port().and_then(|_| {
+            let data = &frame.frm_payload().ok()?.data;
+            data.get(..data.len().checked_sub(4)?)
+        });
+        let mut state = serializer.serialize_struct("MacPayload", 3)?;
+        state.serialize_field("fhdr", &Fhdr(frame))?;
+        state.serialize_field("fPort", &frame.f_port())?;
+        state.serialize_field("frmPayload", &frm_payload.map(HexStr))?;
+        state.end()
+    }
+}
+
+impl<MHDR> Serialize for MacPayload<MHDR>
+where
+    MHDR: Mhdr,
+    MHDR::FCtrl: Serialize,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("PhyPayload", 3)?;
+        state.serialize_field("mhdr", &MhdrJson::new(self.as_bytes()[0]))?;
+        state.serialize_field("payload", &MacPayloadJson(self))?;
+        state.serialize_field("mic", &self.mic().ok())?;
+        state.end()
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct JoinRequestJson<'a> {
+    join_eui: &'a JoinEui,
+    dev_eui: &'a DevEui,
+    dev_nonce: &'a DevNonce,
+}
+
+impl Serialize for JoinRequest {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("PhyPayload", 2)?;
+        state.serialize_field("mhdr", &MhdrJson::new(self.as_bytes()[0]))?;
+        let payload = JoinRequestJson {
+            join_eui: &self.join_eui,
+            dev_eui: &self.dev_eui,
+            dev_nonce: &self.dev_nonce,
+        };
+        state.serialize_field("payload", &payload)?;
+        state.end()
+    }
+}
+
+struct JoinAcceptJson<'a>(&'a JoinAccept);
+
+impl Serialize for JoinAcceptJson<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let join_accept = self.0;
+        let net_id = join_accept.net_id().to_be_bytes();
+        let mut state = serializer.serialize_struct("JoinAcceptPayload", 6)?;
+        state.serialize_field("joinNonce", &join_accept.join_nonce())?;
+        state.serialize_field("homeNetId", &HexStr(&net_id[1..]))?;
+        state.serialize_field("devAddr", &join_accept.dev_addr())?;
+        state.serialize_field("dlSettings", &join_accept.dl_settings())?;
+        state.serialize_field("rxDelay", &join_accept.rx_delay())?;
+        state.serialize_field("cfList", &join_accept.cf_list())?;
+        state.end()
+    }
+}
+
+impl Serialize for JoinAccept {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("PhyPayload", 2)?;
+        state.serialize_field("mhdr", &MhdrJson::new(0x20))?;
+        state.serialize_field("payload", &JoinAcceptJson(self))?;
+        state.end()
+    }
+}
+
+/// `{ "frequencies": [868100000, …] }` or `{ "channelMasks": [255, …] }`
+impl Serialize for CFList<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("CFList", 1)?;
+        match self {
+            CFList::Frequencies(frequencies) => {
+                state.serialize_field("frequencies", frequencies)?;
+            }
+            CFList::ChannelMasks(masks) => {
+                let masks: [u16; 5] = core::array::from_fn(|i| masks[i].get());
+                state.serialize_field("channelMasks", &masks)?;
+            }
+        }
+        state.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use zerocopy::FromBytes as _;
+
+    use crate::{
+        crypto::soft::SoftCrypto,
+        phy_payload::mac_payload::downlink::Downlink,
+        types::{AppSKey, NwkSKey},
+    };
+
+    #[test]
+    fn downlink_json() {
+        let mut packet = [
+            0xa0, 0x04, 0x03, 0x02, 0x01, 0x80, 0xff, 0x2a, 0x2a, 0x0a, 0xf1, 0xa3, 0x6a, 0x05,
+            0xd0, 0x12, 0x5f, 0x88, 0x5d, 0x88, 0x1d, 0x49, 0xe1,
+        ];
+        let mut crypto = SoftCrypto::new(
+            NwkSKey::read_from_bytes(&[2; 16]).unwrap(),
+            AppSKey::read_from_bytes(&[1; 16]).unwrap(),
+        );
+        let downlink = Downlink::new_from_encrypted(&mut packet, 0x12AFF, &mut crypto).unwrap();
+        assert_eq!(
+            serde_json::to_value(&*downlink).unwrap(),
+            serde_json::json!({
+                "mhdr": { "fType": "ConfirmedDataDown", "major": "LoRaWANR1" },
+                "payload": {
+                    "fhdr": {
+                        "devAddr": "01020304",
+                        "fCtrl": { "adr": true, "ack": false, "fPending": false, "fOptsLen": 0 },
+                        "fCnt": 0x2aff,
+                        "fOpts": ""
+                    },
+                    "fPort": 42,
+                    "frmPayload": "68656c6c6f206c6f7261"
+                },
+                "mic": "881d49e1"
+            })
+        );
+    }
+}
@@ -1,16 +1,20 @@
-use zerocopy::{Immutable, IntoBytes, KnownLayout, TryFromBytes, Unaligned};
+use bitfield_struct::bitfield;
+use zerocopy::{FromBytes, Immutable, IntoBytes, KnownLayout, TryFromBytes, Unaligned};
 
 use crate::mac_command::downlink::DownlinkMacCommandDecoder;
 
 use super::{MacPayload, Mhdr};
 
 #[derive(Debug, TryFromBytes, PartialEq, Eq, KnownLayout, IntoBytes, Unaligned, Immutable)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[repr(u8)]
 pub enum DownlinkHeader {
     Unconfirmed = 0b01100000,
     Confirmed = 0b10100000,
 }
 impl Mhdr for DownlinkHeader {
+    type FCtrl = DownlinkFCtrl;
+
     fn new(confirmed: bool) -> Self {
         match confirmed {
             true => Self::Confirmed,
@@ -23,6 +27,25 @@ impl Mhdr for DownlinkHeader {
     }
 }
 
+#[bitfield(u8, defmt = cfg(feature = "defmt"))]
+#[derive(FromBytes, IntoBytes, KnownLayout, Immutable, Unaligned)]
+pub struct DownlinkFCtrl {
+    #[bits(4)]
+    pub f_opts_len: usize,
+    /// The network has more data pending
+    pub f_pending: bool,
+    pub ack: bool,
+    _rfu: bool,
+    pub adr: bool,
+}
+#[cfg(feature = "serde")]
+crate::types::bitfield_serde!(DownlinkFCtrl {
+    adr: bool = set_adr_checked,
+    ack: bool = set_ack_checked,
+    f_pending: bool = set_f_pending_checked,
+    f_opts_len: usize = set_f_opts_len_checked,
+});
+
 pub type Downlink = MacPayload<DownlinkHeader>;
 
 impl Downlink {
@@ -34,10 +57,10 @@ impl Downlink {
     }
     pub fn mac_commands(&self) -> Option<DownlinkMacCommandDecoder<'_>> {
         let f_opts_len = self.f_ctrl.f_opts_len();
-        if self.f_ctrl.f_opts_len() > 0 {
-            Some(DownlinkMacCommandDecoder::new(&self.data[..f_opts_len]))
+        if f_opts_len > 0 {
+            Some(DownlinkMacCommandDecoder::new(self.data.get(..f_opts_len)?))
         } else {
-            let payload = self.frm_payload();
+            let payload = self.frm_payload().ok()?;
             if payload.f_port == 0 {
                 Some(DownlinkMacCommandDecoder::new(&payload.data))
             } else {
@@ -54,7 +77,9 @@ mod tests {
 
     use crate::{
         crypto::soft::SoftCrypto,
+        frame_counter::FrameCounter,
         types::{AppSKey, NwkSKey},
+        Error,
     };
 
     use super::*;
@@ -77,11 +102,70 @@ mod tests {
         assert_eq!(downlink.f_cnt.get(), 0x2AFF);
         let f_ctrl = downlink.f_ctrl;
         assert_eq!(f_ctrl.f_opts_len(), 0);
-        assert_eq!(f_ctrl.adr(), true);
-        assert_eq!(f_ctrl.ack(), false);
-        assert_eq!(f_ctrl.f_pending(), false);
-        let payload = downlink.frm_payload();
+        assert!(f_ctrl.adr());
+        assert!(!f_ctrl.ack());
+        assert!(!f_ctrl.f_pending());
+        let payload = downlink.frm_payload().unwrap();
         assert_eq!(payload.data.len(), 14);
         assert_eq!(&payload.data[..payload.data.len() - 4], b"hello lora")
     }
+    #[test]
+    fn reject_malformed_downlink() {
+        assert_eq!(
+            Downlink::from_bytes(&[0xa0; 5]).err(),
+            Some(Error::Size {
+                expected: 12,
+                actual: 5
+            })
+        );
+        let mut uplink = [0u8; 12];
+        uplink[0] = 0x40;
+        assert_eq!(Downlink::from_bytes(&uplink).err(), Some(Error::Mhdr(0x40)));
+        let mut f_opts = [0u8; 14];
+        f_opts[0] = 0x60;
+        f_opts[5] = 0x0f;
+        assert_eq!(
+            Downlink::from_bytes(&f_opts).err(),
+            Some(Error::FOptsLen {
+                f_opts_len: 15,
+                available: 2
+            })
+        );
+    }
+    #[test]
+    fn decode_downlink_with_counter() {
+        let packet = [
+            0xa0, 0x04, 0x03, 0x02, 0x01, 0x80, 0xff, 0x2a, 0x2a, 0x0a, 0xf1, 0xa3, 0x6a, 0x05,
+            0xd0, 0x12, 0x5f, 0x88, 0x5d, 0x88, 0x1d, 0x49, 0xe1,
+        ];
+        let mut crypto = get_crypto();
+        let mut n_f_cnt_down = FrameCounter::new(0x12A00);
+        let mut buf = packet;
+        let downlink =
+            Downlink::new_from_encrypted_with_counter(&mut buf, &mut n_f_cnt_down, &mut crypto)
+                .unwrap();
+        assert_eq!(downlink.f_port(), Some(0x2a));
+        assert_eq!(n_f_cnt_down.next(), 0x12B00);
+        assert!(matches!(
+            Downlink::new_from_encrypted_with_counter(
+                &mut packet.clone(),
+                &mut n_f_cnt_down,
+                &mut crypto
+            ),
+            Err(Error::FCnt {
+                expected: 0x12B00,
+                received: 0x2AFF
+            })
+        ));
+        let mut n_f_cnt_down = FrameCounter::new(0x2A00);
+        assert!(matches!(
+            Downlink::new_from_encrypted_with_counter(
+                &mut packet.clone(),
+                &mut n_f_cnt_down,
+                &mut crypto
+            ),
+            Err(Error::MIC)
+        ));
+        assert_eq!(n_f_cnt_down.next(), 0x2A00);
+    }
 }
@@ -1,10 +1,10 @@
-use bitfield_struct::bitfield;
 use zerocopy::{
     little_endian::U16, FromBytes, Immutable, IntoBytes, KnownLayout, TryFromBytes, Unaligned,
 };
 
 use crate::{
     crypto::{Crypto, Encrypter as _, Key, Mac as _},
+    frame_counter::FrameCounter,
     mac_command::uplink::{encode_maccommands, UplinkMacCommmand},
     types::{DevAddr, MIC},
     Error,
@@ -12,46 +12,103 @@ use crate::{
 pub mod downlink;
 pub mod uplink;
 
-#[bitfield(u8)]
-#[derive(FromBytes, IntoBytes, KnownLayout, Immutable, Unaligned)]
-pub struct FCtrl {
-    #[bits(4)]
-    pub f_opts_len: usize,
-    pub f_pending: bool,
-    pub ack: bool,
-    _rfu: bool,
-    pub adr: bool,
+use downlink::DownlinkFCtrl;
+use uplink::UplinkFCtrl;
+
+/// Frame control octet, whose bits 4 and 6 depend on the direction, see [`UplinkFCtrl`] and
+/// [`DownlinkFCtrl`]
+pub trait FCtrl: Copy + FromBytes + IntoBytes + Immutable + KnownLayout + Unaligned {
+    fn adr(&self) -> bool;
+    fn ack(&self) -> bool;
+    fn f_opts_len(&self) -> usize;
+    fn set_f_opts_len(&mut self, f_opts_len: usize);
+}
+
+macro_rules! impl_f_ctrl {
+    ($t:ty) => {
+        impl FCtrl for $t {
+            fn adr(&self) -> bool {
+                <$t>::adr(self)
+            }
+            fn ack(&self) -> bool {
+                <$t>::ack(self)
+            }
+            fn f_opts_len(&self) -> usize {
+                <$t>::f_opts_len(self)
+            }
+            fn set_f_opts_len(&mut self, f_opts_len: usize) {
+                <$t>::set_f_opts_len(self, f_opts_len)
+            }
+        }
+    };
 }
+impl_f_ctrl!(UplinkFCtrl);
+impl_f_ctrl!(DownlinkFCtrl);
 
 #[derive(FromBytes, IntoBytes, Immutable, Unaligned)]
-#[repr(packed)]
-pub struct FHDR {
+#[repr(C, packed)]
+pub struct FHDR<F: FCtrl = UplinkFCtrl> {
     dev_addr: DevAddr,
-    f_ctrl: FCtrl,
+    f_ctrl: F,
     f_cnt: U16,
     f_opts: [u8; 15],
 }
-impl FHDR {
+impl FHDR<UplinkFCtrl> {
     pub fn new(
+        dev_addr: DevAddr,
+        adr: bool,
+        ack: bool,
+        class_b: bool,
+        f_cnt: u16,
+        f_opts: &[u8],
+    ) -> Result<Self, Error> {
+        let f_ctrl = UplinkFCtrl::new()
+            .with_adr(adr)
+            .with_ack(ack)
+            .with_class_b(class_b);
+        Self::with_f_ctrl(dev_addr, f_ctrl, f_cnt, f_opts)
+    }
+    pub fn with_adr_ack_req(mut self, adr_ack_req: bool) -> Self {
+        self.f_ctrl.set_adr_ack_req(adr_ack_req);
+        self
+    }
+}
+impl FHDR<DownlinkFCtrl> {
+    pub fn downlink(
         dev_addr: DevAddr,
         adr: bool,
         ack: bool,
         f_pending: bool,
         f_cnt: u16,
         f_opts: &[u8],
+    ) -> Result<Self, Error> {
+        let f_ctrl = DownlinkFCtrl::new()
+            .with_adr(adr)
+            .with_ack(ack)
+            .with_f_pending(f_pending);
+        Self::with_f_ctrl(dev_addr, f_ctrl, f_cnt, f_opts)
+    }
+}
+impl<F: FCtrl> FHDR<F> {
+    /// FHDR with the bits of `f_ctrl`, whose FOptsLen is set from `f_opts`
+    pub fn with_f_ctrl(
+        dev_addr: DevAddr,
+        mut f_ctrl: F,
+        f_cnt: u16,
+        f_opts: &[u8],
     ) -> Result<Self, Error> {
         let mut f_opts_buf = [0u8; 15];
         if f_opts.len() > f_opts_buf.len() {
-            return Err(Error::Size);
+            return Err(Error::FOptsLen {
+                f_opts_len: f_opts.len(),
+                available: f_opts_buf.len(),
+            });
         }
         f_opts_buf[0..f_opts.len()].copy_from_slice(f_opts);
+        f_ctrl.set_f_opts_len(f_opts.len());
         Ok(Self {
             dev_addr,
-            f_ctrl: FCtrl::new()
-                .with_adr(adr)
-                .with_ack(ack)
-                .with_f_pending(f_pending)
-                .with_f_opts_len(f_opts.len()),
+            f_ctrl,
             f_cnt: f_cnt.into(),
             f_opts: f_opts_buf,
         })
@@ -59,41 +116,127 @@ impl FHDR {
     pub fn dev_addr(&self) -> DevAddr {
         self.dev_addr
     }
-    pub fn f_ctrl(&self) -> FCtrl {
+    pub fn f_ctrl(&self) -> F {
         self.f_ctrl
     }
     pub fn f_cnt(&self) -> u16 {
         self.f_cnt.get()
     }
     pub fn f_opts(&self) -> &[u8] {
-        &self.f_opts[..self.f_ctrl.f_opts_len()]
+        &self.f_opts[..self.f_ctrl().f_opts_len()]
+    }
+}
+#[cfg(feature = "defmt")]
+impl<F: FCtrl + defmt::Format> defmt::Format for FHDR<F> {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(
+            f,
+            "FHDR {{ dev_addr: {}, f_ctrl: {}, f_cnt: {=u16}, f_opts: {=[u8]:02x} }}",
+            self.dev_addr(),
+            self.f_ctrl(),
+            self.f_cnt(),
+            self.f_opts()
+        )
     }
 }
 
 #[derive(KnownLayout, FromBytes, IntoBytes, Immutable, Unaligned)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[repr(C, packed)]
 pub struct FRMPayload {
     pub f_port: u8,
     pub data: [u8],
 }
 impl FRMPayload {
-    pub fn new_from_slice<'a>(buf: &'a mut [u8], f_port: u8, slice: &[u8]) -> &'a mut Self {
-        let payload = Self::mut_from_bytes(&mut buf[..1 + slice.len()]).unwrap();
+    pub fn new_from_slice<'a>(
+        buf: &'a mut [u8],
+        f_port: u8,
+        slice: &[u8],
+    ) -> Result<&'a mut Self, Error> {
+        let payload = Self::prefix(buf, slice.len())?;
         payload.f_port = f_port;
         payload.data.copy_from_slice(slice);
-        payload
+        Ok(payload)
     }
     pub fn new_from_maccommands<'a>(
         buf: &'a mut [u8],
         mac_commands: &[UplinkMacCommmand],
-    ) -> &'a mut Self {
-        let res = encode_maccommands(mac_commands, &mut buf[1..]).unwrap();
-        let payload_len = res.len();
-        Self::mut_from_bytes(&mut buf[..1 + payload_len]).unwrap()
+    ) -> Result<&'a mut Self, Error> {
+        let data_len = match buf.get_mut(1..) {
+            Some(data) => encode_maccommands(mac_commands, data)?.len(),
+            None => 0,
+        };
+        let payload = Self::prefix(buf, data_len)?;
+        payload.f_port = 0;
+        Ok(payload)
+    }
+    /// FRMPayload of `data_len` bytes after FPort at the start of `buf`
+    fn prefix(buf: &mut [u8], data_len: usize) -> Result<&mut Self, Error> {
+        let actual = buf.len();
+        let size = Error::Size {
+            expected: 1 + data_len,
+            actual,
+        };
+        let buf = buf.get_mut(..1 + data_len).ok_or(size)?;
+        Self::mut_from_bytes(buf).map_err(|_| size)
     }
 }
 
+#[cfg(feature = "defmt")]
+impl<MHDR> defmt::Format for MacPayload<MHDR>
+where
+    MHDR: Mhdr,
+    MHDR::FCtrl: defmt::Format,
+{
+    fn format(&self, f: defmt::Formatter) {
+        let f_opts_len = self.f_ctrl().f_opts_len();
+        let f_opts = self.data.get(..f_opts_len).unwrap_or_default();
+        // FRMPayload without FPort and MIC
+        let frm_payload = self
+            .f_port()
+            .and_then(|_| self.data.get(f_opts_len + 1..self.data.len() - 4))
+            .unwrap_or_default();
+        defmt::write!(
+            f,
+            "MacPayload {{ confirmed: {=bool}, dev_addr: {}, f_ctrl: {}, f_cnt: {=u16}, f_opts: {=[u8]:02x}, f_port: {}, frm_payload: {=[u8]:02x}, mic: {} }}",
+            self.as_bytes()[0] == MHDR::new(true).as_bytes()[0],
+            self.dev_addr(),
+            self.f_ctrl(),
+            self.f_cnt(),
+            f_opts,
+            self.f_port(),
+            frm_payload,
+            self.mic().ok()
+        )
+    }
+}
+
+/// Checks the length and the MHDR of a received frame before parsing it
+fn check_frame<MHDR: Mhdr>(buf: &[u8]) -> Result<(), Error> {
+    // MHDR, DevAddr, FCtrl, FCnt and MIC
+    const MIN_LEN: usize = 12;
+    if buf.len() < MIN_LEN {
+        return Err(Error::Size {
+            expected: MIN_LEN,
+            actual: buf.len(),
+        });
+    }
+    if MHDR::try_read_from_bytes(&buf[..1]).is_err() {
+        return Err(Error::Mhdr(buf[0]));
+    }
+    let f_opts_len = (buf[5] & 0x0f) as usize;
+    if buf.len() < MIN_LEN + f_opts_len {
+        return Err(Error::FOptsLen {
+            f_opts_len,
+            available: buf.len() - MIN_LEN,
+        });
+    }
+    Ok(())
+}
+
 pub trait Mhdr: IntoBytes + Immutable + TryFromBytes {
+    /// FCtrl of the direction of the frame
+    type FCtrl: FCtrl;
     fn new(confirmed: bool) -> Self;
     fn dir() -> u8;
 }
@@ -106,7 +249,7 @@ where
 {
     mhdr: MHDR,
     dev_addr: DevAddr,
-    f_ctrl: FCtrl,
+    f_ctrl: MHDR::FCtrl,
     f_cnt: U16,
     data: [u8],
 }
@@ -115,78 +258,174 @@ impl<MHDR> MacPayload<MHDR>
 where
     MHDR: Mhdr,
 {
-    pub fn f_ctrl(&self) -> FCtrl {
+    pub fn f_ctrl(&self) -> MHDR::FCtrl {
         self.f_ctrl
     }
     pub fn f_cnt(&self) -> u16 {
         self.f_cnt.get()
     }
-    pub fn new(buf: &mut [u8], confirmed: bool, fhdr: FHDR) -> &mut Self {
+    pub fn dev_addr(&self) -> DevAddr {
+        self.dev_addr
+    }
+    /// FPort of the frame, `None` if the frame carries no FRMPayload
+    pub fn f_port(&self) -> Option<u8> {
+        let f_opts_len = self.f_ctrl().f_opts_len();
+        if self.data.len() > f_opts_len + 4 {
+            Some(self.data[f_opts_len])
+        } else {
+            None
+        }
+    }
+    /// Writes the MHDR and `fhdr` at the start of `buf`, which must also have room for the MIC
+    pub fn new(
+        buf: &mut [u8],
+        confirmed: bool,
+        fhdr: FHDR<MHDR::FCtrl>,
+    ) -> Result<&mut Self, Error> {
         let mhdr = MHDR::new(confirmed);
-        buf[0] = mhdr.as_bytes()[0];
-        let fhdr_len = 7 + fhdr.f_ctrl.f_opts_len();
+        let fhdr_len = 7 + fhdr.f_ctrl().f_opts_len();
+        // mhdr + fhdr + mic
+        if buf.len() < 1 + fhdr_len + 4 {
+            return Err(Error::Size {
+                expected: 1 + fhdr_len + 4,
+                actual: buf.len(),
+            });
+        }
+        buf[..1].copy_from_slice(mhdr.as_bytes());
         buf[1..1 + fhdr_len].copy_from_slice(&fhdr.as_bytes()[..fhdr_len]);
-        let uplink = Self::try_mut_from_bytes(buf).unwrap();
-        uplink
+        let mhdr = buf[0];
+        Self::try_mut_from_bytes(buf).map_err(|_| Error::Mhdr(mhdr))
     }
 
+    /// Parses a received frame, checking its MHDR and that FOpts and the MIC fit in it
+    pub fn from_bytes(buf: &[u8]) -> Result<&Self, Error> {
+        check_frame::<MHDR>(buf)?;
+        Self::try_ref_from_bytes(buf).map_err(|_| Error::Mhdr(buf[0]))
+    }
     pub fn new_from_encrypted<'a, C: Crypto>(
         buf: &'a mut [u8],
         f_cnt: u32,
         crypto: &mut C,
     ) -> Result<&'a mut Self, Error> {
-        let mac_payload = Self::try_mut_from_bytes(buf).map_err(|_| Error::Payload)?;
-        if !mac_payload.validate_mic(crypto, 0x12AFF, mac_payload.as_bytes().len()) {
+        check_frame::<MHDR>(buf)?;
+        let mhdr = buf[0];
+        let mac_payload = Self::try_mut_from_bytes(buf).map_err(|_| Error::Mhdr(mhdr))?;
+        let f_opts_len = mac_payload.f_ctrl().f_opts_len();
+        if !mac_payload.validate_mic(crypto, f_cnt, mac_payload.as_bytes().len()) {
             return Err(Error::MIC);
         }
-        //use encrypt to decrypt
-        mac_payload.encrypt(crypto, f_cnt, mac_payload.data.len() - 4);
+        if mac_payload.f_port().is_some() {
+            //use encrypt to decrypt, f_port and MIC are not encrypted
+            let payload_len = mac_payload.data.len() - f_opts_len - 5;
+            mac_payload.encrypt(crypto, f_cnt, payload_len)?;
+        }
         Ok(mac_payload)
     }
-    pub fn frm_payload(&self) -> &FRMPayload {
-        FRMPayload::ref_from_bytes(&self.data[self.f_ctrl.f_opts_len()..]).unwrap()
+    /// Like [`Self::new_from_encrypted`], reconstructing the full frame counter from `f_cnt`
+    /// and updating it once the MIC is verified
+    pub fn new_from_encrypted_with_counter<'a, C: Crypto>(
+        buf: &'a mut [u8],
+        f_cnt: &mut FrameCounter,
+        crypto: &mut C,
+    ) -> Result<&'a mut Self, Error> {
+        let mac_payload = Self::from_bytes(buf)?;
+        let full_f_cnt = f_cnt.reconstruct(mac_payload.f_cnt())?;
+        let mac_payload = Self::new_from_encrypted(buf, full_f_cnt, crypto)?;
+        f_cnt.accept(full_f_cnt);
+        Ok(mac_payload)
     }
-    pub fn frm_payload_mut(&mut self) -> &mut FRMPayload {
-        FRMPayload::mut_from_bytes(&mut self.data[self.f_ctrl.f_opts_len()..]).unwrap()
+    /// FRMPayload after FOpts, running up to the end of the frame, MIC included
+    pub fn frm_payload(&self) -> Result<&FRMPayload, Error> {
+        let f_opts_len = self.f_ctrl().f_opts_len();
+        self.data
+            .get(f_opts_len..)
+            .and_then(|data| FRMPayload::ref_from_bytes(data).ok())
+            .ok_or(Error::FOptsLen {
+                f_opts_len,
+                available: self.data.len(),
+            })
     }
-    pub fn mic(&self) -> MIC {
-        MIC::read_from_bytes(&self.data[self.data.len() - 4..]).unwrap()
+    pub fn frm_payload_mut(&mut self) -> Result<&mut FRMPayload, Error> {
+        let f_opts_len = self.f_ctrl().f_opts_len();
+        let available = self.data.len();
+        self.data
+            .get_mut(f_opts_len..)
+            .and_then(|data| FRMPayload::mut_from_bytes(data).ok())
+            .ok_or(Error::FOptsLen {
+                f_opts_len,
+                available,
+            })
     }
-    pub fn calculate_mic<C: Crypto>(&self, crypto: &mut C, f_cnt: u32, total_len: usize) -> MIC {
-        //MIC
+    pub fn mic(&self) -> Result<MIC, Error> {
+        let start = self.data.len().checked_sub(4).ok_or(Error::Size {
+            expected: 12,
+            actual: self.as_bytes().len(),
+        })?;
+        MIC::read_from_bytes(&self.data[start..]).map_err(|_| Error::MIC)
+    }
+    /// MIC of the first `total_len` bytes of the frame, MIC included
+    pub fn calculate_mic<C: Crypto>(
+        &self,
+        crypto: &mut C,
+        f_cnt: u32,
+        total_len: usize,
+    ) -> Result<MIC, Error> {
+        let size = Error::Size {
+            expected: total_len,
+            actual: self.as_bytes().len(),
+        };
+        let msg = self
+            .as_bytes()
+            .get(..total_len.checked_sub(4).ok_or(size)?)
+            .ok_or(size)?;
         let mut header = [0u8; 16];
         header[0] = 0x49;
         header[5] = MHDR::dir();
         header[6..10].copy_from_slice(self.dev_addr.as_bytes());
         header[10..14].copy_from_slice(&f_cnt.to_le_bytes());
-        header[15] = total_len as u8 - 4;
-        let mut mac = crypto.get_mac();
-        mac.calculate_mic(&[&header, &self.as_bytes()[..total_len - 4]])
+        header[15] = msg.len() as u8;
+        let mut mac = crypto.get_mac(Key::Network);
+        Ok(mac.calculate_mic(&[&header, msg]))
     }
     pub fn validate_mic<C: Crypto>(&self, crypto: &mut C, f_cnt: u32, total_len: usize) -> bool {
-        self.mic() == self.calculate_mic(crypto, f_cnt, total_len)
+        match (self.mic(), self.calculate_mic(crypto, f_cnt, total_len)) {
+            (Ok(mic), Ok(calculated)) => mic == calculated,
+            _ => false,
+        }
     }
-    pub fn encrypt<C: Crypto>(&mut self, crypto: &mut C, f_cnt: u32, payload_len: usize) {
+    /// Encrypts, or decrypts, the first `payload_len` bytes of FRMPayload after FPort
+    pub fn encrypt<C: Crypto>(
+        &mut self,
+        crypto: &mut C,
+        f_cnt: u32,
+        payload_len: usize,
+    ) -> Result<(), Error> {
         let mut block = [0u8; 16];
         block[0] = 0x01;
         block[5] = MHDR::dir(); //Dir
         block[6..10].copy_from_slice(self.dev_addr.as_bytes());
         block[10..14].copy_from_slice(&f_cnt.to_le_bytes());
-        let payload = self.frm_payload_mut();
+        let payload = self.frm_payload_mut()?;
         let key = match payload.f_port {
             0 => Key::Network,
             _ => Key::Application,
         };
+        let available = payload.data.len();
+        let data = payload.data.get_mut(..payload_len).ok_or(Error::Size {
+            expected: payload_len,
+            actual: available,
+        })?;
         let mut encrypter = crypto.get_encrypter(key);
         let mut ctr = 1;
-        for i in 0..payload_len {
+        for (i, byte) in data.iter_mut().enumerate() {
             let j = i & 0x0f;
             if j == 0 {
                 block[15] = ctr;
                 ctr += 1;
                 encrypter.encrypt_block(&mut block);
             }
-            payload.data[i] ^= block[j]
+            *byte ^= block[j]
         }
+        Ok(())
     }
 }
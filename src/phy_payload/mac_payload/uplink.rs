@@ -1,16 +1,22 @@
-use zerocopy::{Immutable, IntoBytes, KnownLayout, TryFromBytes, Unaligned};
+use bitfield_struct::bitfield;
+use zerocopy::{FromBytes, Immutable, IntoBytes, KnownLayout, TryFromBytes, Unaligned};
 
-use crate::crypto::Crypto;
+use crate::{
+    crypto::Crypto, device::DeviceConfig, frame_counter::FrameCounter, region::Region, Error,
+};
 
 use super::{FRMPayload, MacPayload, Mhdr};
 
 #[derive(IntoBytes, TryFromBytes, KnownLayout, Unaligned, Immutable)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[repr(u8)]
 pub enum UplinkHeader {
     Unconfirmed = 0b01000000,
     Confirmed = 0b10000000,
 }
 impl Mhdr for UplinkHeader {
+    type FCtrl = UplinkFCtrl;
+
     fn new(confirmed: bool) -> Self {
         match confirmed {
             true => Self::Confirmed,
@@ -23,31 +29,114 @@ impl Mhdr for UplinkHeader {
     }
 }
 
+#[bitfield(u8, defmt = cfg(feature = "defmt"))]
+#[derive(FromBytes, IntoBytes, KnownLayout, Immutable, Unaligned)]
+pub struct UplinkFCtrl {
+    #[bits(4)]
+    pub f_opts_len: usize,
+    /// The device is in class B
+    pub class_b: bool,
+    pub ack: bool,
+    pub adr_ack_req: bool,
+    pub adr: bool,
+}
+#[cfg(feature = "serde")]
+crate::types::bitfield_serde!(UplinkFCtrl {
+    adr: bool = set_adr_checked,
+    adr_ack_req: bool = set_adr_ack_req_checked,
+    ack: bool = set_ack_checked,
+    class_b: bool = set_class_b_checked,
+    f_opts_len: usize = set_f_opts_len_checked,
+});
+
 pub type Uplink = MacPayload<UplinkHeader>;
 
 impl Uplink {
-    pub fn build<C, F>(&mut self, f_cnt: u32, crypto: &mut C, c: F) -> &[u8]
+    pub fn build<C, F>(&mut self, f_cnt: u32, crypto: &mut C, c: F) -> Result<&[u8], Error>
+    where
+        C: Crypto,
+        F: FnOnce(&mut [u8]) -> Result<Option<&mut FRMPayload>, Error>,
+    {
+        let payload_len = self.write_payload(c)?;
+        self.finish(f_cnt, crypto, payload_len)
+    }
+    /// Like [`Self::build`], failing with [`Error::MaxPayloadSize`] if the MACPayload is larger
+    /// than the regional maximum for the data rate and dwell time of `config`
+    pub fn build_checked<R, C, F>(
+        &mut self,
+        f_cnt: u32,
+        crypto: &mut C,
+        region: &R,
+        config: &DeviceConfig,
+        c: F,
+    ) -> Result<&[u8], Error>
     where
+        R: Region,
         C: Crypto,
-        F: FnOnce(&mut [u8]) -> Option<&mut FRMPayload>,
+        F: FnOnce(&mut [u8]) -> Result<Option<&mut FRMPayload>, Error>,
+    {
+        let payload_len = self.write_payload(c)?;
+        //fhdr + f_opts + payload
+        let mac_payload_len = 7 + self.f_ctrl.f_opts_len() + payload_len;
+        match region.max_mac_payload_size(config, config.data_rate) {
+            Some(max) if mac_payload_len <= max => self.finish(f_cnt, crypto, payload_len),
+            max => Err(Error::MaxPayloadSize {
+                max: max.unwrap_or_default(),
+                actual: mac_payload_len,
+            }),
+        }
+    }
+    fn write_payload<F>(&mut self, c: F) -> Result<usize, Error>
+    where
+        F: FnOnce(&mut [u8]) -> Result<Option<&mut FRMPayload>, Error>,
     {
         let f_opts_len = self.f_ctrl.f_opts_len();
         let data_len = self.data.len();
         //mhdr + f_opts - MIC
-        let payload_len = if let Some(payload) = c(&mut self.data[f_opts_len..data_len - 4]) {
-            1 + payload.data.len()
-        } else {
-            0
-        };
-        self.encrypt(crypto, f_cnt, payload_len);
+        let buf = data_len
+            .checked_sub(4)
+            .and_then(|end| self.data.get_mut(f_opts_len..end))
+            .ok_or(Error::FOptsLen {
+                f_opts_len,
+                available: data_len.saturating_sub(4),
+            })?;
+        Ok(c(buf)?.map_or(0, |payload| 1 + payload.data.len()))
+    }
+    fn finish<C: Crypto>(
+        &mut self,
+        f_cnt: u32,
+        crypto: &mut C,
+        payload_len: usize,
+    ) -> Result<&[u8], Error> {
+        let f_opts_len = self.f_ctrl.f_opts_len();
+        if payload_len > 0 {
+            // FPort is not encrypted
+            self.encrypt(crypto, f_cnt, payload_len - 1)?;
+        }
         let total_len = 8 + f_opts_len + payload_len + 4;
 
-        let mic = self.calculate_mic(crypto, f_cnt, total_len);
+        let mic = self.calculate_mic(crypto, f_cnt, total_len)?;
         self.data[f_opts_len + payload_len..f_opts_len + payload_len + 4]
             .copy_from_slice(mic.as_bytes());
 
         //mhdr + fhdr + f_opts + payload + mic
-        &self.as_bytes()[..total_len]
+        Ok(&self.as_bytes()[..total_len])
+    }
+    /// Like [`Self::build`], taking the frame counter from `f_cnt_up` and writing its 16 least
+    /// significant bits to the FCnt field
+    pub fn build_with_counter<C, F>(
+        &mut self,
+        f_cnt_up: &mut FrameCounter,
+        crypto: &mut C,
+        c: F,
+    ) -> Result<&[u8], Error>
+    where
+        C: Crypto,
+        F: FnOnce(&mut [u8]) -> Result<Option<&mut FRMPayload>, Error>,
+    {
+        let f_cnt = f_cnt_up.increment()?;
+        self.f_cnt = (f_cnt as u16).into();
+        self.build(f_cnt, crypto, c)
     }
 }
 #[cfg(test)]
@@ -60,6 +149,7 @@ mod tests {
             encode_maccommands, LinkADRAns, LinkAdrAnsStatus, UplinkMacCommmand,
         },
         phy_payload::mac_payload::FHDR,
+        region::{as923::As923, eu868::Eu868},
         types::{AppSKey, DevAddr, NwkSKey},
     };
     fn get_crypto() -> SoftCrypto {
@@ -81,10 +171,12 @@ mod tests {
             &[],
         )
         .unwrap();
-        let uplink = Uplink::new(&mut buf, true, fhdr);
-        let bytes = uplink.build(0, &mut get_crypto(), |buf| {
-            Some(FRMPayload::new_from_slice(buf, 7, &payload))
-        });
+        let uplink = Uplink::new(&mut buf, true, fhdr).unwrap();
+        let bytes = uplink
+            .build(0, &mut get_crypto(), |buf| {
+                FRMPayload::new_from_slice(buf, 7, &payload).map(Some)
+            })
+            .unwrap();
         assert_eq!(
             bytes,
             &[128, 0, 1, 2, 3, 128, 6, 5, 7, 255, 123, 34, 224, 206, 195]
@@ -102,11 +194,31 @@ mod tests {
             &[],
         )
         .unwrap();
-        let uplink = Uplink::new(&mut buf, true, fhdr);
-        let bytes = uplink.build(0, &mut get_crypto(), |_buf| None);
+        let uplink = Uplink::new(&mut buf, true, fhdr).unwrap();
+        let bytes = uplink.build(0, &mut get_crypto(), |_buf| Ok(None)).unwrap();
         assert_eq!(bytes, &[128, 0, 1, 2, 3, 128, 6, 5, 75, 31, 216, 35])
     }
     #[test]
+    fn uplink_f_ctrl() {
+        let mut buf = [0u8; 256];
+        let fhdr = FHDR::new(
+            DevAddr::read_from_bytes(&[0, 1, 2, 3]).unwrap(),
+            true,
+            false,
+            true,
+            0x0506,
+            &[],
+        )
+        .unwrap()
+        .with_adr_ack_req(true);
+        let uplink = Uplink::new(&mut buf, false, fhdr).unwrap();
+        let bytes = uplink.build(0, &mut get_crypto(), |_buf| Ok(None)).unwrap();
+        assert_eq!(bytes[5], 0xd0);
+        let f_ctrl = Uplink::try_ref_from_bytes(bytes).unwrap().f_ctrl();
+        assert!(f_ctrl.adr() && f_ctrl.adr_ack_req() && f_ctrl.class_b());
+        assert!(!f_ctrl.ack());
+    }
+    #[test]
     fn encode_uplink_with_fopts() {
         let mut buf = [0u8; 256];
         let cmds = [
@@ -127,9 +239,9 @@ mod tests {
             encode_maccommands(&cmds, &mut f_opts_buf).unwrap(),
         )
         .unwrap();
-        let uplink = Uplink::new(&mut buf, false, fhdr);
+        let uplink = Uplink::new(&mut buf, false, fhdr).unwrap();
         assert_eq!(
-            uplink.build(0, &mut get_crypto(), |_| { None }),
+            uplink.build(0, &mut get_crypto(), |_| Ok(None)).unwrap(),
             &[
                 0x40, 0x04, 0x03, 0x02, 0x01, 0x03, 0x00, 0x00, 0x02, 0x03, 0x05, 0xd7, 0xfa, 0x0c,
                 0x6c
@@ -156,10 +268,12 @@ mod tests {
             &[],
         )
         .unwrap();
-        let uplink = Uplink::new(&mut buf, false, fhdr);
-        let bytes = uplink.build(0, &mut get_crypto(), |buf| {
-            Some(FRMPayload::new_from_maccommands(buf, &cmds))
-        });
+        let uplink = Uplink::new(&mut buf, false, fhdr).unwrap();
+        let bytes = uplink
+            .build(0, &mut get_crypto(), |buf| {
+                FRMPayload::new_from_maccommands(buf, &cmds).map(Some)
+            })
+            .unwrap();
         assert_eq!(
             bytes,
             &[
@@ -169,6 +283,85 @@ mod tests {
         )
     }
 
+    #[test]
+    fn encode_uplink_with_counter() {
+        let mut buf = [0u8; 256];
+        let payload: [u8; 2] = [0x08, 0x09];
+        let fhdr = FHDR::new(
+            DevAddr::read_from_bytes(&[0, 1, 2, 3]).unwrap(),
+            true,
+            false,
+            false,
+            0,
+            &[],
+        )
+        .unwrap();
+        let mut f_cnt_up = FrameCounter::new(0x10506);
+        let uplink = Uplink::new(&mut buf, true, fhdr).unwrap();
+        let bytes = uplink
+            .build_with_counter(&mut f_cnt_up, &mut get_crypto(), |buf| {
+                FRMPayload::new_from_slice(buf, 7, &payload).map(Some)
+            })
+            .unwrap();
+        assert_eq!(
+            bytes,
+            &[128, 0, 1, 2, 3, 128, 6, 5, 7, 0x71, 0x46, 0xca, 0x40, 0x6c, 0x1c]
+        );
+        assert_eq!(f_cnt_up.next(), 0x10507);
+    }
+    #[test]
+    fn enforce_max_payload_size() {
+        let fhdr = || {
+            FHDR::new(
+                DevAddr::read_from_bytes(&[0, 1, 2, 3]).unwrap(),
+                true,
+                false,
+                false,
+                0,
+                &[],
+            )
+            .unwrap()
+        };
+        let payload = [0u8; 52];
+        let region = Eu868;
+        let config = region.default_config();
+        // DR0 allows 59 bytes of MACPayload, i.e. 51 bytes of FRMPayload without FOpts
+        let mut buf = [0u8; 256];
+        let uplink = Uplink::new(&mut buf, false, fhdr()).unwrap();
+        let bytes = uplink
+            .build_checked(0, &mut get_crypto(), &region, &config, |buf| {
+                FRMPayload::new_from_slice(buf, 1, &payload[..51]).map(Some)
+            })
+            .unwrap();
+        assert_eq!(bytes.len(), 1 + 59 + 4);
+        let uplink = Uplink::new(&mut buf, false, fhdr()).unwrap();
+        assert!(matches!(
+            uplink.build_checked(0, &mut get_crypto(), &region, &config, |buf| {
+                FRMPayload::new_from_slice(buf, 1, &payload).map(Some)
+            }),
+            Err(Error::MaxPayloadSize {
+                max: 59,
+                actual: 60
+            })
+        ));
+
+        // AS923 starts with a dwell time limit, DR0 cannot be used at all
+        let region = As923::As923_1;
+        let mut config = region.default_config();
+        let uplink = Uplink::new(&mut buf, false, fhdr()).unwrap();
+        assert!(matches!(
+            uplink.build_checked(0, &mut get_crypto(), &region, &config, |_| Ok(None)),
+            Err(Error::MaxPayloadSize { max: 0, actual: 7 })
+        ));
+        config.data_rate = 2;
+        let uplink = Uplink::new(&mut buf, false, fhdr()).unwrap();
+        assert!(uplink
+            .build_checked(0, &mut get_crypto(), &region, &config, |buf| {
+                FRMPayload::new_from_slice(buf, 1, &payload[..11]).map(Some)
+            })
+            .is_ok());
+    }
+
     // #[test]
     // fn encode_uplink_with_mac_commands_in_payload() {
     //     let mut buf = [0u8; 255];
@@ -181,7 +374,7 @@ mod tests {
     //         &[],
     //     )
     //     .unwrap();
-    //     let uplink = Uplink::new(&mut buf, true, fhdr);
+    //     let uplink = Uplink::new(&mut buf, true, fhdr).unwrap();
     //     let bytes = uplink.build(|_buf| FRMPayload);
     //     assert_eq!(bytes, &[128, 0, 1, 2, 3, 128, 5, 6, 0, 0, 0, 0])
     // }
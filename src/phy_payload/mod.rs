@@ -1,5 +1,7 @@
 pub mod join_accept;
 pub mod join_request;
+#[cfg(feature = "serde")]
+pub mod json;
 pub mod mac_payload;
 
 // DOWNLINK bytes -> encrypted<downlink> -> downlink
@@ -0,0 +1,252 @@
+//! AS923-1 to AS923-4, sharing the same parameters with their frequencies shifted
+use crate::{
+    device::{
+        channel_plan::{ChannelPlan, DynamicChannelPlan},
+        Channel, ChannelMask, DeviceConfig, Limits,
+    },
+    phy_payload::join_accept::CFList,
+};
+
+use super::{dynamic::DynamicRegion, Bandwidth, DataRate, Region, SubBand, MAX_EIRP};
+
+/// Default MaxEIRP index, 16 dBm
+pub const DEFAULT_MAX_EIRP: u8 = 5;
+pub const RX2_DATA_RATE: u8 = 2;
+
+pub const DATA_RATES: [Option<DataRate>; 8] = [
+    Some(DataRate::lora(12, Bandwidth::Khz125, 250)),
+    Some(DataRate::lora(11, Bandwidth::Khz125, 440)),
+    Some(DataRate::lora(10, Bandwidth::Khz125, 980)),
+    Some(DataRate::lora(9, Bandwidth::Khz125, 1760)),
+    Some(DataRate::lora(8, Bandwidth::Khz125, 3125)),
+    Some(DataRate::lora(7, Bandwidth::Khz125, 5470)),
+    Some(DataRate::lora(7, Bandwidth::Khz250, 11000)),
+    Some(DataRate::fsk(50000)),
+];
+
+/// Maximum MACPayload size per data rate without dwell time limit, repeater compatible
+pub const MAX_MAC_PAYLOAD_SIZES: [Option<usize>; 8] = [
+    Some(59),
+    Some(59),
+    Some(123),
+    Some(123),
+    Some(230),
+    Some(230),
+    Some(230),
+    Some(230),
+];
+
+/// Maximum MACPayload size per data rate with an uplink dwell time of 400 ms
+pub const DWELL_TIME_MAX_MAC_PAYLOAD_SIZES: [Option<usize>; 8] = [
+    None,
+    None,
+    Some(19),
+    Some(61),
+    Some(133),
+    Some(230),
+    Some(230),
+    Some(230),
+];
+
+pub const SUB_BANDS: [SubBand; 1] = [SubBand {
+    min_frequency: 915_000_000,
+    max_frequency: 928_000_000,
+    duty_cycle: 1,
+}];
+
+const fn default_channels(frequency_offset: i32) -> [Channel; 2] {
+    [
+        Channel::new(923_200_000u32.wrapping_add_signed(frequency_offset), 0, 5),
+        Channel::new(923_400_000u32.wrapping_add_signed(frequency_offset), 0, 5),
+    ]
+}
+
+const fn region(default_channels: &'static [Channel], frequency_offset: i32) -> DynamicRegion {
+    DynamicRegion {
+        limits: Limits {
+            min_frequency: 915_000_000,
+            max_frequency: 928_000_000,
+            max_data_rate: 7,
+            max_dl_data_rate: 7,
+            max_tx_power: 7,
+            max_rx1_dr_offset: 7,
+            default_channels: 2,
+            tx_param_setup: true,
+        },
+        data_rates: DATA_RATES,
+        max_mac_payload_sizes: MAX_MAC_PAYLOAD_SIZES,
+        dwell_time_max_mac_payload_sizes: DWELL_TIME_MAX_MAC_PAYLOAD_SIZES,
+        max_eirp: MAX_EIRP[DEFAULT_MAX_EIRP as usize] as i8,
+        default_channels,
+        rx2_frequency: 923_200_000u32.wrapping_add_signed(frequency_offset),
+        rx2_data_rate: RX2_DATA_RATE,
+        join_data_rate: 2,
+        sub_bands: &SUB_BANDS,
+    }
+}
+
+const AS923_1_CHANNELS: [Channel; 2] = default_channels(As923::As923_1.frequency_offset());
+const AS923_2_CHANNELS: [Channel; 2] = default_channels(As923::As923_2.frequency_offset());
+const AS923_3_CHANNELS: [Channel; 2] = default_channels(As923::As923_3.frequency_offset());
+const AS923_4_CHANNELS: [Channel; 2] = default_channels(As923::As923_4.frequency_offset());
+
+const REGIONS: [DynamicRegion; 4] = [
+    region(&AS923_1_CHANNELS, As923::As923_1.frequency_offset()),
+    region(&AS923_2_CHANNELS, As923::As923_2.frequency_offset()),
+    region(&AS923_3_CHANNELS, As923::As923_3.frequency_offset()),
+    region(&AS923_4_CHANNELS, As923::As923_4.frequency_offset()),
+];
+
+/// The device starts with dwell time limits, until a TxParamSetupReq lifts them
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum As923 {
+    As923_1,
+    As923_2,
+    As923_3,
+    As923_4,
+}
+impl As923 {
+    /// Offset in Hz of the frequencies from AS923-1
+    pub const fn frequency_offset(&self) -> i32 {
+        match self {
+            As923::As923_1 => 0,
+            As923::As923_2 => -1_800_000,
+            As923::As923_3 => -6_600_000,
+            As923::As923_4 => -5_900_000,
+        }
+    }
+    fn region(&self) -> &'static DynamicRegion {
+        &REGIONS[*self as usize]
+    }
+}
+
+impl ChannelPlan for As923 {
+    fn channel(&self, config: &DeviceConfig, index: usize) -> Option<Channel> {
+        DynamicChannelPlan.channel(config, index)
+    }
+    fn apply_ch_mask(
+        &self,
+        config: &DeviceConfig,
+        mask: &mut ChannelMask,
+        ch_mask_cntl: u8,
+        ch_mask: u16,
+    ) -> bool {
+        DynamicChannelPlan.apply_ch_mask(config, mask, ch_mask_cntl, ch_mask)
+    }
+}
+
+impl Region for As923 {
+    fn limits(&self) -> Limits {
+        self.region().limits
+    }
+    fn data_rate(&self, data_rate: u8) -> Option<DataRate> {
+        self.region().data_rate(data_rate)
+    }
+    fn max_mac_payload_size(&self, config: &DeviceConfig, data_rate: u8) -> Option<usize> {
+        self.region().max_mac_payload_size(config, data_rate)
+    }
+    /// Relative to the MaxEIRP of the last TxParamSetupReq
+    fn tx_power(&self, config: &DeviceConfig, tx_power: u8) -> Option<i8> {
+        let max_eirp = MAX_EIRP[config.max_eirp as usize & 0x0f] as i8;
+        (tx_power <= 7).then(|| max_eirp - 2 * tx_power as i8)
+    }
+    /// RX1DROffset 6 and 7 increase the data rate by 1 and 2, which is at least DR2 with
+    /// downlink dwell time limits
+    fn rx1_data_rate(&self, config: &DeviceConfig, data_rate: u8, rx1_dr_offset: u8) -> Option<u8> {
+        if data_rate > 7 || rx1_dr_offset > 7 {
+            return None;
+        }
+        let min_data_rate = if config.downlink_dwell_time { 2 } else { 0 };
+        let data_rate = match rx1_dr_offset {
+            6 => data_rate + 1,
+            7 => data_rate + 2,
+            offset => data_rate.saturating_sub(offset),
+        };
+        Some(data_rate.clamp(min_data_rate, 5))
+    }
+    fn rx2_frequency(&self) -> u32 {
+        self.region().rx2_frequency
+    }
+    fn rx2_data_rate(&self) -> u8 {
+        self.region().rx2_data_rate
+    }
+    fn default_channels(&self) -> &[Channel] {
+        self.region().default_channels
+    }
+    fn join_channels(&self) -> ChannelMask {
+        ChannelMask::all(self.region().default_channels.len())
+    }
+    fn join_channel(&self, _attempt: u32, random: u32) -> (usize, u8) {
+        self.region().join_channel(random)
+    }
+    fn sub_bands(&self) -> &[SubBand] {
+        self.region().sub_bands
+    }
+    fn apply_cf_list(&self, config: &mut DeviceConfig, cf_list: &CFList) {
+        self.region().apply_cf_list(config, cf_list)
+    }
+    fn default_config(&self) -> DeviceConfig {
+        let region = self.region();
+        let mut config = DeviceConfig::new(region.limits, RX2_DATA_RATE, region.rx2_frequency);
+        for (index, channel) in region.default_channels.iter().enumerate() {
+            config.set_channel(index, Some(*channel));
+        }
+        config.max_eirp = DEFAULT_MAX_EIRP;
+        config.uplink_dwell_time = true;
+        config.downlink_dwell_time = true;
+        config
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        device::processor::{DevStatus, MacProcessor},
+        mac_command::downlink::DownlinkMacCommandDecoder,
+    };
+
+    use super::*;
+    #[test]
+    fn frequency_offsets() {
+        let region = As923::As923_3;
+        let config = region.default_config();
+        assert_eq!(region.rx2_frequency(), 916_600_000);
+        assert_eq!(
+            region.channel(&config, 1),
+            Some(Channel::new(916_800_000, 0, 5))
+        );
+        assert_eq!(region.channel(&config, 2), None);
+        assert_eq!(As923::As923_2.default_channels()[0].frequency, 921_400_000);
+        assert_eq!(As923::As923_4.default_channels()[1].frequency, 917_500_000);
+    }
+    #[test]
+    fn dwell_time() {
+        let region = As923::As923_1;
+        let mut config = region.default_config();
+        assert_eq!(region.max_mac_payload_size(&config, 1), None);
+        assert_eq!(region.max_mac_payload_size(&config, 2), Some(19));
+        assert_eq!(region.rx1_data_rate(&config, 2, 2), Some(2));
+        assert_eq!(region.rx1_data_rate(&config, 4, 7), Some(5));
+        assert_eq!(region.tx_power(&config, 1), Some(14));
+
+        // TxParamSetupReq without dwell time limits and a MaxEIRP of 20 dBm
+        let cmds = [0x09, 0x07];
+        let mut processor = MacProcessor::new();
+        let dev_status = DevStatus {
+            battery: 0,
+            margin: 0,
+        };
+        processor
+            .process(
+                &region,
+                &mut config,
+                DownlinkMacCommandDecoder::new(&cmds),
+                dev_status,
+            )
+            .unwrap();
+        assert_eq!(region.max_mac_payload_size(&config, 1), Some(59));
+        assert_eq!(region.rx1_data_rate(&config, 2, 2), Some(0));
+        assert_eq!(region.tx_power(&config, 1), Some(18));
+    }
+}
@@ -0,0 +1,214 @@
+//! AU915-928
+use crate::{
+    device::{
+        channel_plan::{ChannelPlan, FixedChannelPlan},
+        Channel, ChannelMask, DeviceConfig, Limits,
+    },
+    phy_payload::join_accept::CFList,
+};
+
+use super::{
+    fixed::{FixedRegion, MAX_TX_POWER},
+    Bandwidth, DataRate, Region, SubBand,
+};
+
+pub const RX2_FREQUENCY: u32 = 923_300_000;
+pub const RX2_DATA_RATE: u8 = 8;
+
+const REGION: FixedRegion = FixedRegion {
+    plan: FixedChannelPlan {
+        first_frequency_125: 915_200_000,
+        min_dr_125: 0,
+        max_dr_125: 5,
+        first_frequency_500: 915_900_000,
+        dr_500: 6,
+    },
+    limits: Limits {
+        min_frequency: 915_000_000,
+        max_frequency: 928_000_000,
+        max_data_rate: 6,
+        max_dl_data_rate: 13,
+        max_tx_power: MAX_TX_POWER,
+        max_rx1_dr_offset: 5,
+        default_channels: 72,
+        tx_param_setup: true,
+    },
+    data_rates: [
+        Some(DataRate::lora(12, Bandwidth::Khz125, 250)),
+        Some(DataRate::lora(11, Bandwidth::Khz125, 440)),
+        Some(DataRate::lora(10, Bandwidth::Khz125, 980)),
+        Some(DataRate::lora(9, Bandwidth::Khz125, 1760)),
+        Some(DataRate::lora(8, Bandwidth::Khz125, 3125)),
+        Some(DataRate::lora(7, Bandwidth::Khz125, 5470)),
+        Some(DataRate::lora(8, Bandwidth::Khz500, 12500)),
+        None,
+        Some(DataRate::lora(12, Bandwidth::Khz500, 980)),
+        Some(DataRate::lora(11, Bandwidth::Khz500, 1760)),
+        Some(DataRate::lora(10, Bandwidth::Khz500, 3900)),
+        Some(DataRate::lora(9, Bandwidth::Khz500, 7000)),
+        Some(DataRate::lora(8, Bandwidth::Khz500, 12500)),
+        Some(DataRate::lora(7, Bandwidth::Khz500, 21900)),
+    ],
+    max_mac_payload_sizes: [
+        Some(59),
+        Some(59),
+        Some(59),
+        Some(123),
+        Some(230),
+        Some(230),
+        Some(230),
+        None,
+        Some(41),
+        Some(117),
+        Some(230),
+        Some(230),
+        Some(230),
+        Some(230),
+    ],
+    dwell_time_max_mac_payload_sizes: [
+        None,
+        None,
+        Some(19),
+        Some(61),
+        Some(133),
+        Some(230),
+        Some(230),
+        None,
+        Some(41),
+        Some(117),
+        Some(230),
+        Some(230),
+        Some(230),
+        Some(230),
+    ],
+    rx1_data_rate: 8,
+    first_dl_frequency: RX2_FREQUENCY,
+    rx2_data_rate: RX2_DATA_RATE,
+    join_data_rate_125: 2,
+};
+
+pub const SUB_BANDS: [SubBand; 1] = [SubBand {
+    min_frequency: 915_000_000,
+    max_frequency: 928_000_000,
+    duty_cycle: 1,
+}];
+
+/// AU915-928, using the sub-bands set in `sub_bands`: bit `n` enables the 125 kHz channels
+/// `8 * n` to `8 * n + 7` and the 500 kHz channel `64 + n`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Au915 {
+    pub sub_bands: u8,
+}
+impl Au915 {
+    pub fn new(sub_bands: u8) -> Self {
+        Self { sub_bands }
+    }
+}
+impl Default for Au915 {
+    /// All sub-bands
+    fn default() -> Self {
+        Self::new(0xff)
+    }
+}
+
+impl ChannelPlan for Au915 {
+    fn channel(&self, config: &DeviceConfig, index: usize) -> Option<Channel> {
+        REGION.plan.channel(config, index)
+    }
+    fn apply_ch_mask(
+        &self,
+        config: &DeviceConfig,
+        mask: &mut ChannelMask,
+        ch_mask_cntl: u8,
+        ch_mask: u16,
+    ) -> bool {
+        REGION
+            .plan
+            .apply_ch_mask(config, mask, ch_mask_cntl, ch_mask)
+    }
+    fn supports_new_channel(&self) -> bool {
+        REGION.plan.supports_new_channel()
+    }
+}
+
+impl Region for Au915 {
+    fn limits(&self) -> Limits {
+        REGION.limits
+    }
+    fn data_rate(&self, data_rate: u8) -> Option<DataRate> {
+        REGION.data_rate(data_rate)
+    }
+    fn max_mac_payload_size(&self, config: &DeviceConfig, data_rate: u8) -> Option<usize> {
+        REGION.max_mac_payload_size(config, data_rate)
+    }
+    fn tx_power(&self, _config: &DeviceConfig, tx_power: u8) -> Option<i8> {
+        REGION.tx_power(tx_power)
+    }
+    fn rx1_data_rate(
+        &self,
+        _config: &DeviceConfig,
+        data_rate: u8,
+        rx1_dr_offset: u8,
+    ) -> Option<u8> {
+        REGION.rx1_data_rate(data_rate, rx1_dr_offset)
+    }
+    fn rx2_frequency(&self) -> u32 {
+        RX2_FREQUENCY
+    }
+    fn rx2_data_rate(&self) -> u8 {
+        RX2_DATA_RATE
+    }
+    fn default_channels(&self) -> &[Channel] {
+        &[]
+    }
+    fn join_channels(&self) -> ChannelMask {
+        FixedChannelPlan::sub_band_mask(self.sub_bands)
+    }
+    fn join_channel(&self, attempt: u32, random: u32) -> (usize, u8) {
+        REGION.join_channel(self.sub_bands, attempt, random)
+    }
+    fn sub_bands(&self) -> &[SubBand] {
+        &SUB_BANDS
+    }
+    fn apply_cf_list(&self, config: &mut DeviceConfig, cf_list: &CFList) {
+        REGION.apply_cf_list(config, cf_list)
+    }
+    fn rx1_frequency(&self, _config: &DeviceConfig, channel: usize) -> Option<u32> {
+        REGION.rx1_frequency(channel)
+    }
+    fn default_config(&self) -> DeviceConfig {
+        REGION.default_config(self.sub_bands)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn au915() {
+        let region = Au915::new(0x01);
+        let config = region.default_config();
+        assert_eq!(
+            config.channel_mask,
+            ChannelMask::new([0x00ff, 0, 0, 0, 0x0001])
+        );
+        assert_eq!(
+            region.channel(&config, 64),
+            Some(Channel::new(915_900_000, 6, 6))
+        );
+        assert_eq!(region.rx1_frequency(&config, 10), Some(924_500_000));
+        assert_eq!(region.join_channel(0, 3), (3, 2));
+        assert_eq!(region.join_channel(1, 3), (64, 6));
+
+        assert_eq!(region.rx1_data_rate(&config, 0, 0), Some(8));
+        assert_eq!(region.rx1_data_rate(&config, 6, 0), Some(13));
+        assert_eq!(region.rx1_data_rate(&config, 6, 5), Some(9));
+        let mut config = config;
+        assert_eq!(region.max_mac_payload_size(&config, 2), Some(59));
+        config.uplink_dwell_time = true;
+        assert_eq!(region.max_mac_payload_size(&config, 1), None);
+        assert_eq!(region.max_mac_payload_size(&config, 2), Some(19));
+        assert_eq!(region.data_rate(7), None);
+    }
+}
@@ -0,0 +1,245 @@
+//! CN470-510, with the channel plan types introduced in RP002-1.0.3
+use crate::{
+    device::{channel_plan::ChannelPlan, Channel, ChannelMask, DeviceConfig, Limits},
+    phy_payload::join_accept::CFList,
+};
+
+use super::{Bandwidth, DataRate, Region, SubBand};
+
+/// Default maximum EIRP in dBm, rounded down from 19.15 dBm
+pub const MAX_EIRP: i8 = 19;
+pub const RX2_DATA_RATE: u8 = 1;
+pub const JOIN_DATA_RATE: u8 = 1;
+
+pub const DATA_RATES: [Option<DataRate>; 8] = [
+    Some(DataRate::lora(12, Bandwidth::Khz125, 250)),
+    Some(DataRate::lora(11, Bandwidth::Khz125, 440)),
+    Some(DataRate::lora(10, Bandwidth::Khz125, 980)),
+    Some(DataRate::lora(9, Bandwidth::Khz125, 1760)),
+    Some(DataRate::lora(8, Bandwidth::Khz125, 3125)),
+    Some(DataRate::lora(7, Bandwidth::Khz125, 5470)),
+    Some(DataRate::lora(7, Bandwidth::Khz500, 21900)),
+    Some(DataRate::fsk(50000)),
+];
+
+/// Maximum MACPayload size per data rate, repeater compatible. DR0 is not used for uplinks.
+pub const MAX_MAC_PAYLOAD_SIZES: [Option<usize>; 8] = [
+    None,
+    Some(31),
+    Some(94),
+    Some(192),
+    Some(230),
+    Some(230),
+    Some(230),
+    Some(230),
+];
+
+pub const SUB_BANDS: [SubBand; 1] = [SubBand {
+    min_frequency: 470_000_000,
+    max_frequency: 510_000_000,
+    duty_cycle: 1,
+}];
+
+const LIMITS: Limits = Limits {
+    min_frequency: 470_000_000,
+    max_frequency: 510_000_000,
+    max_data_rate: 7,
+    max_dl_data_rate: 7,
+    max_tx_power: 7,
+    max_rx1_dr_offset: 5,
+    default_channels: 64,
+    tx_param_setup: false,
+};
+
+/// Channel plan type, depending on the antenna of the device
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Cn470Plan {
+    /// 20 MHz antenna, plan A: 64 uplink channels in 470.3-476.5 and 503.5-509.7 MHz, RX1 on
+    /// the downlink channel of the same index in 483.9-496.5 MHz
+    A20,
+    /// 20 MHz antenna, plan B: 64 channels in 476.9-483.1 and 496.9-503.1 MHz, RX1 on the
+    /// uplink frequency
+    B20,
+    /// 26 MHz antenna, plan A: 48 uplink channels in 470.3-479.7 MHz, 24 downlink channels in
+    /// 490.1-494.7 MHz
+    A26,
+    /// 26 MHz antenna, plan B: 48 uplink channels in 480.3-489.7 MHz, 24 downlink channels in
+    /// 500.1-504.7 MHz
+    B26,
+}
+impl Cn470Plan {
+    pub fn channels(&self) -> usize {
+        match self {
+            Cn470Plan::A20 | Cn470Plan::B20 => 64,
+            Cn470Plan::A26 | Cn470Plan::B26 => 48,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Cn470 {
+    pub plan: Cn470Plan,
+}
+impl Cn470 {
+    pub fn new(plan: Cn470Plan) -> Self {
+        Self { plan }
+    }
+    fn frequency(&self, index: usize) -> Option<u32> {
+        let (first, second) = match self.plan {
+            Cn470Plan::A20 => (470_300_000, 503_500_000),
+            Cn470Plan::B20 => (476_900_000, 496_900_000),
+            Cn470Plan::A26 => (470_300_000, 0),
+            Cn470Plan::B26 => (480_300_000, 0),
+        };
+        match index {
+            _ if index >= self.plan.channels() => None,
+            0..32 => Some(first + 200_000 * index as u32),
+            _ if second != 0 => Some(second + 200_000 * (index - 32) as u32),
+            _ => Some(first + 200_000 * index as u32),
+        }
+    }
+    /// RX2 frequency in Hz after joining on `channel`. With the 20 MHz plans, it depends on
+    /// the group of 32 channels used.
+    pub fn join_rx2_frequency(&self, channel: usize) -> u32 {
+        match (self.plan, channel < 32) {
+            (Cn470Plan::A20, true) => 485_300_000,
+            (Cn470Plan::A20, false) => 486_900_000,
+            (Cn470Plan::B20, true) => 478_300_000,
+            (Cn470Plan::B20, false) => 498_300_000,
+            (Cn470Plan::A26, _) => 492_500_000,
+            (Cn470Plan::B26, _) => 502_500_000,
+        }
+    }
+}
+
+impl ChannelPlan for Cn470 {
+    fn supports_new_channel(&self) -> bool {
+        false
+    }
+    fn channel(&self, _config: &DeviceConfig, index: usize) -> Option<Channel> {
+        self.frequency(index)
+            .map(|frequency| Channel::new(frequency, 1, 5))
+    }
+    /// ChMaskCntl 0 to 3, respectively 0 to 2, apply ChMask to 16 channels and 6 enables all
+    /// channels
+    fn apply_ch_mask(
+        &self,
+        _config: &DeviceConfig,
+        mask: &mut ChannelMask,
+        ch_mask_cntl: u8,
+        ch_mask: u16,
+    ) -> bool {
+        match ch_mask_cntl as usize {
+            bank if bank < self.plan.channels() / 16 => mask.set_bank(bank, ch_mask),
+            6 => *mask = ChannelMask::all(self.plan.channels()),
+            _ => return false,
+        }
+        true
+    }
+}
+
+impl Region for Cn470 {
+    fn limits(&self) -> Limits {
+        Limits {
+            default_channels: self.plan.channels(),
+            ..LIMITS
+        }
+    }
+    fn data_rate(&self, data_rate: u8) -> Option<DataRate> {
+        DATA_RATES.get(data_rate as usize).copied().flatten()
+    }
+    fn max_mac_payload_size(&self, _config: &DeviceConfig, data_rate: u8) -> Option<usize> {
+        MAX_MAC_PAYLOAD_SIZES
+            .get(data_rate as usize)
+            .copied()
+            .flatten()
+    }
+    fn tx_power(&self, _config: &DeviceConfig, tx_power: u8) -> Option<i8> {
+        (tx_power <= LIMITS.max_tx_power).then(|| MAX_EIRP - 2 * tx_power as i8)
+    }
+    fn rx1_data_rate(
+        &self,
+        _config: &DeviceConfig,
+        data_rate: u8,
+        rx1_dr_offset: u8,
+    ) -> Option<u8> {
+        (data_rate <= LIMITS.max_data_rate && rx1_dr_offset <= LIMITS.max_rx1_dr_offset)
+            .then(|| data_rate.saturating_sub(rx1_dr_offset))
+    }
+    /// RX2 frequency after joining on the first group of channels, see
+    /// [`Cn470::join_rx2_frequency`]
+    fn rx2_frequency(&self) -> u32 {
+        self.join_rx2_frequency(0)
+    }
+    fn rx2_data_rate(&self) -> u8 {
+        RX2_DATA_RATE
+    }
+    fn default_channels(&self) -> &[Channel] {
+        &[]
+    }
+    fn join_channels(&self) -> ChannelMask {
+        ChannelMask::all(self.plan.channels())
+    }
+    fn join_channel(&self, _attempt: u32, random: u32) -> (usize, u8) {
+        (random as usize % self.plan.channels(), JOIN_DATA_RATE)
+    }
+    fn sub_bands(&self) -> &[SubBand] {
+        &SUB_BANDS
+    }
+    fn apply_cf_list(&self, config: &mut DeviceConfig, cf_list: &CFList) {
+        if let CFList::ChannelMasks(masks) = cf_list {
+            let mut channel_mask = ChannelMask::default();
+            for (bank, mask) in masks.iter().enumerate().take(self.plan.channels() / 16) {
+                channel_mask.set_bank(bank, mask.get());
+            }
+            if !channel_mask.is_empty() {
+                config.channel_mask = channel_mask;
+            }
+        }
+    }
+    fn rx1_frequency(&self, config: &DeviceConfig, channel: usize) -> Option<u32> {
+        let (first, channels) = match self.plan {
+            Cn470Plan::A20 => (483_900_000, 64),
+            Cn470Plan::B20 => return self.channel(config, channel).map(|c| c.frequency),
+            Cn470Plan::A26 => (490_100_000, 24),
+            Cn470Plan::B26 => (500_100_000, 24),
+        };
+        (channel < self.plan.channels()).then(|| first + 200_000 * (channel % channels) as u32)
+    }
+    fn default_config(&self) -> DeviceConfig {
+        let mut config = DeviceConfig::new(self.limits(), RX2_DATA_RATE, self.rx2_frequency());
+        config.channel_mask = self.join_channels();
+        config
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn plan_types() {
+        let region = Cn470::new(Cn470Plan::A20);
+        let config = region.default_config();
+        assert_eq!(config.channel_mask.count(), 64);
+        assert_eq!(region.channel(&config, 31).unwrap().frequency, 476_500_000);
+        assert_eq!(region.channel(&config, 32).unwrap().frequency, 503_500_000);
+        assert_eq!(region.channel(&config, 64), None);
+        assert_eq!(region.rx1_frequency(&config, 63), Some(496_500_000));
+        assert_eq!(region.join_rx2_frequency(40), 486_900_000);
+
+        let region = Cn470::new(Cn470Plan::B26);
+        let config = region.default_config();
+        assert_eq!(config.channel_mask.count(), 48);
+        assert_eq!(region.channel(&config, 47).unwrap().frequency, 489_700_000);
+        assert_eq!(region.rx1_frequency(&config, 30), Some(501_300_000));
+        assert_eq!(region.rx2_frequency(), 502_500_000);
+        assert_eq!(region.max_mac_payload_size(&config, 0), None);
+
+        let mut mask = config.channel_mask;
+        assert!(region.apply_ch_mask(&config, &mut mask, 2, 0x0001));
+        assert!(!region.apply_ch_mask(&config, &mut mask, 3, 0x0001));
+        assert_eq!(mask, ChannelMask::new([0xffff, 0xffff, 0x0001, 0, 0]));
+    }
+}
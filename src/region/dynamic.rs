@@ -0,0 +1,65 @@
+//! Common parts of the regions whose channels are defined by the network
+use crate::{
+    device::{Channel, DeviceConfig, Limits},
+    phy_payload::join_accept::CFList,
+};
+
+use super::{DataRate, SubBand};
+
+pub(super) struct DynamicRegion {
+    pub limits: Limits,
+    pub data_rates: [Option<DataRate>; 8],
+    pub max_mac_payload_sizes: [Option<usize>; 8],
+    /// Maximum MACPayload sizes with an uplink dwell time of 400 ms
+    pub dwell_time_max_mac_payload_sizes: [Option<usize>; 8],
+    /// Default maximum EIRP in dBm
+    pub max_eirp: i8,
+    pub default_channels: &'static [Channel],
+    pub rx2_frequency: u32,
+    pub rx2_data_rate: u8,
+    pub join_data_rate: u8,
+    pub sub_bands: &'static [SubBand],
+}
+
+impl DynamicRegion {
+    pub fn data_rate(&self, data_rate: u8) -> Option<DataRate> {
+        self.data_rates.get(data_rate as usize).copied().flatten()
+    }
+    pub fn max_mac_payload_size(&self, config: &DeviceConfig, data_rate: u8) -> Option<usize> {
+        let sizes = match config.uplink_dwell_time {
+            true => &self.dwell_time_max_mac_payload_sizes,
+            false => &self.max_mac_payload_sizes,
+        };
+        sizes.get(data_rate as usize).copied().flatten()
+    }
+    pub fn tx_power(&self, tx_power: u8) -> Option<i8> {
+        (tx_power <= self.limits.max_tx_power).then(|| self.max_eirp - 2 * tx_power as i8)
+    }
+    /// RX1 data rate of the regions where it is the uplink one minus RX1DROffset
+    pub fn rx1_data_rate(&self, data_rate: u8, rx1_dr_offset: u8) -> Option<u8> {
+        (data_rate <= self.limits.max_data_rate && rx1_dr_offset <= self.limits.max_rx1_dr_offset)
+            .then(|| data_rate.saturating_sub(rx1_dr_offset))
+    }
+    /// A random default channel
+    pub fn join_channel(&self, random: u32) -> (usize, u8) {
+        (
+            random as usize % self.default_channels.len(),
+            self.join_data_rate,
+        )
+    }
+    /// Channels 3 to 7 use the data rates of the first default channel
+    pub fn apply_cf_list(&self, config: &mut DeviceConfig, cf_list: &CFList) {
+        let Some(default) = self.default_channels.first() else {
+            return;
+        };
+        if let CFList::Frequencies(frequencies) = cf_list {
+            for (index, frequency) in frequencies.iter().enumerate() {
+                let channel = match frequency.hz() {
+                    0 => None,
+                    frequency => Some(Channel::new(frequency, default.min_dr, default.max_dr)),
+                };
+                config.set_channel(self.default_channels.len() + index, channel);
+            }
+        }
+    }
+}
@@ -0,0 +1,239 @@
+//! EU863-870
+use crate::{
+    device::{
+        channel_plan::{ChannelPlan, DynamicChannelPlan},
+        Channel, ChannelMask, DeviceConfig, Limits,
+    },
+    phy_payload::join_accept::CFList,
+};
+
+use super::{dynamic::DynamicRegion, Bandwidth, DataRate, Region, SubBand};
+
+/// Default maximum EIRP in dBm
+pub const MAX_EIRP: i8 = 16;
+pub const RX2_FREQUENCY: u32 = 869_525_000;
+pub const RX2_DATA_RATE: u8 = 0;
+
+pub const DEFAULT_CHANNELS: [Channel; 3] = [
+    Channel::new(868_100_000, 0, 5),
+    Channel::new(868_300_000, 0, 5),
+    Channel::new(868_500_000, 0, 5),
+];
+
+pub const DATA_RATES: [Option<DataRate>; 8] = [
+    Some(DataRate::lora(12, Bandwidth::Khz125, 250)),
+    Some(DataRate::lora(11, Bandwidth::Khz125, 440)),
+    Some(DataRate::lora(10, Bandwidth::Khz125, 980)),
+    Some(DataRate::lora(9, Bandwidth::Khz125, 1760)),
+    Some(DataRate::lora(8, Bandwidth::Khz125, 3125)),
+    Some(DataRate::lora(7, Bandwidth::Khz125, 5470)),
+    Some(DataRate::lora(7, Bandwidth::Khz250, 11000)),
+    Some(DataRate::fsk(50000)),
+];
+
+/// Maximum MACPayload size per data rate, repeater compatible
+pub const MAX_MAC_PAYLOAD_SIZES: [Option<usize>; 8] = [
+    Some(59),
+    Some(59),
+    Some(59),
+    Some(123),
+    Some(230),
+    Some(230),
+    Some(230),
+    Some(230),
+];
+
+/// ETSI EN 300 220 sub-bands
+pub const SUB_BANDS: [SubBand; 6] = [
+    SubBand {
+        min_frequency: 863_000_000,
+        max_frequency: 865_000_000,
+        duty_cycle: 1000,
+    },
+    SubBand {
+        min_frequency: 865_000_000,
+        max_frequency: 868_000_000,
+        duty_cycle: 100,
+    },
+    SubBand {
+        min_frequency: 868_000_000,
+        max_frequency: 868_600_000,
+        duty_cycle: 100,
+    },
+    SubBand {
+        min_frequency: 868_700_000,
+        max_frequency: 869_200_000,
+        duty_cycle: 1000,
+    },
+    SubBand {
+        min_frequency: 869_400_000,
+        max_frequency: 869_650_000,
+        duty_cycle: 10,
+    },
+    SubBand {
+        min_frequency: 869_700_000,
+        max_frequency: 870_000_000,
+        duty_cycle: 100,
+    },
+];
+
+const REGION: DynamicRegion = DynamicRegion {
+    limits: Limits {
+        min_frequency: 863_000_000,
+        max_frequency: 870_000_000,
+        max_data_rate: 7,
+        max_dl_data_rate: 7,
+        max_tx_power: 7,
+        max_rx1_dr_offset: 5,
+        default_channels: DEFAULT_CHANNELS.len(),
+        tx_param_setup: false,
+    },
+    data_rates: DATA_RATES,
+    max_mac_payload_sizes: MAX_MAC_PAYLOAD_SIZES,
+    dwell_time_max_mac_payload_sizes: MAX_MAC_PAYLOAD_SIZES,
+    max_eirp: MAX_EIRP,
+    default_channels: &DEFAULT_CHANNELS,
+    rx2_frequency: RX2_FREQUENCY,
+    rx2_data_rate: RX2_DATA_RATE,
+    join_data_rate: 0,
+    sub_bands: &SUB_BANDS,
+};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Eu868;
+
+impl ChannelPlan for Eu868 {
+    fn channel(&self, config: &DeviceConfig, index: usize) -> Option<Channel> {
+        DynamicChannelPlan.channel(config, index)
+    }
+    fn apply_ch_mask(
+        &self,
+        config: &DeviceConfig,
+        mask: &mut ChannelMask,
+        ch_mask_cntl: u8,
+        ch_mask: u16,
+    ) -> bool {
+        DynamicChannelPlan.apply_ch_mask(config, mask, ch_mask_cntl, ch_mask)
+    }
+}
+
+impl Region for Eu868 {
+    fn limits(&self) -> Limits {
+        REGION.limits
+    }
+    fn data_rate(&self, data_rate: u8) -> Option<DataRate> {
+        REGION.data_rate(data_rate)
+    }
+    fn max_mac_payload_size(&self, config: &DeviceConfig, data_rate: u8) -> Option<usize> {
+        REGION.max_mac_payload_size(config, data_rate)
+    }
+    fn tx_power(&self, _config: &DeviceConfig, tx_power: u8) -> Option<i8> {
+        REGION.tx_power(tx_power)
+    }
+    fn rx1_data_rate(
+        &self,
+        _config: &DeviceConfig,
+        data_rate: u8,
+        rx1_dr_offset: u8,
+    ) -> Option<u8> {
+        REGION.rx1_data_rate(data_rate, rx1_dr_offset)
+    }
+    fn rx2_frequency(&self) -> u32 {
+        REGION.rx2_frequency
+    }
+    fn rx2_data_rate(&self) -> u8 {
+        REGION.rx2_data_rate
+    }
+    fn default_channels(&self) -> &[Channel] {
+        REGION.default_channels
+    }
+    fn join_channels(&self) -> ChannelMask {
+        ChannelMask::all(DEFAULT_CHANNELS.len())
+    }
+    fn join_channel(&self, _attempt: u32, random: u32) -> (usize, u8) {
+        REGION.join_channel(random)
+    }
+    fn sub_bands(&self) -> &[SubBand] {
+        REGION.sub_bands
+    }
+    fn apply_cf_list(&self, config: &mut DeviceConfig, cf_list: &CFList) {
+        REGION.apply_cf_list(config, cf_list)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use zerocopy::TryFromBytes;
+
+    use crate::{
+        device::link_adr::process_link_adr_block,
+        mac_command::downlink::{
+            DlSettings, DownlinkMacCommand, DownlinkMacCommandDecoder, Frequency,
+        },
+        region::Modulation,
+    };
+
+    use super::*;
+    #[test]
+    fn lookups() {
+        let region = Eu868;
+        let config = region.default_config();
+        assert_eq!(
+            region.data_rate(5).unwrap().modulation,
+            Modulation::LoRa {
+                spreading_factor: 7,
+                bandwidth: Bandwidth::Khz125
+            }
+        );
+        assert_eq!(region.data_rate(8), None);
+        assert_eq!(region.max_mac_payload_size(&config, 3), Some(123));
+        assert_eq!(region.tx_power(&config, 7), Some(2));
+        assert_eq!(region.rx1_data_rate(&config, 2, 3), Some(0));
+        assert_eq!(region.rx1_data_rate(&config, 5, 6), None);
+        assert_eq!(region.sub_band(869_525_000).unwrap().duty_cycle, 10);
+        assert_eq!(region.sub_band(869_300_000), None);
+
+        let dl_settings = DlSettings::new()
+            .with_rx1_dr_offset(1)
+            .with_rx2_data_rate(3);
+        assert_eq!(
+            region.rx_param_setup(&config, 5, &dl_settings),
+            DATA_RATES[4].zip(DATA_RATES[3])
+        );
+    }
+    #[test]
+    fn link_adr_req() {
+        let region = Eu868;
+        let config = region.default_config();
+        assert_eq!(config.channel_mask, region.join_channels());
+        assert_eq!(config.rx.rx2_data_rate, 0);
+
+        // DR5, TXPower 3, channels 0 and 2
+        let cmds = [0x03, 0x53, 0x05, 0x00, 0x01];
+        let Some(DownlinkMacCommand::LinkADRReq(req)) =
+            DownlinkMacCommandDecoder::new(&cmds).next()
+        else {
+            panic!("not a LinkADRReq");
+        };
+        assert_eq!(
+            region.link_adr(&config, &req.data_rate_tx_power),
+            (DATA_RATES[5], Some(10))
+        );
+        let params = process_link_adr_block(&region, &config, [&req]).unwrap();
+        assert_eq!(params.channel_mask, ChannelMask::new([0x0005, 0, 0, 0, 0]));
+    }
+    #[test]
+    fn cf_list() {
+        let region = Eu868;
+        let mut config = region.default_config();
+        // 867.1 MHz as channel 3 in on-air order, channels 4 to 7 disabled
+        let mut frequencies = [0u8; 15];
+        frequencies[..3].copy_from_slice(&[0x18, 0x4f, 0x84]);
+        let frequencies = <[Frequency; 5]>::try_ref_from_bytes(&frequencies).unwrap();
+        region.apply_cf_list(&mut config, &CFList::Frequencies(frequencies));
+        assert_eq!(config.channels[3], Some(Channel::new(867_100_000, 0, 5)));
+        assert_eq!(config.channels[4], None);
+        assert_eq!(config.channel_mask, ChannelMask::new([0x000f, 0, 0, 0, 0]));
+    }
+}
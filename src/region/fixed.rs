@@ -0,0 +1,89 @@
+//! Common parts of the regions with 64 + 8 uplink channels and 8 downlink channels
+use crate::{
+    device::{channel_plan::FixedChannelPlan, ChannelMask, DeviceConfig, Limits},
+    phy_payload::join_accept::CFList,
+};
+
+use super::DataRate;
+
+pub(super) const MAX_EIRP: i8 = 30;
+pub(super) const MAX_TX_POWER: u8 = 14;
+
+pub(super) struct FixedRegion {
+    pub plan: FixedChannelPlan,
+    pub limits: Limits,
+    /// Uplink data rates followed by the downlink ones, from DR8
+    pub data_rates: [Option<DataRate>; 14],
+    pub max_mac_payload_sizes: [Option<usize>; 14],
+    /// Maximum MACPayload sizes with an uplink dwell time of 400 ms
+    pub dwell_time_max_mac_payload_sizes: [Option<usize>; 14],
+    /// RX1 data rate of an uplink at DR0 with a RX1DROffset of 0
+    pub rx1_data_rate: u8,
+    /// Frequency in Hz of downlink channel 0, the 8 downlink channels are 600 kHz apart
+    pub first_dl_frequency: u32,
+    pub rx2_data_rate: u8,
+    /// Data rate of the join-requests sent on 125 kHz channels
+    pub join_data_rate_125: u8,
+}
+
+impl FixedRegion {
+    pub fn data_rate(&self, data_rate: u8) -> Option<DataRate> {
+        self.data_rates.get(data_rate as usize).copied().flatten()
+    }
+    pub fn max_mac_payload_size(&self, config: &DeviceConfig, data_rate: u8) -> Option<usize> {
+        let sizes = match config.uplink_dwell_time {
+            true => &self.dwell_time_max_mac_payload_sizes,
+            false => &self.max_mac_payload_sizes,
+        };
+        sizes.get(data_rate as usize).copied().flatten()
+    }
+    pub fn tx_power(&self, tx_power: u8) -> Option<i8> {
+        (tx_power <= MAX_TX_POWER).then(|| MAX_EIRP - 2 * tx_power as i8)
+    }
+    pub fn rx1_data_rate(&self, data_rate: u8, rx1_dr_offset: u8) -> Option<u8> {
+        (data_rate <= self.limits.max_data_rate && rx1_dr_offset <= self.limits.max_rx1_dr_offset)
+            .then(|| {
+                (self.rx1_data_rate + data_rate)
+                    .saturating_sub(rx1_dr_offset)
+                    .clamp(8, 13)
+            })
+    }
+    pub fn rx1_frequency(&self, channel: usize) -> Option<u32> {
+        (channel < 72).then(|| self.first_dl_frequency + 600_000 * (channel % 8) as u32)
+    }
+    pub fn default_config(&self, sub_bands: u8) -> DeviceConfig {
+        let mut config =
+            DeviceConfig::new(self.limits, self.rx2_data_rate, self.first_dl_frequency);
+        config.channel_mask = FixedChannelPlan::sub_band_mask(sub_bands);
+        config
+    }
+    /// Alternates between a random 125 kHz channel and the 500 kHz channel of a sub-band,
+    /// moving to the next enabled sub-band after both
+    pub fn join_channel(&self, sub_bands: u8, attempt: u32, random: u32) -> (usize, u8) {
+        let sub_bands = match sub_bands {
+            0 => 0xff,
+            sub_bands => sub_bands,
+        };
+        let nth = (attempt / 2 % sub_bands.count_ones()) as usize;
+        let sub_band = (0..8)
+            .filter(|sub_band| sub_bands & (1 << sub_band) != 0)
+            .nth(nth)
+            .unwrap_or(0);
+        match attempt % 2 {
+            0 => (8 * sub_band + random as usize % 8, self.join_data_rate_125),
+            _ => (64 + sub_band, self.plan.dr_500),
+        }
+    }
+    pub fn apply_cf_list(&self, config: &mut DeviceConfig, cf_list: &CFList) {
+        if let CFList::ChannelMasks(masks) = cf_list {
+            let mut channel_mask = ChannelMask::default();
+            for (bank, mask) in masks.iter().enumerate() {
+                channel_mask.set_bank(bank, mask.get());
+            }
+            channel_mask.set_bank(4, channel_mask.bank(4) & 0xff);
+            if !channel_mask.is_empty() {
+                config.channel_mask = channel_mask;
+            }
+        }
+    }
+}
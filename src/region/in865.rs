@@ -0,0 +1,170 @@
+//! IN865-867
+use crate::{
+    device::{
+        channel_plan::{ChannelPlan, DynamicChannelPlan},
+        Channel, ChannelMask, DeviceConfig, Limits,
+    },
+    phy_payload::join_accept::CFList,
+};
+
+use super::{dynamic::DynamicRegion, Bandwidth, DataRate, Region, SubBand};
+
+/// Default maximum EIRP in dBm
+pub const MAX_EIRP: i8 = 30;
+pub const RX2_FREQUENCY: u32 = 866_550_000;
+pub const RX2_DATA_RATE: u8 = 2;
+
+pub const DEFAULT_CHANNELS: [Channel; 3] = [
+    Channel::new(865_062_500, 0, 5),
+    Channel::new(865_402_500, 0, 5),
+    Channel::new(865_985_000, 0, 5),
+];
+
+pub const DATA_RATES: [Option<DataRate>; 8] = [
+    Some(DataRate::lora(12, Bandwidth::Khz125, 250)),
+    Some(DataRate::lora(11, Bandwidth::Khz125, 440)),
+    Some(DataRate::lora(10, Bandwidth::Khz125, 980)),
+    Some(DataRate::lora(9, Bandwidth::Khz125, 1760)),
+    Some(DataRate::lora(8, Bandwidth::Khz125, 3125)),
+    Some(DataRate::lora(7, Bandwidth::Khz125, 5470)),
+    None,
+    Some(DataRate::fsk(50000)),
+];
+
+/// Maximum MACPayload size per data rate, repeater compatible
+pub const MAX_MAC_PAYLOAD_SIZES: [Option<usize>; 8] = [
+    Some(59),
+    Some(59),
+    Some(59),
+    Some(123),
+    Some(230),
+    Some(230),
+    None,
+    Some(230),
+];
+
+/// RX1 data rate by uplink data rate and RX1DROffset
+pub const RX1_DATA_RATES: [Option<[u8; 8]>; 8] = [
+    Some([0, 0, 0, 0, 0, 0, 1, 2]),
+    Some([1, 0, 0, 0, 0, 0, 2, 3]),
+    Some([2, 1, 0, 0, 0, 0, 3, 4]),
+    Some([3, 2, 1, 0, 0, 0, 4, 5]),
+    Some([4, 3, 2, 1, 0, 0, 5, 5]),
+    Some([5, 4, 3, 2, 1, 0, 5, 7]),
+    None,
+    Some([7, 5, 5, 4, 3, 2, 7, 7]),
+];
+
+pub const SUB_BANDS: [SubBand; 1] = [SubBand {
+    min_frequency: 865_000_000,
+    max_frequency: 867_000_000,
+    duty_cycle: 1,
+}];
+
+const REGION: DynamicRegion = DynamicRegion {
+    limits: Limits {
+        min_frequency: 865_000_000,
+        max_frequency: 867_000_000,
+        max_data_rate: 7,
+        max_dl_data_rate: 7,
+        max_tx_power: 10,
+        max_rx1_dr_offset: 7,
+        default_channels: DEFAULT_CHANNELS.len(),
+        tx_param_setup: false,
+    },
+    data_rates: DATA_RATES,
+    max_mac_payload_sizes: MAX_MAC_PAYLOAD_SIZES,
+    dwell_time_max_mac_payload_sizes: MAX_MAC_PAYLOAD_SIZES,
+    max_eirp: MAX_EIRP,
+    default_channels: &DEFAULT_CHANNELS,
+    rx2_frequency: RX2_FREQUENCY,
+    rx2_data_rate: RX2_DATA_RATE,
+    join_data_rate: 0,
+    sub_bands: &SUB_BANDS,
+};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct In865;
+
+impl ChannelPlan for In865 {
+    fn channel(&self, config: &DeviceConfig, index: usize) -> Option<Channel> {
+        DynamicChannelPlan.channel(config, index)
+    }
+    fn apply_ch_mask(
+        &self,
+        config: &DeviceConfig,
+        mask: &mut ChannelMask,
+        ch_mask_cntl: u8,
+        ch_mask: u16,
+    ) -> bool {
+        DynamicChannelPlan.apply_ch_mask(config, mask, ch_mask_cntl, ch_mask)
+    }
+}
+
+impl Region for In865 {
+    fn limits(&self) -> Limits {
+        REGION.limits
+    }
+    fn data_rate(&self, data_rate: u8) -> Option<DataRate> {
+        REGION.data_rate(data_rate)
+    }
+    fn max_mac_payload_size(&self, config: &DeviceConfig, data_rate: u8) -> Option<usize> {
+        REGION.max_mac_payload_size(config, data_rate)
+    }
+    fn tx_power(&self, _config: &DeviceConfig, tx_power: u8) -> Option<i8> {
+        REGION.tx_power(tx_power)
+    }
+    fn rx1_data_rate(
+        &self,
+        _config: &DeviceConfig,
+        data_rate: u8,
+        rx1_dr_offset: u8,
+    ) -> Option<u8> {
+        RX1_DATA_RATES
+            .get(data_rate as usize)
+            .copied()
+            .flatten()?
+            .get(rx1_dr_offset as usize)
+            .copied()
+    }
+    fn rx2_frequency(&self) -> u32 {
+        REGION.rx2_frequency
+    }
+    fn rx2_data_rate(&self) -> u8 {
+        REGION.rx2_data_rate
+    }
+    fn default_channels(&self) -> &[Channel] {
+        REGION.default_channels
+    }
+    fn join_channels(&self) -> ChannelMask {
+        ChannelMask::all(DEFAULT_CHANNELS.len())
+    }
+    fn join_channel(&self, _attempt: u32, random: u32) -> (usize, u8) {
+        REGION.join_channel(random)
+    }
+    fn sub_bands(&self) -> &[SubBand] {
+        REGION.sub_bands
+    }
+    fn apply_cf_list(&self, config: &mut DeviceConfig, cf_list: &CFList) {
+        REGION.apply_cf_list(config, cf_list)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn in865() {
+        let region = In865;
+        let config = region.default_config();
+        assert_eq!(region.data_rate(6), None);
+        assert_eq!(region.max_mac_payload_size(&config, 6), None);
+        assert_eq!(region.tx_power(&config, 10), Some(10));
+        assert_eq!(region.tx_power(&config, 11), None);
+        assert_eq!(region.rx1_data_rate(&config, 3, 1), Some(2));
+        assert_eq!(region.rx1_data_rate(&config, 5, 7), Some(7));
+        assert_eq!(region.rx1_data_rate(&config, 6, 0), None);
+        assert_eq!(region.join_channel(0, 4), (1, 0));
+    }
+}
@@ -0,0 +1,194 @@
+//! Regional parameters, giving a meaning to the indexes sent in MAC commands.
+pub mod as923;
+pub mod au915;
+pub mod cn470;
+mod dynamic;
+pub mod eu868;
+mod fixed;
+pub mod in865;
+pub mod kr920;
+pub mod us915;
+
+use core::time::Duration;
+
+use crate::{
+    airtime::{fsk_time_on_air, LoRaPacket},
+    device::{channel_plan::ChannelPlan, Channel, ChannelMask, DeviceConfig, Limits},
+    mac_command::downlink::{DataRateTXPower, DlSettings},
+    phy_payload::join_accept::CFList,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Bandwidth {
+    Khz125,
+    Khz250,
+    Khz500,
+}
+impl Bandwidth {
+    pub fn hz(&self) -> u32 {
+        match self {
+            Bandwidth::Khz125 => 125_000,
+            Bandwidth::Khz250 => 250_000,
+            Bandwidth::Khz500 => 500_000,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Modulation {
+    LoRa {
+        spreading_factor: u8,
+        bandwidth: Bandwidth,
+    },
+    Fsk,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DataRate {
+    pub modulation: Modulation,
+    /// Indicative physical bit rate in bit/s
+    pub bitrate: u32,
+}
+impl DataRate {
+    pub const fn lora(spreading_factor: u8, bandwidth: Bandwidth, bitrate: u32) -> Self {
+        Self {
+            modulation: Modulation::LoRa {
+                spreading_factor,
+                bandwidth,
+            },
+            bitrate,
+        }
+    }
+    pub const fn fsk(bitrate: u32) -> Self {
+        Self {
+            modulation: Modulation::Fsk,
+            bitrate,
+        }
+    }
+    /// Time on air of a PHYPayload of `len` bytes, uplinks having a CRC
+    pub fn time_on_air(&self, len: usize, uplink: bool) -> Duration {
+        match self.modulation {
+            Modulation::LoRa {
+                spreading_factor,
+                bandwidth,
+            } => match uplink {
+                true => LoRaPacket::uplink(spreading_factor, bandwidth),
+                false => LoRaPacket::downlink(spreading_factor, bandwidth),
+            }
+            .time_on_air(len),
+            Modulation::Fsk => fsk_time_on_air(self.bitrate, len),
+        }
+    }
+}
+
+/// Frequency range sharing a duty-cycle limit
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SubBand {
+    /// Lowest frequency in Hz
+    pub min_frequency: u32,
+    /// Highest frequency in Hz
+    pub max_frequency: u32,
+    /// The duty cycle is limited to 1 / duty_cycle
+    pub duty_cycle: u16,
+}
+impl SubBand {
+    pub fn contains(&self, frequency: u32) -> bool {
+        (self.min_frequency..=self.max_frequency).contains(&frequency)
+    }
+}
+
+/// Listen-before-talk requirement
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Lbt {
+    /// The channel is free if the received power stays below this threshold, in dBm
+    pub threshold: i16,
+    /// Minimum time the channel must be free before transmitting
+    pub sense_time: Duration,
+}
+
+/// MaxEIRP in dBm of TxParamSetupReq, by index
+pub const MAX_EIRP: [u8; 16] = [
+    8, 10, 12, 13, 14, 16, 18, 20, 21, 24, 26, 27, 29, 30, 33, 36,
+];
+
+pub trait Region: ChannelPlan {
+    fn limits(&self) -> Limits;
+    /// `None` if the data rate is not defined in the region
+    fn data_rate(&self, data_rate: u8) -> Option<DataRate>;
+    /// Maximum MACPayload size in bytes of an uplink, taking the dwell time into account.
+    /// `None` if the data rate cannot be used.
+    fn max_mac_payload_size(&self, config: &DeviceConfig, data_rate: u8) -> Option<usize>;
+    /// EIRP in dBm of TXPower `tx_power`, `None` if it is not defined
+    fn tx_power(&self, config: &DeviceConfig, tx_power: u8) -> Option<i8>;
+    /// RX1 data rate for an uplink at `data_rate`, `None` if either is invalid
+    fn rx1_data_rate(&self, config: &DeviceConfig, data_rate: u8, rx1_dr_offset: u8) -> Option<u8>;
+    /// Default RX2 frequency in Hz
+    fn rx2_frequency(&self) -> u32;
+    /// Default RX2 data rate
+    fn rx2_data_rate(&self) -> u8;
+    /// Channels defined by default
+    fn default_channels(&self) -> &[Channel];
+    /// Channels of [`Self::default_config`] that can be used to join
+    fn join_channels(&self) -> ChannelMask;
+    /// Channel and data rate of the join-request `attempt`, `random` being a random number
+    /// drawn for each attempt
+    fn join_channel(&self, attempt: u32, random: u32) -> (usize, u8);
+    fn sub_bands(&self) -> &[SubBand];
+    /// Applies the CFList of a join-accept, ignoring the types not used in the region
+    fn apply_cf_list(&self, config: &mut DeviceConfig, cf_list: &CFList);
+
+    /// RX1 frequency in Hz for an uplink on `channel`
+    fn rx1_frequency(&self, config: &DeviceConfig, channel: usize) -> Option<u32> {
+        self.channel(config, channel)
+            .map(|channel| channel.dl_frequency)
+    }
+    /// Listen-before-talk requirement, if any
+    fn lbt(&self) -> Option<Lbt> {
+        None
+    }
+    /// Configuration of a device that has just joined
+    fn default_config(&self) -> DeviceConfig {
+        let mut config =
+            DeviceConfig::new(self.limits(), self.rx2_data_rate(), self.rx2_frequency());
+        for (index, channel) in self.default_channels().iter().enumerate() {
+            config.set_channel(index, Some(*channel));
+        }
+        config
+    }
+    /// Sub-band of `frequency`, `None` if it is outside the region's bands
+    fn sub_band(&self, frequency: u32) -> Option<&SubBand> {
+        self.sub_bands()
+            .iter()
+            .find(|sub_band| sub_band.contains(frequency))
+    }
+    /// Data rate and EIRP of a LinkADRReq. Values of 0xF, which keep the current setting, and
+    /// undefined ones are `None`.
+    fn link_adr(
+        &self,
+        config: &DeviceConfig,
+        req: &DataRateTXPower,
+    ) -> (Option<DataRate>, Option<i8>) {
+        (
+            self.data_rate(req.data_rate()),
+            self.tx_power(config, req.tx_power()),
+        )
+    }
+    /// RX1 and RX2 data rates of an RXParamSetupReq for an uplink at `data_rate`
+    fn rx_param_setup(
+        &self,
+        config: &DeviceConfig,
+        data_rate: u8,
+        dl_settings: &DlSettings,
+    ) -> Option<(DataRate, DataRate)> {
+        let rx1 = self.rx1_data_rate(config, data_rate, dl_settings.rx1_dr_offset())?;
+        Some((
+            self.data_rate(rx1)?,
+            self.data_rate(dl_settings.rx2_data_rate())?,
+        ))
+    }
+}
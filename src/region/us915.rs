@@ -0,0 +1,235 @@
+//! US902-928
+use crate::{
+    device::{
+        channel_plan::{ChannelPlan, FixedChannelPlan},
+        Channel, ChannelMask, DeviceConfig, Limits,
+    },
+    phy_payload::join_accept::CFList,
+};
+
+use super::{
+    fixed::{FixedRegion, MAX_TX_POWER},
+    Bandwidth, DataRate, Region, SubBand,
+};
+
+pub const RX2_FREQUENCY: u32 = 923_300_000;
+pub const RX2_DATA_RATE: u8 = 8;
+
+/// Maximum MACPayload size per data rate, repeater compatible
+pub const MAX_MAC_PAYLOAD_SIZES: [Option<usize>; 14] = [
+    Some(19),
+    Some(61),
+    Some(133),
+    Some(250),
+    Some(250),
+    None,
+    None,
+    None,
+    Some(41),
+    Some(117),
+    Some(230),
+    Some(230),
+    Some(230),
+    Some(230),
+];
+
+const REGION: FixedRegion = FixedRegion {
+    plan: FixedChannelPlan {
+        first_frequency_125: 902_300_000,
+        min_dr_125: 0,
+        max_dr_125: 3,
+        first_frequency_500: 903_000_000,
+        dr_500: 4,
+    },
+    limits: Limits {
+        min_frequency: 902_000_000,
+        max_frequency: 928_000_000,
+        max_data_rate: 4,
+        max_dl_data_rate: 13,
+        max_tx_power: MAX_TX_POWER,
+        max_rx1_dr_offset: 3,
+        default_channels: 72,
+        tx_param_setup: false,
+    },
+    data_rates: [
+        Some(DataRate::lora(10, Bandwidth::Khz125, 980)),
+        Some(DataRate::lora(9, Bandwidth::Khz125, 1760)),
+        Some(DataRate::lora(8, Bandwidth::Khz125, 3125)),
+        Some(DataRate::lora(7, Bandwidth::Khz125, 5470)),
+        Some(DataRate::lora(8, Bandwidth::Khz500, 12500)),
+        None,
+        None,
+        None,
+        Some(DataRate::lora(12, Bandwidth::Khz500, 980)),
+        Some(DataRate::lora(11, Bandwidth::Khz500, 1760)),
+        Some(DataRate::lora(10, Bandwidth::Khz500, 3900)),
+        Some(DataRate::lora(9, Bandwidth::Khz500, 7000)),
+        Some(DataRate::lora(8, Bandwidth::Khz500, 12500)),
+        Some(DataRate::lora(7, Bandwidth::Khz500, 21900)),
+    ],
+    max_mac_payload_sizes: MAX_MAC_PAYLOAD_SIZES,
+    dwell_time_max_mac_payload_sizes: MAX_MAC_PAYLOAD_SIZES,
+    rx1_data_rate: 10,
+    first_dl_frequency: RX2_FREQUENCY,
+    rx2_data_rate: RX2_DATA_RATE,
+    join_data_rate_125: 0,
+};
+
+pub const SUB_BANDS: [SubBand; 1] = [SubBand {
+    min_frequency: 902_000_000,
+    max_frequency: 928_000_000,
+    duty_cycle: 1,
+}];
+
+/// US902-928, using the sub-bands set in `sub_bands`: bit `n` enables the 125 kHz channels
+/// `8 * n` to `8 * n + 7` and the 500 kHz channel `64 + n`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Us915 {
+    pub sub_bands: u8,
+}
+impl Us915 {
+    pub fn new(sub_bands: u8) -> Self {
+        Self { sub_bands }
+    }
+}
+impl Default for Us915 {
+    /// All sub-bands
+    fn default() -> Self {
+        Self::new(0xff)
+    }
+}
+
+impl ChannelPlan for Us915 {
+    fn channel(&self, config: &DeviceConfig, index: usize) -> Option<Channel> {
+        REGION.plan.channel(config, index)
+    }
+    fn apply_ch_mask(
+        &self,
+        config: &DeviceConfig,
+        mask: &mut ChannelMask,
+        ch_mask_cntl: u8,
+        ch_mask: u16,
+    ) -> bool {
+        REGION
+            .plan
+            .apply_ch_mask(config, mask, ch_mask_cntl, ch_mask)
+    }
+    fn supports_new_channel(&self) -> bool {
+        REGION.plan.supports_new_channel()
+    }
+}
+
+impl Region for Us915 {
+    fn limits(&self) -> Limits {
+        REGION.limits
+    }
+    fn data_rate(&self, data_rate: u8) -> Option<DataRate> {
+        REGION.data_rate(data_rate)
+    }
+    fn max_mac_payload_size(&self, config: &DeviceConfig, data_rate: u8) -> Option<usize> {
+        REGION.max_mac_payload_size(config, data_rate)
+    }
+    fn tx_power(&self, _config: &DeviceConfig, tx_power: u8) -> Option<i8> {
+        REGION.tx_power(tx_power)
+    }
+    fn rx1_data_rate(
+        &self,
+        _config: &DeviceConfig,
+        data_rate: u8,
+        rx1_dr_offset: u8,
+    ) -> Option<u8> {
+        REGION.rx1_data_rate(data_rate, rx1_dr_offset)
+    }
+    fn rx2_frequency(&self) -> u32 {
+        RX2_FREQUENCY
+    }
+    fn rx2_data_rate(&self) -> u8 {
+        RX2_DATA_RATE
+    }
+    fn default_channels(&self) -> &[Channel] {
+        &[]
+    }
+    fn join_channels(&self) -> ChannelMask {
+        FixedChannelPlan::sub_band_mask(self.sub_bands)
+    }
+    fn join_channel(&self, attempt: u32, random: u32) -> (usize, u8) {
+        REGION.join_channel(self.sub_bands, attempt, random)
+    }
+    fn sub_bands(&self) -> &[SubBand] {
+        &SUB_BANDS
+    }
+    fn apply_cf_list(&self, config: &mut DeviceConfig, cf_list: &CFList) {
+        REGION.apply_cf_list(config, cf_list)
+    }
+    fn rx1_frequency(&self, _config: &DeviceConfig, channel: usize) -> Option<u32> {
+        REGION.rx1_frequency(channel)
+    }
+    fn default_config(&self) -> DeviceConfig {
+        REGION.default_config(self.sub_bands)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use zerocopy::little_endian::U16;
+
+    use crate::{
+        device::link_adr::process_link_adr_block,
+        mac_command::downlink::{DownlinkMacCommand, DownlinkMacCommandDecoder, LinkADRReq},
+    };
+
+    use super::*;
+    fn link_adr_reqs(cmds: &[u8]) -> impl Iterator<Item = LinkADRReq> + '_ {
+        DownlinkMacCommandDecoder::new(cmds).map(|cmd| match cmd {
+            DownlinkMacCommand::LinkADRReq(req) => req,
+            _ => panic!("not a LinkADRReq"),
+        })
+    }
+    #[test]
+    fn sub_band_2() {
+        let region = Us915::new(0x02);
+        let config = region.default_config();
+        assert_eq!(config.channel_mask.count(), 9);
+        assert_eq!(region.channel(&config, 8).unwrap().frequency, 903_900_000);
+        assert_eq!(region.channel(&config, 65).unwrap().frequency, 904_600_000);
+        assert_eq!(region.rx1_frequency(&config, 65), Some(923_900_000));
+        assert_eq!(region.rx1_frequency(&config, 72), None);
+
+        assert_eq!(region.join_channel(0, 13), (13, 0));
+        assert_eq!(region.join_channel(1, 13), (65, 4));
+        assert_eq!(Us915::new(0x12).join_channel(2, 13), (37, 0));
+    }
+    #[test]
+    fn data_rates() {
+        let region = Us915::default();
+        let config = region.default_config();
+        assert_eq!(region.data_rate(5), None);
+        assert_eq!(region.max_mac_payload_size(&config, 0), Some(19));
+        assert_eq!(region.rx1_data_rate(&config, 0, 0), Some(10));
+        assert_eq!(region.rx1_data_rate(&config, 4, 0), Some(13));
+        assert_eq!(region.rx1_data_rate(&config, 4, 1), Some(13));
+        assert_eq!(region.rx1_data_rate(&config, 1, 3), Some(8));
+        assert_eq!(region.rx1_data_rate(&config, 0, 4), None);
+        assert_eq!(region.tx_power(&config, 14), Some(2));
+        assert_eq!(region.tx_power(&config, 15), None);
+    }
+    #[test]
+    fn link_adr_and_cf_list() {
+        let region = Us915::default();
+        let mut config = region.default_config();
+        // ChMaskCntl 7 disables all 125 kHz channels, then channels 8 to 15 at DR3
+        let cmds = [0x03, 0x30, 0x00, 0x00, 0x71, 0x03, 0x30, 0x00, 0xff, 0x01];
+        let reqs: Vec<_> = link_adr_reqs(&cmds).collect();
+        let params = process_link_adr_block(&region, &config, &reqs).unwrap();
+        assert_eq!(params.channel_mask, ChannelMask::new([0xff00, 0, 0, 0, 0]));
+        assert_eq!(params.data_rate, 3);
+
+        let masks = [0x0000, 0x00ff, 0, 0, 0x1ff].map(U16::new);
+        region.apply_cf_list(&mut config, &CFList::ChannelMasks(&masks));
+        assert_eq!(
+            config.channel_mask,
+            ChannelMask::new([0x0000, 0x00ff, 0, 0, 0xff])
+        );
+    }
+}
@@ -0,0 +1,394 @@
+use zerocopy::{little_endian::U32, FromBytes, Immutable, IntoBytes, KnownLayout};
+
+use crate::{
+    crypto::Crypto,
+    frame_counter::FrameCounter,
+    mac_command::uplink::UplinkMacCommmand,
+    phy_payload::mac_payload::downlink::Downlink,
+    types::{AppSKey, DevAddr, NwkSKey},
+    Error,
+};
+
+use super::{FrameCodec, Session, SessionKeys, Version};
+
+/// Frame counters as kept in non-volatile memory.
+///
+/// `f_cnt_up` is written ahead: every FCntUp below it may already have been used. `f_cnt_down`
+/// is the FCntDown expected at the time of the last write, unless FCntDown 2^32 - 1 has been
+/// received.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, FromBytes, IntoBytes, KnownLayout, Immutable,
+)]
+#[repr(C)]
+pub struct StoredCounters {
+    f_cnt_up: U32,
+    f_cnt_down: U32,
+    f_cnt_down_exhausted: u8,
+}
+impl StoredCounters {
+    pub fn new(f_cnt_up: u32, f_cnt_down: u32) -> Self {
+        Self {
+            f_cnt_up: f_cnt_up.into(),
+            f_cnt_down: f_cnt_down.into(),
+            f_cnt_down_exhausted: 0,
+        }
+    }
+    fn with_f_cnt_down(f_cnt_up: u32, f_cnt_down: &FrameCounter) -> Self {
+        Self {
+            f_cnt_down_exhausted: f_cnt_down.is_exhausted().into(),
+            ..Self::new(f_cnt_up, f_cnt_down.next())
+        }
+    }
+    fn f_cnt_down_counter(&self) -> FrameCounter {
+        match self.f_cnt_down_exhausted() {
+            true => FrameCounter::exhausted(),
+            false => FrameCounter::new(self.f_cnt_down()),
+        }
+    }
+    pub fn f_cnt_up(&self) -> u32 {
+        self.f_cnt_up.get()
+    }
+    pub fn f_cnt_down(&self) -> u32 {
+        self.f_cnt_down.get()
+    }
+    /// Whether FCntDown 2^32 - 1 has been received, every downlink is then rejected
+    pub fn f_cnt_down_exhausted(&self) -> bool {
+        self.f_cnt_down_exhausted != 0
+    }
+}
+#[cfg(feature = "defmt")]
+impl defmt::Format for StoredCounters {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(
+            f,
+            "StoredCounters {{ f_cnt_up: {=u32}, f_cnt_down: {=u32}, f_cnt_down_exhausted: {=bool} }}",
+            self.f_cnt_up(),
+            self.f_cnt_down(),
+            self.f_cnt_down_exhausted()
+        )
+    }
+}
+
+/// Non-volatile storage for the frame counters of an ABP device
+pub trait CounterStore {
+    /// Returns `None` if no counters have been stored yet
+    fn load(&mut self) -> Result<Option<StoredCounters>, Error>;
+    fn store(&mut self, counters: &StoredCounters) -> Result<(), Error>;
+}
+
+/// ABP session whose counters survive reboots.
+///
+/// FCntUp is reserved `commit_interval` frames ahead, so the store is written once every
+/// `commit_interval` uplinks and an FCntUp is never reused after a power loss. After a reboot
+/// up to `commit_interval` counter values are skipped. FCntDown is written after every
+/// accepted downlink, so a downlink cannot be replayed after a power loss.
+pub struct AbpSession<C: Crypto, S: CounterStore> {
+    session: Session<C>,
+    store: S,
+    commit_interval: u32,
+    reserved_f_cnt_up: u32,
+}
+
+impl<C, S> AbpSession<C, S>
+where
+    C: Crypto + From<SessionKeys>,
+    S: CounterStore,
+{
+    pub fn activate(
+        version: Version,
+        dev_addr: DevAddr,
+        nwk_s_key: NwkSKey,
+        app_s_key: AppSKey,
+        mut store: S,
+        commit_interval: u32,
+    ) -> Result<Self, Error> {
+        let stored = store.load()?.unwrap_or_default();
+        let mut session = Session::new(version, dev_addr, nwk_s_key, app_s_key);
+        let counters = session.counters_mut();
+        counters.f_cnt_up = FrameCounter::new(stored.f_cnt_up());
+        counters.n_f_cnt_down = stored.f_cnt_down_counter();
+        let mut abp = Self {
+            session,
+            store,
+            commit_interval: commit_interval.max(1),
+            reserved_f_cnt_up: stored.f_cnt_up(),
+        };
+        abp.commit()?;
+        Ok(abp)
+    }
+}
+
+impl<C, S> AbpSession<C, S>
+where
+    C: Crypto,
+    S: CounterStore,
+{
+    fn commit(&mut self) -> Result<(), Error> {
+        let counters = self.session.counters();
+        let reserved = counters
+            .f_cnt_up
+            .next()
+            .saturating_add(self.commit_interval);
+        self.store.store(&StoredCounters::with_f_cnt_down(
+            reserved,
+            &counters.n_f_cnt_down,
+        ))?;
+        self.reserved_f_cnt_up = reserved;
+        Ok(())
+    }
+    pub fn session(&self) -> &Session<C> {
+        &self.session
+    }
+    /// E.g. to enable ADR. Frame counters changed through it are not stored.
+    pub fn session_mut(&mut self) -> &mut Session<C> {
+        &mut self.session
+    }
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+    /// See [`Session::encode_uplink`]. The counters are committed before the first uplink
+    /// of every `commit_interval` uplinks.
+    pub fn encode_uplink<'a>(
+        &mut self,
+        buf: &'a mut [u8],
+        confirmed: bool,
+        f_port: u8,
+        payload: &[u8],
+        mac_cmds: &[UplinkMacCommmand],
+    ) -> Result<&'a [u8], Error> {
+        if self.session.counters().f_cnt_up.next() >= self.reserved_f_cnt_up {
+            self.commit()?;
+        }
+        self.session
+            .encode_uplink(buf, confirmed, f_port, payload, mac_cmds)
+    }
+    /// See [`Session::decode_downlink`]. The FCntDown of an accepted downlink is stored
+    /// before the downlink is returned. If the store fails the downlink is dropped: the
+    /// session is left as before the downlink, which may be received again.
+    pub fn decode_downlink<'a>(&mut self, buf: &'a mut [u8]) -> Result<&'a mut Downlink, Error> {
+        let (f_cnt_down, ack) = (self.session.counters.n_f_cnt_down, self.session.ack);
+        let downlink = self.session.decode_downlink(buf)?;
+        let stored = StoredCounters::with_f_cnt_down(
+            self.reserved_f_cnt_up,
+            &self.session.counters.n_f_cnt_down,
+        );
+        if let Err(e) = self.store.store(&stored) {
+            self.session.counters.n_f_cnt_down = f_cnt_down;
+            self.session.ack = ack;
+            return Err(e);
+        }
+        Ok(downlink)
+    }
+}
+
+impl<C: Crypto, S: CounterStore> FrameCodec for AbpSession<C, S> {
+    fn encode_uplink<'a>(
+        &mut self,
+        buf: &'a mut [u8],
+        confirmed: bool,
+        f_port: u8,
+        payload: &[u8],
+        mac_cmds: &[UplinkMacCommmand],
+    ) -> Result<&'a [u8], Error> {
+        AbpSession::encode_uplink(self, buf, confirmed, f_port, payload, mac_cmds)
+    }
+    fn decode_downlink<'a>(&mut self, buf: &'a mut [u8]) -> Result<&'a mut Downlink, Error> {
+        AbpSession::decode_downlink(self, buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use crate::{
+        class_a::{ClassA, Output},
+        crypto::soft::SoftCrypto,
+        phy_payload::mac_payload::downlink::Downlink,
+        session::RxSettings,
+    };
+
+    use super::*;
+
+    #[derive(Default)]
+    struct MemoryStore {
+        counters: Option<StoredCounters>,
+        writes: usize,
+        /// Number of writes after which writes fail
+        fail_after: Option<usize>,
+    }
+    impl CounterStore for &mut MemoryStore {
+        fn load(&mut self) -> Result<Option<StoredCounters>, Error> {
+            Ok(self.counters)
+        }
+        fn store(&mut self, counters: &StoredCounters) -> Result<(), Error> {
+            if self.fail_after.is_some_and(|n| self.writes >= n) {
+                return Err(Error::Storage);
+            }
+            self.counters = Some(*counters);
+            self.writes += 1;
+            Ok(())
+        }
+    }
+    fn activate(store: &mut MemoryStore) -> AbpSession<SoftCrypto, &mut MemoryStore> {
+        AbpSession::activate(
+            Version::V1_0_4,
+            DevAddr::read_from_bytes(&[4, 3, 2, 1]).unwrap(),
+            NwkSKey::read_from_bytes(&[2; 16]).unwrap(),
+            AppSKey::read_from_bytes(&[1; 16]).unwrap(),
+            store,
+            10,
+        )
+        .unwrap()
+    }
+    #[test]
+    fn commit_every_n_uplinks() {
+        let mut store = MemoryStore::default();
+        let mut abp = activate(&mut store);
+        let mut buf = [0u8; 64];
+        for _ in 0..10 {
+            abp.encode_uplink(&mut buf, false, 1, b"hi", &[]).unwrap();
+        }
+        assert_eq!(abp.store().writes, 1);
+        assert_eq!(abp.store().counters.unwrap().f_cnt_up(), 10);
+        abp.encode_uplink(&mut buf, false, 1, b"hi", &[]).unwrap();
+        assert_eq!(abp.store().writes, 2);
+        assert_eq!(abp.store().counters.unwrap().f_cnt_up(), 20);
+        assert_eq!(abp.session().counters().f_cnt_up.next(), 11);
+    }
+    #[test]
+    fn never_reuse_f_cnt_after_reboot() {
+        let mut store = MemoryStore::default();
+        let mut buf = [0u8; 64];
+        let mut abp = activate(&mut store);
+        for _ in 0..15 {
+            abp.encode_uplink(&mut buf, false, 1, b"hi", &[]).unwrap();
+        }
+        // power loss, the session is lost
+        let mut abp = activate(&mut store);
+        assert_eq!(abp.session().counters().f_cnt_up.next(), 20);
+        let bytes = abp.encode_uplink(&mut buf, false, 1, b"hi", &[]).unwrap();
+        assert_eq!(&bytes[6..8], &[20, 0]);
+        assert_eq!(store.counters.unwrap().f_cnt_up(), 30);
+    }
+    #[test]
+    fn no_downlink_replay_after_reboot() {
+        let downlink = [
+            0xa0, 0x04, 0x03, 0x02, 0x01, 0x80, 0xff, 0x2a, 0x2a, 0x0a, 0xf1, 0xa3, 0x6a, 0x05,
+            0xd0, 0x12, 0x5f, 0x88, 0x5d, 0x88, 0x1d, 0x49, 0xe1,
+        ];
+        let mut store = MemoryStore {
+            counters: Some(StoredCounters::new(0, 0x12a00)),
+            ..Default::default()
+        };
+        let mut abp = activate(&mut store);
+        abp.decode_downlink(&mut downlink.clone()).unwrap();
+        assert_eq!(abp.store().counters.unwrap().f_cnt_down(), 0x12b00);
+        assert_eq!(abp.store().counters.unwrap().f_cnt_up(), 10);
+        // power loss, the session is lost
+        let mut abp = activate(&mut store);
+        assert!(matches!(
+            abp.decode_downlink(&mut downlink.clone()),
+            Err(Error::FCnt {
+                expected: 0x12b00,
+                received: 0x2aff
+            })
+        ));
+    }
+    #[test]
+    fn no_downlink_after_last_f_cnt_down_and_reboot() {
+        // confirmed downlink with FCntDown 2^32 - 1
+        let mut downlink = [
+            0xa0, 0x04, 0x03, 0x02, 0x01, 0x80, 0xff, 0xff, 0x2a, 0x0a, 0xf1, 0xa3, 0x6a, 0x05,
+            0xd0, 0x12, 0x5f, 0x88, 0x5d, 0x88, 0x1d, 0x49, 0xe1,
+        ];
+        let mic = Downlink::from_bytes(&downlink)
+            .unwrap()
+            .calculate_mic(
+                &mut SoftCrypto::new(
+                    NwkSKey::read_from_bytes(&[2; 16]).unwrap(),
+                    AppSKey::read_from_bytes(&[1; 16]).unwrap(),
+                ),
+                u32::MAX,
+                downlink.len(),
+            )
+            .unwrap();
+        downlink[19..].copy_from_slice(mic.as_bytes());
+        let mut store = MemoryStore {
+            counters: Some(StoredCounters::new(0, u32::MAX - 1)),
+            ..Default::default()
+        };
+        let mut abp = activate(&mut store);
+        abp.decode_downlink(&mut downlink.clone()).unwrap();
+        assert!(abp.store().counters.unwrap().f_cnt_down_exhausted());
+        // power loss, the session is lost
+        let mut abp = activate(&mut store);
+        assert!(matches!(
+            abp.decode_downlink(&mut downlink.clone()),
+            Err(Error::FCntExhausted)
+        ));
+        assert!(store.counters.unwrap().f_cnt_down_exhausted());
+    }
+    #[test]
+    fn drop_downlink_on_store_error() {
+        let downlink = [
+            0xa0, 0x04, 0x03, 0x02, 0x01, 0x80, 0xff, 0x2a, 0x2a, 0x0a, 0xf1, 0xa3, 0x6a, 0x05,
+            0xd0, 0x12, 0x5f, 0x88, 0x5d, 0x88, 0x1d, 0x49, 0xe1,
+        ];
+        let mut store = MemoryStore {
+            counters: Some(StoredCounters::new(0, 0x12a00)),
+            fail_after: Some(1),
+            ..Default::default()
+        };
+        let mut abp = activate(&mut store);
+        assert!(matches!(
+            abp.decode_downlink(&mut downlink.clone()),
+            Err(Error::Storage)
+        ));
+        assert_eq!(abp.session().counters().n_f_cnt_down.next(), 0x12a00);
+        assert!(!abp.session().ack_pending());
+        assert_eq!(abp.store().counters.unwrap().f_cnt_down(), 0x12a00);
+    }
+    #[test]
+    fn drive_class_a() {
+        let mut store = MemoryStore::default();
+        let mut abp = activate(&mut store);
+        abp.session_mut().set_adr(true);
+        let rx_settings = RxSettings {
+            rx1_dr_offset: 0,
+            rx2_data_rate: 0,
+            rx_delay: Duration::from_secs(1),
+        };
+        let mut device = ClassA::new(abp, rx_settings);
+        let Output::Transmit { bytes, .. } =
+            device.send(Duration::ZERO, 0, false, 1, b"hi").unwrap()
+        else {
+            panic!("expected a transmission");
+        };
+        // ADR bit of FCtrl
+        assert_eq!(bytes[5], 0x80);
+        assert_eq!(device.session().session().counters().f_cnt_up.next(), 1);
+        assert_eq!(device.session().store().writes, 1);
+    }
+    #[test]
+    fn propagate_store_errors() {
+        struct FailingStore;
+        impl CounterStore for FailingStore {
+            fn load(&mut self) -> Result<Option<StoredCounters>, Error> {
+                Ok(None)
+            }
+            fn store(&mut self, _counters: &StoredCounters) -> Result<(), Error> {
+                Err(Error::Storage)
+            }
+        }
+        let abp = AbpSession::<SoftCrypto, _>::activate(
+            Version::V1_0_4,
+            DevAddr::read_from_bytes(&[4, 3, 2, 1]).unwrap(),
+            NwkSKey::read_from_bytes(&[2; 16]).unwrap(),
+            AppSKey::read_from_bytes(&[1; 16]).unwrap(),
+            FailingStore,
+            10,
+        );
+        assert!(matches!(abp, Err(Error::Storage)));
+    }
+}
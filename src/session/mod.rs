@@ -0,0 +1,423 @@
+use core::time::Duration;
+
+use zerocopy::{
+    little_endian::U32, FromBytes, Immutable, IntoBytes, KnownLayout, TryFromBytes, Unaligned,
+};
+
+use crate::{
+    crypto::Crypto,
+    frame_counter::{FrameCounter, FrameCounters},
+    mac_command::uplink::{encode_maccommands, UplinkMacCommmand},
+    phy_payload::mac_payload::{downlink::Downlink, uplink::Uplink, FRMPayload, FHDR},
+    types::{AppSKey, DevAddr, NwkSKey},
+    Error,
+};
+pub mod abp;
+pub mod otaa;
+
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, TryFromBytes, IntoBytes, KnownLayout, Immutable, Unaligned,
+)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum Version {
+    /// LoRaWAN 1.0.0 to 1.0.3
+    V1_0 = 0,
+    /// LoRaWAN 1.0.4
+    V1_0_4 = 1,
+}
+
+#[derive(Clone, Copy, FromBytes, IntoBytes, KnownLayout, Immutable)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(C)]
+pub struct SessionKeys {
+    pub nwk_s_key: NwkSKey,
+    pub app_s_key: AppSKey,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RxWindow {
+    Rx1,
+    Rx2,
+}
+
+/// Receive window parameters of a session
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RxSettings {
+    pub rx1_dr_offset: u8,
+    pub rx2_data_rate: u8,
+    /// Delay between the end of an uplink and RX1, RX2 opens a second later
+    pub rx_delay: Duration,
+}
+
+/// Everything needed to resume a session, e.g. after deep sleep, including a pending ACK.
+/// Store it with `as_bytes` and restore it with `try_read_from_bytes`.
+#[derive(Clone, Copy, TryFromBytes, IntoBytes, KnownLayout, Immutable)]
+#[repr(C)]
+pub struct SessionState {
+    version: Version,
+    dev_addr: DevAddr,
+    keys: SessionKeys,
+    f_cnt_up: U32,
+    f_cnt_down: U32,
+    adr: bool,
+    f_cnt_down_exhausted: bool,
+    ack: bool,
+}
+#[cfg(feature = "defmt")]
+impl defmt::Format for SessionState {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(
+            f,
+            "SessionState {{ version: {}, dev_addr: {}, keys: {}, f_cnt_up: {=u32}, f_cnt_down: {=u32}, adr: {=bool}, f_cnt_down_exhausted: {=bool}, ack: {=bool} }}",
+            self.version,
+            self.dev_addr,
+            self.keys,
+            self.f_cnt_up.get(),
+            self.f_cnt_down.get(),
+            self.adr,
+            self.f_cnt_down_exhausted,
+            self.ack
+        )
+    }
+}
+
+/// Builds the uplinks and verifies the downlinks of an activated session, see [`Session`]
+/// and [`abp::AbpSession`]
+pub trait FrameCodec {
+    /// See [`Session::encode_uplink`]
+    fn encode_uplink<'a>(
+        &mut self,
+        buf: &'a mut [u8],
+        confirmed: bool,
+        f_port: u8,
+        payload: &[u8],
+        mac_cmds: &[UplinkMacCommmand],
+    ) -> Result<&'a [u8], Error>;
+    /// See [`Session::decode_downlink`]
+    fn decode_downlink<'a>(&mut self, buf: &'a mut [u8]) -> Result<&'a mut Downlink, Error>;
+}
+
+/// Activated session, either by ABP or by a join procedure.
+///
+/// Frames are built and verified with the session keys and counters; the ACK bit of the next
+/// uplink is set after a confirmed downlink.
+pub struct Session<C: Crypto> {
+    version: Version,
+    dev_addr: DevAddr,
+    keys: SessionKeys,
+    crypto: C,
+    counters: FrameCounters,
+    adr: bool,
+    ack: bool,
+    adr_ack_req: bool,
+}
+
+impl<C> Session<C>
+where
+    C: Crypto + From<SessionKeys>,
+{
+    pub fn new(
+        version: Version,
+        dev_addr: DevAddr,
+        nwk_s_key: NwkSKey,
+        app_s_key: AppSKey,
+    ) -> Self {
+        let keys = SessionKeys {
+            nwk_s_key,
+            app_s_key,
+        };
+        Self {
+            version,
+            dev_addr,
+            keys,
+            crypto: C::from(keys),
+            counters: FrameCounters::default(),
+            adr: false,
+            ack: false,
+            adr_ack_req: false,
+        }
+    }
+    pub fn restore(state: &SessionState) -> Self {
+        let mut session = Self::new(
+            state.version,
+            state.dev_addr,
+            state.keys.nwk_s_key,
+            state.keys.app_s_key,
+        );
+        session.counters.f_cnt_up = FrameCounter::new(state.f_cnt_up.get());
+        session.counters.n_f_cnt_down = match state.f_cnt_down_exhausted {
+            true => FrameCounter::exhausted(),
+            false => FrameCounter::new(state.f_cnt_down.get()),
+        };
+        session.adr = state.adr;
+        session.ack = state.ack;
+        session
+    }
+}
+
+impl<C: Crypto> Session<C> {
+    pub fn state(&self) -> SessionState {
+        SessionState {
+            version: self.version,
+            dev_addr: self.dev_addr,
+            keys: self.keys,
+            f_cnt_up: self.counters.f_cnt_up.next().into(),
+            f_cnt_down: self.counters.n_f_cnt_down.next().into(),
+            adr: self.adr,
+            f_cnt_down_exhausted: self.counters.n_f_cnt_down.is_exhausted(),
+            ack: self.ack,
+        }
+    }
+    pub fn version(&self) -> Version {
+        self.version
+    }
+    pub fn dev_addr(&self) -> DevAddr {
+        self.dev_addr
+    }
+    /// FCntDown is held in `n_f_cnt_down`
+    pub fn counters(&self) -> &FrameCounters {
+        &self.counters
+    }
+    pub fn counters_mut(&mut self) -> &mut FrameCounters {
+        &mut self.counters
+    }
+    pub fn adr(&self) -> bool {
+        self.adr
+    }
+    pub fn set_adr(&mut self, adr: bool) {
+        self.adr = adr
+    }
+    /// Sets ADRACKReq in the next uplinks if ADR is enabled, see
+    /// [`crate::device::adr_backoff::AdrBackoff`]
+    pub fn set_adr_ack_req(&mut self, adr_ack_req: bool) {
+        self.adr_ack_req = adr_ack_req
+    }
+    /// Whether the next uplink acknowledges a confirmed downlink
+    pub fn ack_pending(&self) -> bool {
+        self.ack
+    }
+
+    /// Builds an uplink into `buf`.
+    ///
+    /// With `f_port` 0 the MAC commands are sent encrypted in FRMPayload and `payload` must be
+    /// empty, otherwise they are sent in FOpts. An empty `payload` on a non-zero `f_port`
+    /// produces a frame without FPort.
+    pub fn encode_uplink<'a>(
+        &mut self,
+        buf: &'a mut [u8],
+        confirmed: bool,
+        f_port: u8,
+        payload: &[u8],
+        mac_cmds: &[UplinkMacCommmand],
+    ) -> Result<&'a [u8], Error> {
+        let mut f_opts_buf = [0u8; 15];
+        let mac_cmds_len = mac_cmds.iter().map(|cmd| cmd.as_bytes().len() + 1).sum();
+        let (f_opts, data_len) = if f_port == 0 {
+            if !payload.is_empty() {
+                return Err(Error::FPort(Some(0)));
+            }
+            (&[][..], mac_cmds_len)
+        } else {
+            let f_opts =
+                encode_maccommands(mac_cmds, &mut f_opts_buf).map_err(|_| Error::FOptsLen {
+                    f_opts_len: mac_cmds_len,
+                    available: 15,
+                })?;
+            (f_opts, payload.len())
+        };
+        let frm_payload_len = if data_len > 0 { 1 + data_len } else { 0 };
+        // mhdr + fhdr + f_opts + payload + mic
+        let len = 8 + f_opts.len() + frm_payload_len + 4;
+        if buf.len() < len {
+            return Err(Error::Size {
+                expected: len,
+                actual: buf.len(),
+            });
+        }
+        let fhdr = FHDR::new(self.dev_addr, self.adr, self.ack, false, 0, f_opts)?
+            .with_adr_ack_req(self.adr && self.adr_ack_req);
+        let uplink = Uplink::new(buf, confirmed, fhdr)?;
+        let bytes =
+            uplink.build_with_counter(&mut self.counters.f_cnt_up, &mut self.crypto, |buf| {
+                if frm_payload_len == 0 {
+                    Ok(None)
+                } else if f_port == 0 {
+                    FRMPayload::new_from_maccommands(buf, mac_cmds).map(Some)
+                } else {
+                    FRMPayload::new_from_slice(buf, f_port, payload).map(Some)
+                }
+            })?;
+        self.ack = false;
+        Ok(bytes)
+    }
+
+    /// Verifies and decrypts a downlink addressed to this session
+    pub fn decode_downlink<'a>(&mut self, buf: &'a mut [u8]) -> Result<&'a mut Downlink, Error> {
+        let downlink = Downlink::from_bytes(buf)?;
+        if downlink.dev_addr() != self.dev_addr {
+            return Err(Error::DevAddr);
+        }
+        let downlink = Downlink::new_from_encrypted_with_counter(
+            buf,
+            &mut self.counters.n_f_cnt_down,
+            &mut self.crypto,
+        )?;
+        self.ack = downlink.confirmed();
+        Ok(downlink)
+    }
+}
+
+impl<C: Crypto> FrameCodec for Session<C> {
+    fn encode_uplink<'a>(
+        &mut self,
+        buf: &'a mut [u8],
+        confirmed: bool,
+        f_port: u8,
+        payload: &[u8],
+        mac_cmds: &[UplinkMacCommmand],
+    ) -> Result<&'a [u8], Error> {
+        Session::encode_uplink(self, buf, confirmed, f_port, payload, mac_cmds)
+    }
+    fn decode_downlink<'a>(&mut self, buf: &'a mut [u8]) -> Result<&'a mut Downlink, Error> {
+        Session::decode_downlink(self, buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use zerocopy::FromBytes;
+
+    use crate::{
+        crypto::soft::SoftCrypto,
+        mac_command::uplink::{LinkADRAns, LinkAdrAnsStatus},
+    };
+
+    use super::*;
+    fn get_session() -> Session<SoftCrypto> {
+        Session::new(
+            Version::V1_0,
+            DevAddr::read_from_bytes(&[4, 3, 2, 1]).unwrap(),
+            NwkSKey::read_from_bytes(&[2; 16]).unwrap(),
+            AppSKey::read_from_bytes(&[1; 16]).unwrap(),
+        )
+    }
+    fn confirmed_downlink() -> [u8; 23] {
+        [
+            0xa0, 0x04, 0x03, 0x02, 0x01, 0x80, 0xff, 0x2a, 0x2a, 0x0a, 0xf1, 0xa3, 0x6a, 0x05,
+            0xd0, 0x12, 0x5f, 0x88, 0x5d, 0x88, 0x1d, 0x49, 0xe1,
+        ]
+    }
+    #[test]
+    fn encode_session_uplink() {
+        let mut session = get_session();
+        session.set_adr(true);
+        let mut buf = [0u8; 64];
+        let cmds = [UplinkMacCommmand::LinkADRAns(LinkADRAns {
+            status: LinkAdrAnsStatus::new().with_power_ack(true),
+        })];
+        let bytes = session
+            .encode_uplink(&mut buf, false, 1, b"hi", &cmds)
+            .unwrap();
+        assert_eq!(
+            bytes,
+            &[
+                0x40, 0x04, 0x03, 0x02, 0x01, 0x82, 0x00, 0x00, 0x03, 0x04, 0x01, 0x03, 0x5c, 0x77,
+                0x35, 0x2a, 0x56
+            ]
+        );
+        assert_eq!(session.counters().f_cnt_up.next(), 1);
+        let bytes = session
+            .encode_uplink(&mut buf, false, 0, &[], &cmds)
+            .unwrap();
+        assert_eq!(
+            bytes,
+            &[
+                0x40, 0x04, 0x03, 0x02, 0x01, 0x80, 0x01, 0x00, 0x00, 0x9c, 0x8c, 0xc8, 0xa8, 0x3b,
+                0xbb
+            ]
+        );
+        assert!(session
+            .encode_uplink(&mut buf, false, 0, b"hi", &cmds)
+            .is_err());
+        assert!(session
+            .encode_uplink(&mut buf[..16], false, 1, b"hello", &[])
+            .is_err());
+    }
+    #[test]
+    fn acknowledge_confirmed_downlink() {
+        let mut session = get_session();
+        session.counters_mut().n_f_cnt_down = FrameCounter::new(0x12A00);
+        let mut packet = confirmed_downlink();
+        let downlink = session.decode_downlink(&mut packet).unwrap();
+        assert_eq!(&downlink.frm_payload().unwrap().data[..10], b"hello lora");
+        assert!(session.ack_pending());
+        let mut buf = [0u8; 64];
+        let bytes = session.encode_uplink(&mut buf, false, 1, &[], &[]).unwrap();
+        assert_eq!(bytes[5], 0x20);
+        assert!(!session.ack_pending());
+
+        session.set_adr_ack_req(true);
+        let bytes = session.encode_uplink(&mut buf, false, 1, &[], &[]).unwrap();
+        assert_eq!(bytes[5], 0x00);
+        session.set_adr(true);
+        let bytes = session.encode_uplink(&mut buf, false, 1, &[], &[]).unwrap();
+        assert_eq!(bytes[5], 0xc0);
+    }
+    #[test]
+    fn reject_foreign_downlink() {
+        let mut session = Session::<SoftCrypto>::new(
+            Version::V1_0,
+            DevAddr::read_from_bytes(&[1, 2, 3, 4]).unwrap(),
+            NwkSKey::read_from_bytes(&[2; 16]).unwrap(),
+            AppSKey::read_from_bytes(&[1; 16]).unwrap(),
+        );
+        let mut packet = confirmed_downlink();
+        assert!(matches!(
+            session.decode_downlink(&mut packet),
+            Err(Error::DevAddr)
+        ));
+    }
+    #[test]
+    fn persist_session() {
+        let mut session = get_session();
+        session.set_adr(true);
+        let mut buf = [0u8; 64];
+        session
+            .encode_uplink(&mut buf, false, 1, b"hi", &[])
+            .unwrap();
+        session.counters_mut().n_f_cnt_down = FrameCounter::new(0x12A00);
+        let state = session.state();
+        let stored = state.as_bytes();
+        assert_eq!(stored.len(), 48);
+
+        let state = SessionState::try_read_from_bytes(stored).unwrap();
+        let mut session = Session::<SoftCrypto>::restore(&state);
+        assert_eq!(session.version(), Version::V1_0);
+        assert_eq!(session.counters().f_cnt_up.next(), 1);
+        assert!(session.adr());
+        let mut packet = confirmed_downlink();
+        assert!(session.decode_downlink(&mut packet).is_ok());
+        let session = Session::<SoftCrypto>::restore(&session.state());
+        assert!(session.ack_pending());
+
+        let mut invalid = [0u8; 48];
+        invalid.copy_from_slice(stored);
+        invalid[0] = 0xff;
+        assert!(SessionState::try_read_from_bytes(&invalid).is_err());
+    }
+    #[test]
+    fn persist_exhausted_f_cnt_down() {
+        let mut session = get_session();
+        session.counters_mut().n_f_cnt_down = FrameCounter::new(u32::MAX - 1);
+        session.counters_mut().n_f_cnt_down.accept(u32::MAX);
+        let state = session.state();
+        let state = SessionState::try_read_from_bytes(state.as_bytes()).unwrap();
+        let session = Session::<SoftCrypto>::restore(&state);
+        let counter = session.counters().n_f_cnt_down;
+        assert!(counter.is_exhausted());
+        assert_eq!(counter.reconstruct(0xffff), Err(Error::FCntExhausted));
+    }
+}
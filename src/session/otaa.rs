@@ -0,0 +1,394 @@
+//! Over-the-air activation.
+//!
+//! [`Join`] drives the join procedure without doing any I/O: it returns the frames to
+//! transmit and the receive windows to open, and consumes the end of transmission, the
+//! frames received and the end of the receive windows.
+use core::time::Duration;
+
+use zerocopy::{
+    little_endian::{U16, U32},
+    transmute, FromBytes, Immutable, IntoBytes, KnownLayout,
+};
+
+use crate::{
+    crypto::{Crypto, Encrypter as _, Key},
+    phy_payload::{
+        join_accept::{CFList, JoinAccept},
+        join_request::JoinRequest,
+    },
+    types::{AppKey, DevEui, DevNonce, JoinEui},
+    Error,
+};
+
+use super::{RxSettings, RxWindow, Session, SessionKeys, Version};
+
+pub const JOIN_ACCEPT_DELAY1: Duration = Duration::from_secs(5);
+pub const JOIN_ACCEPT_DELAY2: Duration = Duration::from_secs(6);
+
+/// Nonces as kept in non-volatile memory.
+///
+/// `dev_nonce` is written ahead: it is the DevNonce of the next join-request. `join_nonce` is
+/// the lowest JoinNonce accepted in the next join-accept.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, FromBytes, IntoBytes, KnownLayout, Immutable,
+)]
+#[repr(C)]
+pub struct StoredNonces {
+    dev_nonce: U16,
+    join_nonce: U32,
+}
+impl StoredNonces {
+    pub fn new(dev_nonce: u16, join_nonce: u32) -> Self {
+        Self {
+            dev_nonce: dev_nonce.into(),
+            join_nonce: join_nonce.into(),
+        }
+    }
+    pub fn dev_nonce(&self) -> u16 {
+        self.dev_nonce.get()
+    }
+    pub fn join_nonce(&self) -> u32 {
+        self.join_nonce.get()
+    }
+}
+#[cfg(feature = "defmt")]
+impl defmt::Format for StoredNonces {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(
+            f,
+            "StoredNonces {{ dev_nonce: {=u16}, join_nonce: {=u32} }}",
+            self.dev_nonce(),
+            self.join_nonce()
+        )
+    }
+}
+
+/// Non-volatile storage for the nonces of an OTAA device
+pub trait NonceStore {
+    /// Returns `None` if no nonces have been stored yet
+    fn load(&mut self) -> Result<Option<StoredNonces>, Error>;
+    fn store(&mut self, nonces: &StoredNonces) -> Result<(), Error>;
+}
+
+#[derive(Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum JoinAction<'a> {
+    /// Transmit `bytes` at `at`, then report the end of the transmission with
+    /// [`Join::tx_done`]
+    Transmit { bytes: &'a [u8], at: Duration },
+    /// Open `window` at `at`, then pass the frames received to [`Join::received`] and report
+    /// the end of the window with [`Join::rx_timeout`]
+    Receive { window: RxWindow, at: Duration },
+}
+
+/// Result of a successful join procedure
+pub struct Joined<'a, C: Crypto> {
+    pub session: Session<C>,
+    pub rx_settings: RxSettings,
+    pub cf_list: Option<CFList<'a>>,
+}
+
+enum State {
+    Idle,
+    Transmitting,
+    Rx1 { tx_done: Duration },
+    Rx2,
+}
+
+/// OTAA join procedure of a LoRaWAN 1.0.x end-device.
+///
+/// The DevNonce is a counter that is stored before every join-request, so it is never reused.
+/// With [`Version::V1_0_4`] a join-accept is only accepted if its JoinNonce is higher than
+/// the one of the last join-accept.
+pub struct Join<C: Crypto, S: NonceStore> {
+    version: Version,
+    join_eui: JoinEui,
+    dev_eui: DevEui,
+    app_key: AppKey,
+    crypto: C,
+    store: S,
+    nonces: StoredNonces,
+    dev_nonce: DevNonce,
+    state: State,
+    buf: [u8; 23],
+}
+
+impl<C, S> Join<C, S>
+where
+    C: Crypto,
+    S: NonceStore,
+{
+    pub fn new(
+        version: Version,
+        join_eui: JoinEui,
+        dev_eui: DevEui,
+        app_key: AppKey,
+        crypto: C,
+        mut store: S,
+    ) -> Result<Self, Error> {
+        let nonces = store.load()?.unwrap_or_default();
+        Ok(Self {
+            version,
+            join_eui,
+            dev_eui,
+            app_key,
+            crypto,
+            store,
+            nonces,
+            dev_nonce: DevNonce::from(0),
+            state: State::Idle,
+            buf: [0; 23],
+        })
+    }
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+    /// DevNonce of the next join-request
+    pub fn next_dev_nonce(&self) -> u16 {
+        self.nonces.dev_nonce()
+    }
+
+    /// Starts a join attempt with a new DevNonce, abandoning any attempt in progress.
+    ///
+    /// Fails with [`Error::Nonce`] once all DevNonces have been used.
+    pub fn start(&mut self, now: Duration) -> Result<JoinAction<'_>, Error> {
+        let dev_nonce = self.nonces.dev_nonce();
+        let next = dev_nonce.checked_add(1).ok_or(Error::Nonce)?;
+        let nonces = StoredNonces::new(next, self.nonces.join_nonce());
+        self.store.store(&nonces)?;
+        self.nonces = nonces;
+        self.dev_nonce = DevNonce::from(dev_nonce);
+        let join_request = JoinRequest::new(self.join_eui, self.dev_eui, self.dev_nonce);
+        let bytes = join_request.build(&mut self.buf, &mut self.crypto, &self.app_key)?;
+        self.state = State::Transmitting;
+        Ok(JoinAction::Transmit { bytes, at: now })
+    }
+    /// The join-request was transmitted until `at`. Returns the RX1 window to open.
+    pub fn tx_done(&mut self, at: Duration) -> Option<JoinAction<'_>> {
+        let State::Transmitting = self.state else {
+            return None;
+        };
+        self.state = State::Rx1 { tx_done: at };
+        Some(JoinAction::Receive {
+            window: RxWindow::Rx1,
+            at: at + JOIN_ACCEPT_DELAY1,
+        })
+    }
+    /// The current receive window closed without a valid join-accept. Returns the RX2
+    /// window to open after RX1, or `None` once the attempt failed and [`Self::start`] should
+    /// be called again.
+    pub fn rx_timeout(&mut self) -> Option<JoinAction<'_>> {
+        match self.state {
+            State::Rx1 { tx_done } => {
+                self.state = State::Rx2;
+                Some(JoinAction::Receive {
+                    window: RxWindow::Rx2,
+                    at: tx_done + JOIN_ACCEPT_DELAY2,
+                })
+            }
+            _ => {
+                self.state = State::Idle;
+                None
+            }
+        }
+    }
+    /// Accepts a frame received in RX1 or RX2.
+    ///
+    /// On error the frame is discarded and the receive window stays open.
+    pub fn received<'a>(&mut self, buf: &'a mut [u8]) -> Result<Joined<'a, C>, Error>
+    where
+        C: From<SessionKeys>,
+    {
+        let (State::Rx1 { .. } | State::Rx2) = self.state else {
+            return Err(Error::State);
+        };
+        let join_accept = JoinAccept::new_from_encrypted(buf, &mut self.crypto, &self.app_key)?;
+        let join_nonce = join_accept.join_nonce();
+        if self.version == Version::V1_0_4 {
+            if join_nonce < self.nonces.join_nonce() {
+                return Err(Error::Nonce);
+            }
+            let nonces = StoredNonces::new(self.nonces.dev_nonce(), join_nonce + 1);
+            self.store.store(&nonces)?;
+            self.nonces = nonces;
+        }
+        let keys = session_keys(&mut self.crypto, &self.app_key, join_accept, self.dev_nonce);
+        self.state = State::Idle;
+        let dl_settings = join_accept.dl_settings();
+        Ok(Joined {
+            session: Session::new(
+                self.version,
+                join_accept.dev_addr(),
+                keys.nwk_s_key,
+                keys.app_s_key,
+            ),
+            rx_settings: RxSettings {
+                rx1_dr_offset: dl_settings.rx1_dr_offset(),
+                rx2_data_rate: dl_settings.rx2_dr(),
+                rx_delay: Duration::from_secs(join_accept.rx_delay().into()),
+            },
+            cf_list: join_accept.cf_list(),
+        })
+    }
+}
+
+/// Derives the session keys of a LoRaWAN 1.0.x end-device from a join-accept
+pub fn session_keys<C: Crypto>(
+    crypto: &mut C,
+    app_key: &AppKey,
+    join_accept: &JoinAccept,
+    dev_nonce: DevNonce,
+) -> SessionKeys {
+    let mut block = [0u8; 16];
+    block[1..4].copy_from_slice(&join_accept.join_nonce().to_le_bytes()[..3]);
+    block[4..7].copy_from_slice(&join_accept.net_id().to_le_bytes()[..3]);
+    block[7..9].copy_from_slice(dev_nonce.as_bytes());
+    let mut derive = |prefix| {
+        let mut block = block;
+        block[0] = prefix;
+        crypto
+            .get_encrypter(Key::Raw(transmute!(*app_key)))
+            .encrypt_block(&mut block);
+        block
+    };
+    SessionKeys {
+        nwk_s_key: transmute!(derive(0x01)),
+        app_s_key: transmute!(derive(0x02)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        crypto::soft::SoftCrypto,
+        types::{AppSKey, NwkSKey},
+    };
+
+    use super::*;
+
+    #[derive(Default)]
+    struct MemoryStore {
+        nonces: Option<StoredNonces>,
+    }
+    impl NonceStore for &mut MemoryStore {
+        fn load(&mut self) -> Result<Option<StoredNonces>, Error> {
+            Ok(self.nonces)
+        }
+        fn store(&mut self, nonces: &StoredNonces) -> Result<(), Error> {
+            self.nonces = Some(*nonces);
+            Ok(())
+        }
+    }
+    fn get_join(store: &mut MemoryStore) -> Join<SoftCrypto, &mut MemoryStore> {
+        Join::new(
+            Version::V1_0_4,
+            JoinEui::read_from_bytes(&[0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08]).unwrap(),
+            DevEui::read_from_bytes(&[0x10, 0x20, 0x30, 0x40, 0x50, 0x60, 0x70, 0x80]).unwrap(),
+            AppKey::read_from_bytes(&core::array::from_fn::<u8, 16, _>(|i| i as u8)).unwrap(),
+            SoftCrypto::new(
+                NwkSKey::read_from_bytes(&[0; 16]).unwrap(),
+                AppSKey::read_from_bytes(&[0; 16]).unwrap(),
+            ),
+            store,
+        )
+        .unwrap()
+    }
+    fn join_accept() -> [u8; 17] {
+        [
+            0x20, 0x55, 0x25, 0xdd, 0x4c, 0x27, 0xc7, 0x82, 0xa9, 0xe9, 0xb4, 0x0b, 0x47, 0x38,
+            0xfc, 0x84, 0xbc,
+        ]
+    }
+    #[test]
+    fn join() {
+        let mut store = MemoryStore::default();
+        let mut join = get_join(&mut store);
+        assert_eq!(
+            join.start(Duration::from_secs(10)).unwrap(),
+            JoinAction::Transmit {
+                bytes: &[
+                    0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x10, 0x20, 0x30, 0x40,
+                    0x50, 0x60, 0x70, 0x80, 0x00, 0x00, 0x90, 0x29, 0xa2, 0xe8
+                ],
+                at: Duration::from_secs(10)
+            }
+        );
+        assert_eq!(
+            join.tx_done(Duration::from_secs(11)),
+            Some(JoinAction::Receive {
+                window: RxWindow::Rx1,
+                at: Duration::from_secs(16)
+            })
+        );
+        assert!(join.received(&mut [0x40; 17]).is_err());
+        assert_eq!(
+            join.rx_timeout(),
+            Some(JoinAction::Receive {
+                window: RxWindow::Rx2,
+                at: Duration::from_secs(17)
+            })
+        );
+        let mut packet = join_accept();
+        let joined = join.received(&mut packet).unwrap();
+        assert_eq!(
+            joined.session.dev_addr().as_bytes(),
+            &[0x04, 0x03, 0x02, 0x01]
+        );
+        assert_eq!(
+            joined.rx_settings,
+            RxSettings {
+                rx1_dr_offset: 1,
+                rx2_data_rate: 3,
+                rx_delay: Duration::from_secs(1),
+            }
+        );
+        assert!(joined.cf_list.is_none());
+        let state = joined.session.state();
+        assert_eq!(
+            &state.as_bytes()[5..37],
+            &[
+                0x35, 0x74, 0xcd, 0x94, 0xc0, 0xa1, 0x47, 0xa9, 0xcc, 0xd7, 0x6c, 0xe0, 0x00, 0x03,
+                0x94, 0xc9, 0xa9, 0xe9, 0x6b, 0x55, 0x52, 0xbf, 0x79, 0x9b, 0x18, 0x6f, 0x92, 0xcc,
+                0xcd, 0x98, 0x9b, 0x1e
+            ]
+        );
+        assert_eq!(join.store().nonces, Some(StoredNonces::new(1, 0x030202)));
+        assert_eq!(join.rx_timeout(), None);
+    }
+    #[test]
+    fn retry_with_new_dev_nonce() {
+        let mut store = MemoryStore::default();
+        let mut join = get_join(&mut store);
+        join.start(Duration::ZERO).unwrap();
+        join.tx_done(Duration::from_secs(1)).unwrap();
+        join.rx_timeout().unwrap();
+        assert_eq!(join.rx_timeout(), None);
+        assert!(join.received(&mut join_accept()).is_err());
+        // reboot
+        let mut join = get_join(&mut store);
+        assert_eq!(join.next_dev_nonce(), 1);
+        let JoinAction::Transmit { bytes, .. } = join.start(Duration::ZERO).unwrap() else {
+            panic!("expected a join-request");
+        };
+        assert_eq!(&bytes[17..19], &[0x01, 0x00]);
+        assert_eq!(store.nonces.unwrap().dev_nonce(), 2);
+    }
+    #[test]
+    fn reject_replayed_join_accept() {
+        let mut store = MemoryStore {
+            nonces: Some(StoredNonces::new(7, 0x030202)),
+        };
+        let mut join = get_join(&mut store);
+        join.start(Duration::ZERO).unwrap();
+        join.tx_done(Duration::ZERO).unwrap();
+        assert!(matches!(
+            join.received(&mut join_accept()),
+            Err(Error::Nonce)
+        ));
+        let mut store = MemoryStore {
+            nonces: Some(StoredNonces::new(u16::MAX, 0)),
+        };
+        let mut join = get_join(&mut store);
+        assert!(matches!(join.start(Duration::ZERO), Err(Error::Nonce)));
+    }
+}
@@ -0,0 +1,99 @@
+//! Hex representation of identifiers and keys
+use core::fmt;
+
+/// Lowercase hex of `bytes`, last byte first if `reversed`, e.g. for the LSB-first on-air order
+/// of EUIs and DevAddr
+pub(crate) struct Hex<'a> {
+    bytes: &'a [u8],
+    reversed: bool,
+    separator: Option<char>,
+}
+
+impl<'a> Hex<'a> {
+    pub(crate) fn new(bytes: &'a [u8], reversed: bool) -> Self {
+        Self {
+            bytes,
+            reversed,
+            separator: None,
+        }
+    }
+
+    /// Writes `separator` between bytes, e.g. `'-'` or `':'` for EUI-64 notation
+    pub(crate) fn separated(self, separator: char) -> Self {
+        Self {
+            separator: Some(separator),
+            ..self
+        }
+    }
+}
+
+impl fmt::Display for Hex<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut first = true;
+        let mut write = |byte: &u8| {
+            if let Some(separator) = self.separator.filter(|_| !first) {
+                write!(f, "{separator}")?;
+            }
+            first = false;
+            write!(f, "{byte:02x}")
+        };
+        if self.reversed {
+            self.bytes.iter().rev().try_for_each(&mut write)
+        } else {
+            self.bytes.iter().try_for_each(&mut write)
+        }
+    }
+}
+
+/// Parses `2 * N` hex digits, or `N` pairs of digits all separated by either `'-'` or `':'`,
+/// the first digits giving the last byte if `reversed`
+pub(crate) fn decode<const N: usize>(s: &str, reversed: bool) -> Option<[u8; N]> {
+    let s = s.as_bytes();
+    let (step, separator) = if s.len() == 2 * N {
+        (2, None)
+    } else if s.len() + 1 == 3 * N {
+        (3, s.get(2).copied())
+    } else {
+        return None;
+    };
+    if !matches!(separator, None | Some(b'-' | b':')) {
+        return None;
+    }
+    let mut bytes = [0u8; N];
+    for (i, chunk) in s.chunks(step).enumerate() {
+        let (pair, rest) = chunk.split_at(2);
+        if rest.iter().any(|&c| Some(c) != separator) {
+            return None;
+        }
+        let byte = (digit(pair[0])? << 4) | digit(pair[1])?;
+        bytes[if reversed { N - 1 - i } else { i }] = byte;
+    }
+    Some(bytes)
+}
+
+fn digit(c: u8) -> Option<u8> {
+    match c {
+        b'0'..=b'9' => Some(c - b'0'),
+        b'a'..=b'f' => Some(c - b'a' + 10),
+        b'A'..=b'F' => Some(c - b'A' + 10),
+        _ => None,
+    }
+}
+
+/// Deserializes a string through the `FromStr` of `T`
+#[cfg(feature = "serde")]
+pub(crate) struct FromStrVisitor<T>(pub(crate) core::marker::PhantomData<T>);
+
+#[cfg(feature = "serde")]
+impl<T: core::str::FromStr> serde::de::Visitor<'_> for FromStrVisitor<T> {
+    type Value = T;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "a hex string")
+    }
+
+    fn visit_str<E: serde::de::Error>(self, s: &str) -> Result<Self::Value, E> {
+        s.parse()
+            .map_err(|_| E::invalid_value(serde::de::Unexpected::Str(s), &self))
+    }
+}
@@ -1,28 +1,338 @@
+use core::{fmt, str::FromStr};
 use zerocopy::{FromBytes, Immutable, IntoBytes, KnownLayout};
+
+use crate::Error;
+
+pub(crate) mod hex;
+
 #[allow(dead_code)]
-#[derive(Clone, Copy, IntoBytes, FromBytes, KnownLayout, Immutable)]
+#[derive(PartialEq, Eq, Clone, Copy, IntoBytes, FromBytes, KnownLayout, Immutable)]
 pub struct DevEui([u8; 8]);
 
 #[allow(dead_code)]
-#[derive(Clone, Copy, IntoBytes, FromBytes, KnownLayout, Immutable)]
+#[derive(PartialEq, Eq, Clone, Copy, IntoBytes, FromBytes, KnownLayout, Immutable)]
 pub struct JoinEui([u8; 8]);
 
 #[allow(dead_code)]
-#[derive(Clone, Copy, IntoBytes, FromBytes, KnownLayout, Immutable)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, IntoBytes, FromBytes, KnownLayout, Immutable)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct DevNonce([u8; 2]);
+impl From<u16> for DevNonce {
+    fn from(dev_nonce: u16) -> Self {
+        Self(dev_nonce.to_le_bytes())
+    }
+}
+impl From<DevNonce> for u16 {
+    fn from(dev_nonce: DevNonce) -> Self {
+        u16::from_le_bytes(dev_nonce.0)
+    }
+}
 
 #[allow(dead_code)]
-#[derive(Clone, Copy, IntoBytes, FromBytes, KnownLayout, Immutable)]
+#[derive(PartialEq, Eq, Clone, Copy, IntoBytes, FromBytes, KnownLayout, Immutable)]
 pub struct DevAddr([u8; 4]);
 
 #[allow(dead_code)]
 #[derive(PartialEq, Eq, Clone, Copy, IntoBytes, FromBytes, KnownLayout, Immutable)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct MIC([u8; 4]);
 
 #[allow(dead_code)]
-#[derive(Clone, Copy, IntoBytes, FromBytes, KnownLayout, Immutable)]
+#[derive(PartialEq, Eq, Clone, Copy, IntoBytes, FromBytes, KnownLayout, Immutable)]
 pub struct NwkSKey([u8; 16]);
 
 #[allow(dead_code)]
-#[derive(Clone, Copy, IntoBytes, FromBytes, KnownLayout, Immutable)]
+#[derive(PartialEq, Eq, Clone, Copy, IntoBytes, FromBytes, KnownLayout, Immutable)]
 pub struct AppSKey([u8; 16]);
+
+#[allow(dead_code)]
+#[derive(PartialEq, Eq, Clone, Copy, IntoBytes, FromBytes, KnownLayout, Immutable)]
+pub struct AppKey([u8; 16]);
+
+#[allow(dead_code)]
+#[derive(PartialEq, Eq, Clone, Copy, IntoBytes, FromBytes, KnownLayout, Immutable)]
+pub struct GenAppKey([u8; 16]);
+
+#[allow(dead_code)]
+#[derive(PartialEq, Eq, Clone, Copy, IntoBytes, FromBytes, KnownLayout, Immutable)]
+pub struct McRootKey([u8; 16]);
+
+#[allow(dead_code)]
+#[derive(PartialEq, Eq, Clone, Copy, IntoBytes, FromBytes, KnownLayout, Immutable)]
+pub struct McKEKey([u8; 16]);
+
+#[allow(dead_code)]
+#[derive(PartialEq, Eq, Clone, Copy, IntoBytes, FromBytes, KnownLayout, Immutable)]
+pub struct McKey([u8; 16]);
+
+/// Formats and parses identifiers and keys as hex, EUIs and DevAddr MSB-first as usually
+/// written, i.e. reversed from their on-air order. The alternate form `{:#}` separates bytes by
+/// `'-'` as in EUI-64 notation, and parsing accepts bytes separated by either `'-'` or `':'`.
+macro_rules! impl_hex {
+    ($($ty:ident($len:literal, reversed: $reversed:literal)),* $(,)?) => {$(
+        impl $ty {
+            /// Hex with bytes separated by `separator`, e.g. `':'`
+            pub fn separated(&self, separator: char) -> impl fmt::Display + '_ {
+                hex::Hex::new(&self.0, $reversed).separated(separator)
+            }
+        }
+        impl fmt::Display for $ty {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                let hex = hex::Hex::new(&self.0, $reversed);
+                if f.alternate() {
+                    hex.separated('-').fmt(f)
+                } else {
+                    hex.fmt(f)
+                }
+            }
+        }
+        impl FromStr for $ty {
+            type Err = Error;
+
+            fn from_str(s: &str) -> Result<Self, Error> {
+                hex::decode(s, $reversed)
+                    .map(Self)
+                    .ok_or(Error::Hex { expected: $len })
+            }
+        }
+        #[cfg(feature = "serde")]
+        impl serde::Serialize for $ty {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.collect_str(self)
+            }
+        }
+        #[cfg(feature = "serde")]
+        impl<'de> serde::Deserialize<'de> for $ty {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                deserializer.deserialize_str(hex::FromStrVisitor(core::marker::PhantomData))
+            }
+        }
+    )*};
+}
+impl_hex!(
+    DevEui(8, reversed: true),
+    JoinEui(8, reversed: true),
+    DevAddr(4, reversed: true),
+    MIC(4, reversed: false),
+    NwkSKey(16, reversed: false),
+    AppSKey(16, reversed: false),
+    AppKey(16, reversed: false),
+    GenAppKey(16, reversed: false),
+    McRootKey(16, reversed: false),
+    McKEKey(16, reversed: false),
+    McKey(16, reversed: false),
+);
+
+/// Conversions of EUIs and DevAddr, stored in on-air LSB-first byte order, from and to their
+/// numeric value and MSB-first bytes as usually written. `Debug` and `defmt::Format` print
+/// them MSB-first, as `Display`.
+macro_rules! impl_msb_first {
+    ($($ty:ident($int:ty, $len:literal, $defmt:literal)),* $(,)?) => {$(
+        impl $ty {
+            /// From bytes in on-air LSB-first order
+            pub const fn from_lsb_bytes(bytes: [u8; $len]) -> Self {
+                Self(bytes)
+            }
+
+            /// From bytes in MSB-first order, as usually written
+            pub const fn from_msb_bytes(bytes: [u8; $len]) -> Self {
+                Self(<$int>::from_be_bytes(bytes).to_le_bytes())
+            }
+
+            /// Bytes in on-air LSB-first order
+            pub const fn to_lsb_bytes(&self) -> [u8; $len] {
+                self.0
+            }
+
+            /// Bytes in MSB-first order, as usually written
+            pub const fn to_msb_bytes(&self) -> [u8; $len] {
+                <$int>::from_le_bytes(self.0).to_be_bytes()
+            }
+        }
+        impl From<$int> for $ty {
+            fn from(value: $int) -> Self {
+                Self(value.to_le_bytes())
+            }
+        }
+        impl From<$ty> for $int {
+            fn from(value: $ty) -> Self {
+                <$int>::from_le_bytes(value.0)
+            }
+        }
+        impl fmt::Debug for $ty {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, concat!(stringify!($ty), "({})"), self)
+            }
+        }
+        #[cfg(feature = "defmt")]
+        impl defmt::Format for $ty {
+            fn format(&self, f: defmt::Formatter) {
+                defmt::write!(f, $defmt, stringify!($ty), <$int>::from(*self))
+            }
+        }
+    )*};
+}
+impl_msb_first!(
+    DevEui(u64, 8, "{=str}({=u64:016x})"),
+    JoinEui(u64, 8, "{=str}({=u64:016x})"),
+    DevAddr(u32, 4, "{=str}({=u32:08x})"),
+);
+
+impl fmt::Debug for MIC {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "MIC({self})")
+    }
+}
+impl From<[u8; 4]> for MIC {
+    fn from(bytes: [u8; 4]) -> Self {
+        Self(bytes)
+    }
+}
+
+/// Key construction from bytes in the order they are usually written, and a `Debug` and
+/// `defmt::Format` that do not reveal the key, use `Display` to print it
+macro_rules! impl_key {
+    ($($ty:ident),* $(,)?) => {$(
+        impl From<[u8; 16]> for $ty {
+            fn from(bytes: [u8; 16]) -> Self {
+                Self(bytes)
+            }
+        }
+        impl fmt::Debug for $ty {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(concat!(stringify!($ty), "(..)"))
+            }
+        }
+        #[cfg(feature = "defmt")]
+        impl defmt::Format for $ty {
+            fn format(&self, f: defmt::Formatter) {
+                defmt::write!(f, "{=str}(..)", stringify!($ty))
+            }
+        }
+    )*};
+}
+impl_key!(NwkSKey, AppSKey, AppKey, GenAppKey, McRootKey, McKEKey, McKey);
+
+/// DevNonce as a number
+#[cfg(feature = "serde")]
+impl serde::Serialize for DevNonce {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u16(u16::from_le_bytes(self.0))
+    }
+}
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for DevNonce {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        u16::deserialize(deserializer).map(Self::from)
+    }
+}
+
+/// Implements serde for a bitfield as an object of its fields in camelCase, each field given
+/// with its checked setter so that out of range values are rejected
+#[cfg(feature = "serde")]
+macro_rules! bitfield_serde {
+    ($ty:ident { $($field:ident: $field_ty:ty = $setter:ident),* $(,)? }) => {
+        const _: () = {
+            #[derive(serde::Serialize, serde::Deserialize)]
+            #[serde(rename_all = "camelCase")]
+            struct Repr {
+                $($field: $field_ty),*
+            }
+            impl serde::Serialize for $ty {
+                fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                    Repr { $($field: self.$field()),* }.serialize(serializer)
+                }
+            }
+            impl<'de> serde::Deserialize<'de> for $ty {
+                fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                    let repr = Repr::deserialize(deserializer)?;
+                    let mut value = Self::new();
+                    $(
+                        value.$setter(repr.$field).map_err(|_| {
+                            <D::Error as serde::de::Error>::custom(concat!(
+                                stringify!($field),
+                                " out of range"
+                            ))
+                        })?;
+                    )*
+                    Ok(value)
+                }
+            }
+        };
+    };
+}
+#[cfg(feature = "serde")]
+pub(crate) use bitfield_serde;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_format_parse() {
+        let dev_eui = DevEui::from(0x0102_0304_0506_0708);
+        assert_eq!(dev_eui.as_bytes(), &[8, 7, 6, 5, 4, 3, 2, 1]);
+        assert_eq!(format!("{dev_eui}"), "0102030405060708");
+        assert_eq!(format!("{dev_eui:#}"), "01-02-03-04-05-06-07-08");
+        assert_eq!(
+            format!("{}", dev_eui.separated(':')),
+            "01:02:03:04:05:06:07:08"
+        );
+        assert_eq!(format!("{dev_eui:?}"), "DevEui(0102030405060708)");
+        for s in [
+            "0102030405060708",
+            "01-02-03-04-05-06-07-08",
+            "01:02:03:04:05:06:07:08",
+        ] {
+            assert_eq!(s.parse::<DevEui>(), Ok(dev_eui));
+        }
+        assert_eq!(u64::from(dev_eui), 0x0102_0304_0506_0708);
+        assert_eq!(dev_eui.to_msb_bytes(), [1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(DevEui::from_msb_bytes([1, 2, 3, 4, 5, 6, 7, 8]), dev_eui);
+        assert_eq!(DevEui::from_lsb_bytes(dev_eui.to_lsb_bytes()), dev_eui);
+
+        let dev_addr: DevAddr = "26011BDA".parse().unwrap();
+        assert_eq!(dev_addr.as_bytes(), &[0xda, 0x1b, 0x01, 0x26]);
+        assert_eq!(u32::from(dev_addr), 0x2601_1bda);
+        assert_eq!(format!("{dev_addr}"), "26011bda");
+
+        let key = AppKey::from(core::array::from_fn(|i| i as u8));
+        assert_eq!(format!("{key}"), "000102030405060708090a0b0c0d0e0f");
+        assert_eq!(format!("{key:?}"), "AppKey(..)");
+        assert_eq!("000102030405060708090A0B0C0D0E0F".parse(), Ok(key));
+
+        for s in [
+            "010203040506070",
+            "010203040506070x",
+            "01-02-03-04-05-06-07:08",
+            "01.02.03.04.05.06.07.08",
+            "01-02-03-04-05-06-0708-",
+        ] {
+            assert_eq!(s.parse::<DevEui>(), Err(Error::Hex { expected: 8 }));
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_hex() {
+        let dev_eui = DevEui::read_from_bytes(&[8, 7, 6, 5, 4, 3, 2, 1]).unwrap();
+        let json = serde_json::to_string(&dev_eui).unwrap();
+        assert_eq!(json, r#""0102030405060708""#);
+        let parsed: DevEui = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.as_bytes(), dev_eui.as_bytes());
+        let dev_addr: DevAddr = serde_json::from_str(r#""26011BDA""#).unwrap();
+        assert_eq!(dev_addr.as_bytes(), &[0xda, 0x1b, 0x01, 0x26]);
+
+        let key = AppKey::read_from_bytes(&core::array::from_fn::<u8, 16, _>(|i| i as u8)).unwrap();
+        assert_eq!(
+            serde_json::to_string(&key).unwrap(),
+            r#""000102030405060708090a0b0c0d0e0f""#
+        );
+        assert_eq!(
+            serde_json::to_string(&DevNonce::from(0x1234)).unwrap(),
+            "4660"
+        );
+        assert!(serde_json::from_str::<DevAddr>(r#""26011bd""#).is_err());
+        assert!(serde_json::from_str::<DevAddr>(r#""26011bdx""#).is_err());
+    }
+}